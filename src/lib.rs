@@ -34,7 +34,9 @@ extern crate unicode_normalization;
 
 pub mod pinboard;
 
-pub use crate::pinboard::{CacheState, Pin, PinBuilder, Pinboard, PinboardResult, Tag};
+pub use crate::pinboard::{
+    BookmarkStore, CacheState, MemoryStore, Pin, PinBuilder, Pinboard, PinboardResult, Tag,
+};
 
 // TODO: Use github actions for CI integration tests.
 // TODO: make get_api_response return reqwest::Response so we can use serde_json::from_read
@@ -54,6 +56,9 @@ pub use crate::pinboard::{CacheState, Pin, PinBuilder, Pinboard, PinboardResult,
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
+    use std::sync::Once;
+
+    use crate::pinboard::paths::CONFIG_DIR_ENV;
 
     mod postcard_serde {
         use crate::pinboard::pin::Pin;
@@ -74,7 +79,7 @@ mod tests {
             assert_eq!(612, pins.len());
 
             let buf: Vec<u8> = to_allocvec(&pins).expect("Couldn't serialize lots open");
-            assert_eq!(114_293, buf.len());
+            assert_eq!(155_909, buf.len());
 
             let mut dir = env::temp_dir();
             dir.push("test_postcard_serde-vec.bin");
@@ -102,7 +107,7 @@ mod tests {
             let n = reader
                 .read_to_end(&mut buf)
                 .expect("Couldn't read deserialized data!");
-            assert_eq!(114_293, n);
+            assert_eq!(155_909, n);
 
             let pins: Vec<Pin> = from_bytes(buf.as_slice()).unwrap();
             assert_eq!(612, pins.len());
@@ -147,17 +152,18 @@ mod tests {
                 "https://danielkeep.github.io/tlborm/book/README.html",
                 "The Little Book of Rust Macros",
             )
-            .tags("Rust macros")
-            .toread("yes")
-            .shared("no")
+            .tags(["Rust", "macros"])
+            .toread(true)
+            .shared(false)
             .description("WoW!!!")
-            .into_pin();
+            .into_pin()
+            .expect("valid url");
             pin.time = Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap();
 
             let mut buf: Vec<u8> = Vec::new();
             pin.serialize(&mut Serializer::new(&mut buf))
                 .expect("Couldn't serialize");
-            assert_eq!(133, buf.len());
+            assert_eq!(135, buf.len());
 
             let mut dir = env::temp_dir();
             dir.push("test_rmp_serde.bin");
@@ -189,14 +195,13 @@ mod tests {
                 pin.time(),
                 Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap()
             );
-            assert_eq!(pin.tags, "Rust macros");
-            assert_eq!("yes", &pin.toread);
+            assert_eq!(pin.tags, vec!["Rust", "macros"]);
+            assert!(pin.toread);
             assert_eq!("WoW!!!", &pin.extended.expect("pin.extended can't be None"));
             assert_eq!(
-                &pin.url,
+                pin.url,
                 Url::parse("https://danielkeep.github.io/tlborm/book/README.html")
                     .expect("impossible")
-                    .as_str()
             );
             fs::remove_file(dir).expect("Can't delete temp test file");
         }
@@ -212,7 +217,7 @@ mod tests {
             let mut buf: Vec<u8> = Vec::new();
             pins.serialize(&mut Serializer::new(&mut buf))
                 .expect("Couldn't serialize lots of pins");
-            assert_eq!(115_671, buf.len());
+            assert_eq!(157_287, buf.len());
 
             let mut dir = env::temp_dir();
             dir.push("test_rmp_serde-vec.bin");
@@ -278,12 +283,11 @@ mod tests {
                 pin.time(),
                 Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap()
             );
-            assert_eq!(pin.tags, "Rust macros");
+            assert_eq!(pin.tags, vec!["Rust", "macros"]);
             assert_eq!(
-                &pin.url,
+                pin.url,
                 Url::parse("https://danielkeep.github.io/tlborm/book/README.html")
                     .expect("impossible!")
-                    .as_str()
             );
 
             let pin: Result<Pin, _> = from_str(include_str!("../tests/PIN2.json"));
@@ -294,12 +298,11 @@ mod tests {
                 pin.time(),
                 Utc.with_ymd_and_hms(2017, 10, 9, 7, 59, 36).unwrap()
             );
-            assert_eq!(pin.tags, "git ctags vim");
+            assert_eq!(pin.tag_string(), "git ctags vim");
             assert_eq!(
-                &pin.url,
+                pin.url,
                 Url::parse("http://tbaggery.com/2011/08/08/effortless-ctags-with-git.html")
                     .expect("impossible")
-                    .as_str()
             );
         }
 
@@ -353,22 +356,32 @@ mod tests {
                 "https://danielkeep.github.io/tlborm/book/README.html",
                 "The Little Book of Rust Macros",
             )
-            .tags("Rust macros")
-            .toread("no")
-            .shared("no")
-            .into_pin();
+            .tags(["Rust", "macros"])
+            .toread(false)
+            .shared(false)
+            .into_pin()
+            .expect("valid url");
             pin.time = Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap();
             let s = to_string(&pin).expect("Couldn't serialize");
             assert_eq!(
                 r#"{"href":"https://danielkeep.github.io/tlborm/book/README.html",
 "description":"The Little Book of Rust Macros","tags":"Rust macros","shared":"no"
-,"toread":"no","extended":null,"time":"2017-05-22T17:46:54Z"}"#
+,"toread":"no","extended":null,"meta":null,"hash":null,"time":"2017-05-22T17:46:54Z"}"#
                     .replace('\n', ""),
                 s
             );
         }
     }
 
+    /// Points the config folder at an empty temp folder, so `Pinboard::new` doesn't read the
+    /// settings & tag aliases of the machine running the tests.
+    pub(super) fn isolate_config_dir() {
+        static ONCE: Once = Once::new();
+        ONCE.call_once(|| std::env::set_var(CONFIG_DIR_ENV, rand_temp_path()));
+    }
+
+    // `into_path` is deprecated in newer tempfile releases but works with all of them
+    #[allow(deprecated)]
     pub(super) fn rand_temp_path() -> PathBuf {
         tempfile::Builder::new()
             .prefix("rusty_pin_test_")
@@ -0,0 +1,212 @@
+//! Helpers to work with more than one Pinboard account.
+//!
+//! When no cache directory is given, [`Pinboard::new`] keeps each account's cache in its own
+//! folder named after the username part of the `user:TOKEN` auth token, e.g.
+//! `~/.cache/rusty-pin/user` (see [`paths`](super::paths)).
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use thiserror::Error;
+
+use super::cached_data::CachedData;
+use super::paths;
+use super::pin::Pin;
+use super::store::BookmarkStore;
+use super::Pinboard;
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Error)]
+pub enum AccountError {
+    #[error("invalid account name: {0:?}")]
+    InvalidName(String),
+}
+
+/// A pin found by [`search_accounts`] along with the account it belongs to.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, PartialEq, Eq)]
+pub struct AccountPin<'a> {
+    pub user: Option<&'a str>,
+    pub pin: &'a Pin<'a>,
+}
+
+/// Returns the cache folder of `user` within `root` (or the default cache folder).
+///
+/// # Errors
+///
+/// Returns an error if `user` can't be used as a folder name or there is no default cache
+/// folder.
+pub fn account_cache_dir<P: AsRef<Path>>(
+    user: &str,
+    root: Option<P>,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    debug!("account_cache_dir: starting.");
+    if user.is_empty()
+        || user == "."
+        || user == ".."
+        || user.contains(|c| std::path::is_separator(c) || c == '\0')
+    {
+        return Err(Box::new(AccountError::InvalidName(user.to_string())));
+    }
+    let root = match root {
+        Some(p) => p.as_ref().to_path_buf(),
+        None => paths::default_cache_dir()?,
+    };
+    Ok(root.join(user))
+}
+
+/// Lists usernames of accounts that have cached data within `root` (or the default cache
+/// folder), sorted by name.
+///
+/// # Errors
+///
+/// Returns an error if `root` exists but can't be read.
+pub fn cached_accounts<P: AsRef<Path>>(
+    root: Option<P>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    debug!("cached_accounts: starting.");
+    let root = match root {
+        Some(p) => p.as_ref().to_path_buf(),
+        None => paths::default_cache_dir()?,
+    };
+    if !root.is_dir() {
+        return Ok(vec![]);
+    }
+    let mut users = vec![];
+    for entry in fs::read_dir(root)? {
+        let path = entry?.path();
+        if path.is_dir() && CachedData::has_cache_files(&path) {
+            if let Some(user) = path.file_name().and_then(|n| n.to_str()) {
+                users.push(user.to_string());
+            }
+        }
+    }
+    users.sort();
+    Ok(users)
+}
+
+/// Removes cached data of `user` within `root` (or the default cache folder).
+///
+/// Only cache files are deleted (see [`CachedData::clear`]), the account's folder is removed
+/// too if nothing else is left in it. Returns false if there was no cached data for `user`.
+/// # Errors
+///
+/// Returns an error if `user` is not a valid account name or its files can't be removed.
+pub fn remove_cached_account<P: AsRef<Path>>(
+    user: &str,
+    root: Option<P>,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    debug!("remove_cached_account: starting.");
+    let dir = account_cache_dir(user, root)?;
+    if !dir.is_dir() || !CachedData::has_cache_files(&dir) {
+        return Ok(false);
+    }
+    CachedData::init(Some(&dir))?.clear()?;
+    if fs::read_dir(&dir)?.next().is_none() {
+        fs::remove_dir(&dir)?;
+    }
+    Ok(true)
+}
+
+/// Searches bookmarks of all `pinboards` using each one's search settings.
+///
+/// Results are grouped by account in the same order as `pinboards`.
+/// # Errors
+///
+/// Returns the first error any of the searches returns.
+pub fn search_accounts<'a, 'api, 'pin, B: BookmarkStore<'pin>>(
+    pinboards: &'a [Pinboard<'api, 'pin, B>],
+    query: &str,
+) -> Result<Option<Vec<AccountPin<'a>>>, Box<dyn std::error::Error>> {
+    debug!("search_accounts: starting.");
+    let mut found = vec![];
+    for pinboard in pinboards {
+        if let Some(pins) = pinboard.search_items(query)? {
+            let user = pinboard.user();
+            found.extend(pins.into_iter().map(|pin| AccountPin { user, pin }));
+        }
+    }
+    if found.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(found))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::cached_data::{CachedPin, CachedTag};
+    use crate::pinboard::pin::PinBuilder;
+    use crate::pinboard::store::MemoryStore;
+    use crate::tests::rand_temp_path;
+
+    #[test]
+    fn list_remove_accounts() {
+        let _ = env_logger::try_init();
+        debug!("list_remove_accounts: starting");
+        let root = rand_temp_path();
+        assert!(cached_accounts(Some(&root)).unwrap().is_empty());
+
+        for user in ["bob", "alice"] {
+            let dir = account_cache_dir(user, Some(&root)).unwrap();
+            let mut data = CachedData::init(Some(&dir)).unwrap();
+            data.replace_all(vec![], vec![]).unwrap();
+        }
+        // Folders without cached files aren't accounts
+        fs::create_dir_all(root.join("empty")).unwrap();
+        assert_eq!(vec!["alice", "bob"], cached_accounts(Some(&root)).unwrap());
+
+        assert!(remove_cached_account("bob", Some(&root)).unwrap());
+        assert!(!root.join("bob").exists());
+        assert!(!remove_cached_account("bob", Some(&root)).unwrap());
+        assert_eq!(vec!["alice"], cached_accounts(Some(&root)).unwrap());
+
+        // Other files are left alone
+        assert!(!remove_cached_account("empty", Some(&root)).unwrap());
+        assert!(root.join("empty").is_dir());
+        let other = root.join("alice").join("notes.txt");
+        fs::write(&other, "keep me").unwrap();
+        assert!(remove_cached_account("alice", Some(&root)).unwrap());
+        assert!(other.is_file());
+        assert!(cached_accounts(Some(&root)).unwrap().is_empty());
+
+        for user in ["", "..", "a/b"] {
+            assert!(remove_cached_account(user, Some(&root)).is_err());
+        }
+    }
+
+    #[test]
+    fn search_multiple_accounts() {
+        let _ = env_logger::try_init();
+        debug!("search_multiple_accounts: starting");
+        let board = |token: &'static str, url: &'static str| {
+            let pin = PinBuilder::new(url, "rust tutorial")
+                .into_pin()
+                .expect("valid url");
+            let store = MemoryStore::with_data(
+                vec![CachedPin::new(pin)],
+                vec![CachedTag::new(crate::Tag::new("rust".to_string(), 1))],
+            );
+            let mut pinboard = Pinboard::with_store(token, store).unwrap().pinboard;
+            pinboard.enable_fuzzy_search(false);
+            pinboard
+        };
+        let pinboards = vec![
+            board("alice:TOKEN", "https://a.com/"),
+            board("bob:TOKEN", "https://b.com/"),
+        ];
+        let found = search_accounts(&pinboards, "tutorial")
+            .unwrap()
+            .expect("both accounts have a match");
+        let found: Vec<(Option<&str>, &str)> =
+            found.iter().map(|p| (p.user, p.pin.url.as_ref())).collect();
+        assert_eq!(
+            vec![
+                (Some("alice"), "https://a.com/"),
+                (Some("bob"), "https://b.com/")
+            ],
+            found
+        );
+        assert!(search_accounts(&pinboards, "missing").unwrap().is_none());
+    }
+}
@@ -0,0 +1,123 @@
+//! Adding bookmarks whose url may already be bookmarked.
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use super::pin::{Pin, UnsetFlags};
+
+/// What [`Pinboard::add_pin_with_mode`](super::Pinboard::add_pin_with_mode) does when the url
+/// is already bookmarked.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum AddMode {
+    /// Replaces the existing bookmark, keeping its creation time.
+    #[default]
+    Replace,
+    /// Fails with [`AddError::AlreadyExists`].
+    FailIfExists,
+    /// Keeps the existing bookmark, adding new tags and appending the new description.
+    Merge,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AddError {
+    #[error("bookmark already exists: {0}")]
+    AlreadyExists(String),
+}
+
+/// Merges `new` into `existing`.
+///
+/// The existing title & creation time are kept, tags missing from `existing` are added and a
+/// new description is appended to the existing one. Flags explicitly set on `new` win.
+pub(crate) fn merge_pins<'pin>(existing: &Pin, new: Pin<'pin>) -> Pin<'pin> {
+    debug!("merge_pins: starting");
+    let mut tags = existing.tags.clone();
+    for tag in new.tags {
+        if !tags.iter().any(|t| t.to_lowercase() == tag.to_lowercase()) {
+            tags.push(tag);
+        }
+    }
+
+    let existing_extended = existing.extended.as_deref().unwrap_or_default();
+    let new_extended = new.extended.as_deref().unwrap_or_default();
+    let extended = if new_extended.is_empty() || existing_extended.contains(new_extended) {
+        existing
+            .extended
+            .as_ref()
+            .map(|e| Cow::Owned(e.to_string()))
+    } else if existing_extended.is_empty() {
+        new.extended
+    } else {
+        Some(Cow::Owned(format!("{existing_extended}\n\n{new_extended}")))
+    };
+
+    let title = if existing.title.trim().is_empty() {
+        new.title
+    } else {
+        Cow::Owned(existing.title.to_string())
+    };
+
+    Pin {
+        url: new.url,
+        title,
+        tags,
+        shared: if new.unset_flags.shared {
+            existing.shared
+        } else {
+            new.shared
+        },
+        toread: if new.unset_flags.toread {
+            existing.toread
+        } else {
+            new.toread
+        },
+        extended,
+        meta: None,
+        hash: None,
+        time: existing.time,
+        unset_flags: UnsetFlags::default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn merge_two_pins() {
+        let _ = env_logger::try_init();
+        debug!("merge_two_pins: starting");
+        let mut existing = PinBuilder::new("https://example.com/", "Existing")
+            .tags(["rust", "Lang"])
+            .description("old notes")
+            .shared(true)
+            .toread(false)
+            .into_pin()
+            .expect("valid url");
+        existing.time -= chrono::Duration::days(10);
+
+        let new = PinBuilder::new("https://example.com/", "New")
+            .tags(["lang", "book"])
+            .description("new notes")
+            .toread(true)
+            .into_pin()
+            .expect("valid url");
+        let merged = merge_pins(&existing, new);
+        assert_eq!("Existing", merged.title);
+        assert_eq!(vec!["rust", "Lang", "book"], merged.tags);
+        assert_eq!(Some("old notes\n\nnew notes"), merged.extended.as_deref());
+        assert!(merged.shared);
+        assert!(merged.toread);
+        assert_eq!(existing.time, merged.time);
+        assert_eq!(UnsetFlags::default(), merged.unset_flags);
+
+        // Descriptions aren't repeated
+        let new = PinBuilder::new("https://example.com/", "")
+            .description("old notes")
+            .into_pin()
+            .expect("valid url");
+        let merged = merge_pins(&existing, new);
+        assert_eq!(Some("old notes"), merged.extended.as_deref());
+        assert!(!merged.toread);
+    }
+}
@@ -0,0 +1,219 @@
+//! User-defined tag aliases (`js` → `javascript`) used to expand tag searches and to
+//! canonicalize tags of new pins.
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use unicode_normalization::UnicodeNormalization;
+
+use super::paths::{self, PathError};
+use super::pin::Pin;
+
+/// Name of the aliases file within the config folder.
+pub const ALIASES_FN: &str = "aliases.json";
+
+fn normalize(tag: &str) -> String {
+    tag.nfkd().collect::<String>().to_lowercase()
+}
+
+/// Maps aliases to the tag they stand for, saved as a JSON object (`{"js": "javascript"}`).
+///
+/// Aliases are matched after unicode normalization, ignoring case.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TagAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl TagAliases {
+    #[must_use]
+    pub fn new() -> Self {
+        TagAliases::default()
+    }
+
+    /// Makes `alias` stand for `tag`, replacing any previous definition of `alias`.
+    pub fn insert<A: AsRef<str>, T: Into<String>>(&mut self, alias: A, tag: T) {
+        let alias = alias.as_ref();
+        self.aliases.retain(|a, _| normalize(a) != normalize(alias));
+        self.aliases.insert(alias.to_string(), tag.into());
+    }
+
+    /// Removes `alias`, returning the tag it stood for.
+    pub fn remove<A: AsRef<str>>(&mut self, alias: A) -> Option<String> {
+        let alias = normalize(alias.as_ref());
+        let key = self.aliases.keys().find(|a| normalize(a) == alias)?.clone();
+        self.aliases.remove(&key)
+    }
+
+    /// Tag that `alias` stands for, if it is an alias.
+    #[must_use]
+    pub fn canonical<A: AsRef<str>>(&self, alias: A) -> Option<&str> {
+        let alias = normalize(alias.as_ref());
+        self.aliases
+            .iter()
+            .find(|(a, _)| normalize(a) == alias)
+            .map(|(_, tag)| tag.as_str())
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.aliases.is_empty()
+    }
+
+    /// `(alias, tag)` pairs sorted by alias.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.aliases.iter().map(|(a, t)| (a.as_str(), t.as_str()))
+    }
+
+    /// Normalized `query` along with the tag it stands for and all other aliases of that tag.
+    pub(crate) fn expand(&self, query: &str) -> Vec<String> {
+        let query = normalize(query);
+        let canonical = self
+            .canonical(&query)
+            .map_or_else(|| query.clone(), normalize);
+        let mut expanded = vec![query];
+        let related = self
+            .aliases
+            .iter()
+            .filter(|(_, tag)| normalize(tag) == canonical)
+            .map(|(alias, _)| normalize(alias));
+        for tag in std::iter::once(canonical.clone()).chain(related) {
+            if !expanded.contains(&tag) {
+                expanded.push(tag);
+            }
+        }
+        expanded
+    }
+
+    /// Replaces aliases in `tags` with the tags they stand for, dropping duplicates.
+    pub fn canonicalize_tags<I, T>(&self, tags: I) -> Vec<String>
+    where
+        I: IntoIterator<Item = T>,
+        T: AsRef<str>,
+    {
+        let mut canonical: Vec<String> = vec![];
+        for tag in tags {
+            let tag = self.canonical(&tag).unwrap_or(tag.as_ref());
+            if !canonical.iter().any(|t| normalize(t) == normalize(tag)) {
+                canonical.push(tag.to_string());
+            }
+        }
+        canonical
+    }
+
+    /// Returns `pin` with its tags canonicalized, see [`TagAliases::canonicalize_tags`].
+    #[must_use]
+    pub fn canonicalize_pin<'pin>(&self, mut pin: Pin<'pin>) -> Pin<'pin> {
+        pin.tags = self.canonicalize_tags(&pin.tags);
+        pin
+    }
+
+    /// Path of the aliases file shared by all tools: `aliases.json` within
+    /// [`paths::default_config_dir`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found.
+    pub fn aliases_file() -> Result<PathBuf, PathError> {
+        Ok(paths::default_config_dir()?.join(ALIASES_FN))
+    }
+
+    /// Reads aliases from `path`, a missing file has no aliases.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        debug!("load_from: starting.");
+        if !path.as_ref().exists() {
+            return Ok(TagAliases::new());
+        }
+        let fp = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(fp))?)
+    }
+
+    /// Writes aliases to `path`, creating its folder if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("save_to: starting.");
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let fp = File::create(path)?;
+        serde_json::to_writer_pretty(BufWriter::new(fp), self)?;
+        Ok(())
+    }
+
+    /// Reads the shared aliases file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found or the file can't be read.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        TagAliases::load_from(TagAliases::aliases_file()?)
+    }
+
+    /// Reads the shared aliases file, without aliases if it can't be read.
+    pub(crate) fn load_shared() -> Self {
+        TagAliases::load().unwrap_or_else(|e| {
+            info!("couldn't load tag aliases: {}", e);
+            TagAliases::new()
+        })
+    }
+
+    /// Writes the shared aliases file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found or the file can't be written.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(TagAliases::aliases_file()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::rand_temp_path;
+
+    #[test]
+    fn tag_aliases() {
+        let _ = env_logger::try_init();
+        debug!("tag_aliases: starting");
+        let mut aliases = TagAliases::new();
+        aliases.insert("js", "javascript");
+        aliases.insert("ecmascript", "javascript");
+        aliases.insert("golang", "go");
+        aliases.insert("JS", "JavaScript");
+
+        assert_eq!(Some("JavaScript"), aliases.canonical("js"));
+        assert_eq!(None, aliases.canonical("javascript"));
+        assert_eq!(vec!["js", "javascript", "ecmascript"], aliases.expand("JS"));
+        assert_eq!(
+            vec!["javascript", "js", "ecmascript"],
+            aliases.expand("javascript")
+        );
+        assert_eq!(vec!["rust"], aliases.expand("rust"));
+
+        assert_eq!(
+            vec!["JavaScript", "go", "rust"],
+            aliases.canonicalize_tags(["js", "golang", "javascript", "rust", "go"])
+        );
+
+        let path = rand_temp_path().join(ALIASES_FN);
+        assert!(TagAliases::load_from(&path)
+            .expect("missing file")
+            .is_empty());
+        aliases.save_to(&path).expect("couldn't save aliases");
+        assert_eq!(
+            aliases,
+            TagAliases::load_from(&path).expect("couldn't load aliases")
+        );
+        assert_eq!(Some("go".to_string()), aliases.remove("GOLANG"));
+        assert_eq!(None, aliases.canonical("golang"));
+    }
+}
@@ -10,6 +10,9 @@ use env_logger;
 
 use std::collections::HashMap;
 use std::io::Read;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use super::pin::Pin;
 use super::tag::Tag;
@@ -26,6 +29,12 @@ use mockito;
 #[allow(deprecated)]
 const BASE_URL: &str = mockito::SERVER_URL;
 
+/// Minimum time between throttled calls, Pinboard asks clients to wait 3 seconds between calls.
+#[cfg(not(test))]
+const CALL_INTERVAL: Duration = Duration::from_secs(3);
+#[cfg(test)]
+const CALL_INTERVAL: Duration = Duration::ZERO;
+
 /// Struct to hold stringify results Pinboard API returns.
 /// Sometimes it returns a json key of "result_code" & sometimes just "result"!!!
 #[allow(clippy::doc_markdown)]
@@ -55,9 +64,19 @@ struct UpdateTime {
     datetime: DateTime<Utc>,
 }
 
+/// Response of posts/get.
+#[derive(Deserialize, Debug)]
+struct Posts<'pin> {
+    posts: Vec<Pin<'pin>>,
+}
+
 #[derive(Debug, Clone)]
 pub struct Api<'api> {
     auth_token: Cow<'api, str>,
+    /// Shared by clones so they're throttled together.
+    last_call: Arc<Mutex<Option<Instant>>>,
+    /// Last time returned by [`Api::recent_update`].
+    update_time: Arc<Mutex<Option<DateTime<Utc>>>>,
 }
 
 #[allow(clippy::module_name_repetitions)]
@@ -89,11 +108,42 @@ impl<'api, 'pin> Api<'api> {
         let _ = env_logger::try_init();
         Api {
             auth_token: auth_token.into(),
+            last_call: Arc::default(),
+            update_time: Arc::default(),
         }
     }
 
+    /// Blocks until `CALL_INTERVAL` has passed since the previous throttled call.
+    ///
+    /// Used by operations that send many requests in a row.
+    pub fn throttle(&self) {
+        let mut last_call = self
+            .last_call
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+        if let Some(wait) = last_call.and_then(|t| CALL_INTERVAL.checked_sub(t.elapsed())) {
+            debug!("throttle: waiting {:?}", wait);
+            thread::sleep(wait);
+        }
+        *last_call = Some(Instant::now());
+    }
+
+    /// Username part of the `user:TOKEN` auth token.
+    pub fn user(&self) -> Option<&str> {
+        self.auth_token
+            .split_once(':')
+            .map(|(user, _)| user)
+            .filter(|user| !user.is_empty())
+    }
+
     pub fn all_pins(&self) -> Result<Vec<Pin<'pin>>, Box<dyn std::error::Error>> {
         debug!("all_pins: starting.");
+        self.all_pins_counted().map(|(pins, _)| pins)
+    }
+
+    /// Fetches all bookmarks along with the number of bookmarks that couldn't be parsed.
+    pub fn all_pins_counted(&self) -> Result<(Vec<Pin<'pin>>, usize), Box<dyn std::error::Error>> {
+        debug!("all_pins_counted: starting.");
         let res =
             self.get_api_response([BASE_URL, "/posts/all"].concat().as_str(), HashMap::new())?;
         debug!("  received all bookmarks");
@@ -109,7 +159,6 @@ impl<'api, 'pin> Api<'api> {
         let pins: Vec<Pin> = v
             .drain(..)
             .filter_map(|line| serde_json::from_value(line).ok())
-            .filter(|p: &Pin| Url::parse(&p.url).is_ok())
             .collect();
         if pins.len() == v_len {
             info!("parsed all bookmarks. total: {}", pins.len());
@@ -121,7 +170,8 @@ impl<'api, 'pin> Api<'api> {
             );
         }
 
-        Ok(pins)
+        let dropped = v_len - pins.len();
+        Ok((pins, dropped))
     }
 
     pub fn suggest_tags<T: AsRef<str>>(
@@ -155,20 +205,27 @@ impl<'api, 'pin> Api<'api> {
             })?)
     }
 
-    pub fn add_url(&self, p: Pin) -> Result<(), Box<dyn std::error::Error>> {
+    /// Adds `p`, replacing an existing bookmark of the same url only if `replace` is true.
+    ///
+    /// The bookmark's creation time is set to `p.time`.
+    pub fn add_url(&self, p: Pin, replace: bool) -> Result<(), Box<dyn std::error::Error>> {
         debug!("add_url: starting.");
-        let url: &str = &p.url;
-        let extended = &p.extended.unwrap_or_default();
+        let url: &str = p.url.as_str();
+        let tags = p.tag_string();
+        let dt = p.time.to_rfc3339_opts(SecondsFormat::Secs, true);
+        let extended = p.extended.as_deref().unwrap_or_default();
+        let yes_no = |flag: bool| if flag { "yes" } else { "no" };
         let mut map = HashMap::new();
         debug!(" url: {}", url);
 
         map.insert("url", url);
         map.insert("description", &p.title);
-        map.insert("tags", &p.tags);
-        map.insert("toread", &p.toread);
+        map.insert("tags", &tags);
+        map.insert("toread", yes_no(p.toread));
         map.insert("extended", extended);
-        map.insert("shared", &p.shared);
-        map.insert("replace", "yes");
+        map.insert("shared", yes_no(p.shared));
+        map.insert("replace", yes_no(replace));
+        map.insert("dt", &dt);
 
         debug!("Sending payload to: {}/posts/add\n\t{:?}", BASE_URL, map);
         self.get_api_response([BASE_URL, "/posts/add"].concat().as_str(), map)
@@ -179,6 +236,22 @@ impl<'api, 'pin> Api<'api> {
             .and_then(self::ApiResult::ok)
     }
 
+    /// Fetches the bookmark of `url` from the server, `None` if it isn't bookmarked.
+    pub fn get_pin<T: AsRef<str>>(
+        &self,
+        url: T,
+    ) -> Result<Option<Pin<'pin>>, Box<dyn std::error::Error>> {
+        debug!("get_pin: starting.");
+        let mut query = HashMap::new();
+        query.insert("url", url.as_ref());
+        self.get_api_response([BASE_URL, "/posts/get"].concat().as_str(), query)
+            .and_then(|res| {
+                serde_json::from_str::<Posts>(&res)
+                    .map_err(|e| From::from(ApiError::SerdeError(e.to_string())))
+            })
+            .map(|posts| posts.posts.into_iter().next())
+    }
+
     pub fn tag_rename<T: AsRef<str>>(
         &self,
         old: T,
@@ -278,7 +351,22 @@ impl<'api, 'pin> Api<'api> {
         .and_then(|res| {
             serde_json::from_str(&res).map_err(|e| From::from(ApiError::SerdeError(e.to_string())))
         })
-        .map(|date: UpdateTime| date.datetime)
+        .map(|date: UpdateTime| {
+            *self
+                .update_time
+                .lock()
+                .unwrap_or_else(std::sync::PoisonError::into_inner) = Some(date.datetime);
+            date.datetime
+        })
+    }
+
+    /// Server's update time as last returned by [`Api::recent_update`], without asking the
+    /// server again.
+    pub fn last_update_time(&self) -> Option<DateTime<Utc>> {
+        *self
+            .update_time
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
     }
 
     fn add_auth_token<T: AsRef<str>>(&self, url: T) -> Url {
@@ -458,6 +546,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn get_a_pin() {
+        let _ = env_logger::try_init();
+        debug!("get_a_pin: starting.");
+        let mock_get = |url: &str, body: &str| {
+            mockito::mock(
+                "GET",
+                mockito::Matcher::Regex(r"^/posts/get.*$".to_string()),
+            )
+            .match_query(mockito::Matcher::UrlEncoded(
+                "url".to_string(),
+                url.to_string(),
+            ))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(body)
+            .create()
+        };
+        let _m1 = mock_get(
+            "http://tbaggery.com/",
+            r#"{"date":"2017-10-09T07:59:36Z","user":"user","posts":[{"href":"http:\/\/tbaggery.com\/",
+"description":"tbaggery","extended":"","meta":"5a1f","hash":"9e2c","time":"2017-10-09T07:59:36Z",
+"shared":"no","toread":"yes","tags":"git ctags vim"}]}"#,
+        );
+        let _m2 = mock_get(
+            "http://not.bookmarked/",
+            r#"{"date":"2017-10-09T07:59:36Z","user":"user","posts":[]}"#,
+        );
+        let api = Api::new(include_str!("api_token.txt"));
+        let pin = api
+            .get_pin("http://tbaggery.com/")
+            .expect("couldn't get pin")
+            .expect("pin should exist");
+        assert_eq!(vec!["git", "ctags", "vim"], pin.tags);
+        assert!(pin.toread && !pin.shared);
+        assert_eq!(Some("5a1f"), pin.meta.as_deref());
+        assert!(api
+            .get_pin("http://not.bookmarked/")
+            .expect("couldn't get pin")
+            .is_none());
+    }
+
     #[test]
     fn delete_api_test() {
         let _ = env_logger::try_init();
@@ -502,28 +632,24 @@ mod tests {
         let _m1 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
         let api = Api::new(include_str!("api_token.txt"));
         let p = PinBuilder::new(TEST_URL, "test bookmark/pin")
-            .tags("tagestan what")
+            .tags(["tagestan", "what"])
             .description("russian website!")
-            .shared("yes")
-            .into_pin();
-        let res = api.add_url(p);
+            .shared(true)
+            .into_pin()
+            .expect("valid url");
+        let res = api.add_url(p, true);
         res.expect("Error in adding a pin.");
 
         {
-            // Adding a malformed url
-            let _m1 = start_mockito_server(
-                r"^/posts/add.+bad_url.*$",
-                200,
-                r#"{"result_code":"missing url"}"#,
-            );
-            let p = PinBuilder::new(":// bad_url/#", "test bookmark/pin")
-                .tags("tagestan what")
-                .description("russian website!")
-                .shared("yes")
-                .into_pin();
+            // Server rejecting a bookmark
+            let _m1 =
+                start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"missing url"}"#);
+            let p = PinBuilder::new(TEST_URL, "test bookmark/pin")
+                .into_pin()
+                .expect("valid url");
             let r = api
-                .add_url(p)
-                .expect_err("server should not have accepted malformed url");
+                .add_url(p, true)
+                .expect_err("server should not have accepted the bookmark");
             assert_eq!("missing url", r.to_string());
         }
     }
@@ -598,6 +724,15 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_api_user() {
+        let _ = env_logger::try_init();
+        debug!("test_api_user: starting.");
+        assert_eq!(Some("alice"), Api::new("alice:ABC123").user());
+        assert_eq!(None, Api::new(":ABC123").user());
+        assert_eq!(None, Api::new("ABC123").user());
+    }
+
     #[test]
     fn test_all_pins() {
         let _ = env_logger::try_init();
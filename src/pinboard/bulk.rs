@@ -0,0 +1,117 @@
+//! Editing many bookmarks at once.
+use url::Url;
+
+use super::patch::{PatchError, PinPatch};
+use super::pin::Pin;
+
+/// A change applied to every bookmark of a
+/// [`Pinboard::bulk_edit`](super::Pinboard::bulk_edit) selection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BulkOp {
+    /// Adds the tag to bookmarks that don't have it.
+    AddTag(String),
+    /// Removes the tag (ignoring case).
+    RemoveTag(String),
+    /// Replaces tag `from` (ignoring case) with `to` on bookmarks that have `from`.
+    ReplaceTag {
+        from: String,
+        to: String,
+    },
+    SetToread(bool),
+    SetShared(bool),
+}
+
+/// What happened to a bookmark of a bulk edit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BulkStatus {
+    /// The edited bookmark was sent to the server.
+    Updated,
+    /// The operations didn't change the bookmark so nothing was sent.
+    Unchanged,
+}
+
+/// Result of a bulk edit for one bookmark.
+#[derive(Debug)]
+pub struct BulkEditOutcome {
+    pub url: Url,
+    pub status: Result<BulkStatus, Box<dyn std::error::Error>>,
+}
+
+/// A cached bookmark before & after the operations, or why they can't be applied.
+pub(crate) type BulkEdit<'pin> = Result<(Pin<'pin>, Pin<'pin>), PatchError>;
+
+/// Builds the patch that applies `op` to `pin`.
+fn patch_for(pin: &Pin, op: &BulkOp) -> PinPatch {
+    let has_tag = |tag: &str| {
+        pin.tags
+            .iter()
+            .any(|t| t.to_lowercase() == tag.to_lowercase())
+    };
+    let patch = PinPatch::new();
+    match op {
+        BulkOp::AddTag(tag) => patch.add_tag(tag.as_str()),
+        BulkOp::RemoveTag(tag) => patch.remove_tag(tag.as_str()),
+        BulkOp::ReplaceTag { from, to } if has_tag(from) => {
+            patch.remove_tag(from.as_str()).add_tag(to.as_str())
+        }
+        BulkOp::ReplaceTag { .. } => patch,
+        BulkOp::SetToread(toread) => patch.toread(*toread),
+        BulkOp::SetShared(shared) => patch.shared(*shared),
+    }
+}
+
+/// Applies `ops` to `pin` one after the other, in the given order.
+pub(crate) fn apply_ops<'pin>(pin: &Pin<'pin>, ops: &[BulkOp]) -> Result<Pin<'pin>, PatchError> {
+    ops.iter()
+        .try_fold(pin.clone(), |pin, op| patch_for(&pin, op).apply(&pin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn bulk_ops_patch() {
+        let _ = env_logger::try_init();
+        debug!("bulk_ops_patch: starting");
+        let pin = PinBuilder::new("https://example.com/", "Example")
+            .tags(["rust", "todo"])
+            .toread(true)
+            .into_pin()
+            .expect("valid url");
+        let ops = [
+            BulkOp::ReplaceTag {
+                from: "Rust".to_string(),
+                to: "rust-lang".to_string(),
+            },
+            BulkOp::ReplaceTag {
+                from: "go".to_string(),
+                to: "golang".to_string(),
+            },
+            BulkOp::RemoveTag("todo".to_string()),
+            BulkOp::AddTag("book".to_string()),
+            BulkOp::SetToread(false),
+        ];
+        let patched = apply_ops(&pin, &ops).expect("valid patch");
+        assert_eq!(vec!["rust-lang", "book"], patched.tags);
+        assert!(!patched.toread);
+        assert!(!patched.shared);
+
+        // Operations that don't change anything
+        let ops = [
+            BulkOp::AddTag("TODO".to_string()),
+            BulkOp::SetToread(true),
+            BulkOp::RemoveTag("go".to_string()),
+        ];
+        assert_eq!(pin, apply_ops(&pin, &ops).expect("valid patch"));
+
+        // Operations apply in order
+        let add = BulkOp::AddTag("x".to_string());
+        let remove = BulkOp::RemoveTag("x".to_string());
+        let patched = apply_ops(&pin, &[add.clone(), remove.clone()]).expect("valid patch");
+        assert_eq!(vec!["rust", "todo"], patched.tags);
+        let patched = apply_ops(&pin, &[remove, add]).expect("valid patch");
+        assert_eq!(vec!["rust", "todo", "x"], patched.tags);
+    }
+}
@@ -3,21 +3,40 @@
 use super::*;
 use env_logger;
 use std::fs;
+#[cfg(not(feature = "sqlite"))]
 use std::io::Write;
+#[cfg(not(feature = "sqlite"))]
 use std::io::{BufReader, BufWriter};
 
+#[cfg(not(feature = "sqlite"))]
 use crate::rmps;
 use serde::Serialize;
 
 use self::tag::Tag;
+use super::paths;
 use super::pin::Pin;
+use super::store::{recount_tags, upsert_sorted, BookmarkStore, StoreMeta};
+
+#[cfg(not(feature = "sqlite"))]
+use super::index::SearchIndex;
+#[cfg(feature = "sqlite")]
+use super::sqlite_cache::{SqliteCache, SCHEMA_VERSION};
 
 const TAGS_CACHE_FN: &str = "tags.cache";
 const PINS_CACHE_FN: &str = "pins.cache";
-
+#[cfg(not(feature = "sqlite"))]
+const META_CACHE_FN: &str = "meta.cache";
+#[cfg(not(feature = "sqlite"))]
+const INDEX_CACHE_FN: &str = "index.cache";
+#[cfg(feature = "sqlite")]
+const SQLITE_CACHE_FN: &str = "cache.sqlite";
+
+#[cfg(not(feature = "sqlite"))]
 const FILE_BUF_SIZE: usize = 4 * 1024 * 1024;
+#[cfg(not(feature = "sqlite"))]
 const CACHE_BUF_SIZE: usize = 1024;
 
+/// A [`BookmarkStore`] that keeps pins & tags in files inside a cache directory.
 #[derive(Debug)]
 pub struct CachedData<'pin> {
     pub pins: Option<Vec<CachedPin<'pin>>>,
@@ -25,46 +44,154 @@ pub struct CachedData<'pin> {
     pub cache_dir: PathBuf,
     pub tags_cache_file: PathBuf,
     pub pins_cache_file: PathBuf,
+    #[cfg(not(feature = "sqlite"))]
+    pub meta_cache_file: PathBuf,
+    #[cfg(not(feature = "sqlite"))]
+    pub index_cache_file: PathBuf,
+    #[cfg(not(feature = "sqlite"))]
+    index: Option<SearchIndex>,
+    #[cfg(feature = "sqlite")]
+    pub sqlite_cache_file: PathBuf,
+    #[cfg(feature = "sqlite")]
+    db: Option<SqliteCache>,
+    meta: StoreMeta,
     cache_files_valid: bool,
 }
 
-// TODO: Add a url_lowered field to CachedPin so we don't have to call
-//       .to_lowercase() in pinboard.find_url() every time //
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// A file written by [`CachedData`] and its size in bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheFile {
+    pub path: PathBuf,
+    pub size: u64,
+}
+
+/// Statistics about data kept by [`CachedData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CacheInfo {
+    pub cache_dir: PathBuf,
+    /// Cache files that currently exist.
+    pub files: Vec<CacheFile>,
+    pub pins: usize,
+    pub tags: usize,
+    pub last_sync: Option<DateTime<Utc>>,
+    pub server_update: Option<DateTime<Utc>>,
+    pub dropped_pins: usize,
+    /// Version of the cache files' format.
+    pub format_version: u32,
+}
+
+impl CacheInfo {
+    /// Total size of all cache files in bytes.
+    #[must_use]
+    pub fn total_size(&self) -> u64 {
+        self.files.iter().map(|f| f.size).sum()
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct CachedPin<'pin> {
     pub pin: Pin<'pin>,
     pub tag_list: Vec<String>,
     pub title_lowered: String,
     pub extended_lowered: Option<String>,
+    pub url_lowered: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub struct CachedTag {
     pub tag: Tag,
     pub tag_lowered: String,
 }
 
+impl<'pin> CachedPin<'pin> {
+    /// Wraps `pin` along with its unicode normalized (nfkd) & lowercased fields.
+    #[must_use]
+    pub fn new(pin: Pin<'pin>) -> Self {
+        let tags_lowered = pin.tag_string().nfkd().collect::<String>().to_lowercase();
+        let title_lowered = pin.title.nfkd().collect::<String>().to_lowercase();
+        let extended_lowered = pin
+            .extended
+            .as_ref()
+            .map(|e| e.nfkd().collect::<String>().to_lowercase());
+        CachedPin {
+            tag_list: tags_lowered
+                .split_whitespace()
+                .map(std::string::ToString::to_string)
+                .collect(),
+            title_lowered,
+            extended_lowered,
+            url_lowered: pin.decoded_url().to_lowercase(),
+            pin,
+        }
+    }
+}
+
+impl CachedTag {
+    /// Wraps `tag` along with its unicode normalized (nfkd) & lowercased name.
+    #[must_use]
+    pub fn new(tag: Tag) -> Self {
+        CachedTag {
+            tag_lowered: tag.0.nfkd().collect::<String>().to_lowercase(),
+            tag,
+        }
+    }
+}
+
+/// Fetches all pins & tags from the server and replaces everything in `store` with them.
+///
+/// `server_update` is the server's update time known by the caller, the saved one is kept if
+/// it's `None`.
+pub(crate) fn refresh_store<'pin, S: BookmarkStore<'pin>>(
+    store: &mut S,
+    api: &api::Api,
+    server_update: Option<DateTime<Utc>>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    debug!("refresh_store: starting");
+    // Fetch all pins & sort them in descending creation time order
+    let (mut pins, dropped_pins) = api.all_pins_counted()?;
+    debug!(" sorting pins");
+    pins.sort_by(|pin1, pin2| pin1.time().cmp(&pin2.time()).reverse());
+    // Lower case all fields of each pin
+    debug!(" unicode normalization and lowercasing fields");
+    let pins = pins.into_iter().map(CachedPin::new).collect();
+
+    // Fetch all tags & sort them by frequency
+    let tags = api
+        .tags_frequency()
+        .map(|mut tags| {
+            debug!("  sorting tags");
+            tags.sort_by(|t1, t2| t1.cmp(t2).reverse());
+            tags
+        })
+        .map(|tags| {
+            debug!(" unicode normalization and lowercasing");
+            tags.into_iter().map(CachedTag::new).collect()
+        })?;
+
+    store.replace_all(pins, tags)?;
+    let mut meta = store.meta().clone();
+    meta.last_sync = Some(Utc::now());
+    if let Some(t) = server_update {
+        meta.server_update = Some(t);
+    }
+    meta.dropped_pins = dropped_pins;
+    store.set_meta(meta)
+}
+
 impl<'pin> CachedData<'pin> {
+    /// Version of the cache files' format.
+    #[cfg(not(feature = "sqlite"))]
+    pub const FORMAT_VERSION: u32 = 2;
+    /// Version of the cache files' format.
+    #[cfg(feature = "sqlite")]
+    #[allow(clippy::cast_sign_loss)]
+    pub const FORMAT_VERSION: u32 = SCHEMA_VERSION as u32;
+
     #[allow(clippy::map_unwrap_or)]
     pub fn new<P: AsRef<Path>>(c_dir: Option<P>) -> Result<Self, Box<dyn std::error::Error>> {
         let _ = env_logger::try_init();
         debug!("new: starting");
-        let cached_dir = c_dir.map(|p| p.as_ref().to_path_buf()).unwrap_or_else(|| {
-            let mut dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from(""));
-            dir.push(".cache");
-            dir.push("rusty-pin");
-            dir
-        });
-        debug!("  cached_dir: {:?}", cached_dir);
-        let mut data = CachedData::create_cache_dir(cached_dir).map(|c_path| CachedData {
-            pins: None,
-            tags: None,
-            tags_cache_file: c_path.join(TAGS_CACHE_FN),
-            pins_cache_file: c_path.join(PINS_CACHE_FN),
-            cache_dir: c_path,
-            cache_files_valid: false,
-        })?;
-
+        let mut data = CachedData::init(c_dir)?;
         if data.load_cache_data_from_file().is_err() {
             data.cache_files_valid = false;
         }
@@ -73,28 +200,45 @@ impl<'pin> CachedData<'pin> {
 
     /// Create an instance for `CachedData` but don't load actual cached files.
     #[allow(clippy::map_unwrap_or)]
-    #[allow(dead_code)]
     pub fn init<P: AsRef<Path>>(c_dir: Option<P>) -> Result<Self, Box<dyn std::error::Error>> {
         let _ = env_logger::try_init();
         debug!("init: starting");
-        let cached_dir = c_dir.map(|p| p.as_ref().to_path_buf()).unwrap_or_else(|| {
-            let mut dir = dirs::home_dir().unwrap_or_else(|| PathBuf::from(""));
-            dir.push(".cache");
-            dir.push("rusty-pin");
-            dir
-        });
+        let cached_dir = match c_dir {
+            Some(p) => p.as_ref().to_path_buf(),
+            None => paths::default_cache_dir()?,
+        };
         debug!("  cached_dir: {:?}", cached_dir);
         let data = CachedData::create_cache_dir(cached_dir).map(|c_path| CachedData {
             pins: None,
             tags: None,
             tags_cache_file: c_path.join(TAGS_CACHE_FN),
             pins_cache_file: c_path.join(PINS_CACHE_FN),
+            #[cfg(not(feature = "sqlite"))]
+            meta_cache_file: c_path.join(META_CACHE_FN),
+            #[cfg(not(feature = "sqlite"))]
+            index_cache_file: c_path.join(INDEX_CACHE_FN),
+            #[cfg(not(feature = "sqlite"))]
+            index: None,
+            #[cfg(feature = "sqlite")]
+            sqlite_cache_file: c_path.join(SQLITE_CACHE_FN),
+            #[cfg(feature = "sqlite")]
+            db: None,
             cache_dir: c_path,
+            meta: StoreMeta::default(),
             cache_files_valid: false,
         })?;
         Ok(data)
     }
 
+    /// Returns true if `dir` holds files written by `CachedData`.
+    pub(crate) fn has_cache_files<P: AsRef<Path>>(dir: P) -> bool {
+        #[cfg(not(feature = "sqlite"))]
+        let files = [PINS_CACHE_FN, TAGS_CACHE_FN];
+        #[cfg(feature = "sqlite")]
+        let files = [SQLITE_CACHE_FN];
+        files.iter().any(|f| dir.as_ref().join(f).is_file())
+    }
+
     fn create_cache_dir<P: AsRef<Path>>(
         cache_dir: P,
     ) -> Result<PathBuf, Box<dyn std::error::Error>> {
@@ -118,18 +262,48 @@ impl<'pin> CachedData<'pin> {
         self.cache_dir = CachedData::create_cache_dir(p)?;
         self.tags_cache_file = self.cache_dir.join(TAGS_CACHE_FN);
         self.pins_cache_file = self.cache_dir.join(PINS_CACHE_FN);
+        #[cfg(not(feature = "sqlite"))]
+        {
+            self.meta_cache_file = self.cache_dir.join(META_CACHE_FN);
+            self.index_cache_file = self.cache_dir.join(INDEX_CACHE_FN);
+            self.index = None;
+        }
+        #[cfg(feature = "sqlite")]
+        {
+            self.sqlite_cache_file = self.cache_dir.join(SQLITE_CACHE_FN);
+            self.db = None;
+        }
         self.pins = None;
         self.tags = None;
+        self.meta = StoreMeta::default();
         self.cache_files_valid = false;
         Ok(())
     }
 
+    #[cfg(feature = "sqlite")]
+    pub fn load_cache_data_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("load_cache_data_from_file: starting");
+        if !self.sqlite_cache_file.exists() {
+            return Err("Missing cache files".into());
+        }
+        let db = SqliteCache::open(&self.sqlite_cache_file)?;
+        self.pins = Some(db.load_pins()?);
+        self.tags = Some(db.load_tags()?);
+        self.meta = db.load_meta()?;
+        self.db = Some(db);
+        self.cache_files_valid = true;
+        Ok(())
+    }
+
+    #[cfg(not(feature = "sqlite"))]
     pub fn load_cache_data_from_file(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("load_cache_data_from_file: starting");
         match (self.tags_cache_file.exists(), self.pins_cache_file.exists()) {
             (true, true) => {
                 self.read_cached_pins()?;
                 self.read_cached_tags()?;
+                self.read_cached_meta();
+                self.load_index();
                 self.cache_files_valid = true;
                 Ok(())
             }
@@ -137,6 +311,7 @@ impl<'pin> CachedData<'pin> {
         }
     }
 
+    #[cfg(not(feature = "sqlite"))]
     fn read_cached_pins(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("read_cached_pins: starting");
         let fp = File::open(&self.pins_cache_file)?;
@@ -145,6 +320,7 @@ impl<'pin> CachedData<'pin> {
         Ok(())
     }
 
+    #[cfg(not(feature = "sqlite"))]
     fn read_cached_tags(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("read_cached_tags: starting");
         let fp = File::open(&self.tags_cache_file)?;
@@ -153,6 +329,89 @@ impl<'pin> CachedData<'pin> {
         Ok(())
     }
 
+    /// Metadata file is optional, caches written by older versions don't have it.
+    #[cfg(not(feature = "sqlite"))]
+    fn read_cached_meta(&mut self) {
+        debug!("read_cached_meta: starting");
+        self.meta = File::open(&self.meta_cache_file)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|fp| rmps::from_read(BufReader::new(fp)).map_err(Into::into))
+            .unwrap_or_else(|e| {
+                debug!("  couldn't read cache metadata: {:?}", e);
+                StoreMeta::default()
+            });
+    }
+
+    /// Files this instance reads & writes.
+    fn cache_files(&self) -> Vec<&PathBuf> {
+        #[cfg(not(feature = "sqlite"))]
+        let files = vec![
+            &self.pins_cache_file,
+            &self.tags_cache_file,
+            &self.meta_cache_file,
+            &self.index_cache_file,
+        ];
+        #[cfg(feature = "sqlite")]
+        let files = vec![&self.sqlite_cache_file];
+        files
+    }
+
+    /// Returns statistics about cached data.
+    #[must_use]
+    pub fn info(&self) -> CacheInfo {
+        debug!("info: starting");
+        let files = self
+            .cache_files()
+            .into_iter()
+            .filter_map(|path| {
+                fs::metadata(path).ok().map(|m| CacheFile {
+                    path: path.clone(),
+                    size: m.len(),
+                })
+            })
+            .collect();
+        CacheInfo {
+            cache_dir: self.cache_dir.clone(),
+            files,
+            pins: self.pins.as_ref().map_or(0, Vec::len),
+            tags: self.tags.as_ref().map_or(0, Vec::len),
+            last_sync: self.meta.last_sync,
+            server_update: self.meta.server_update,
+            dropped_pins: self.meta.dropped_pins,
+            format_version: CachedData::FORMAT_VERSION,
+        }
+    }
+
+    /// Deletes cache files and forgets cached data.
+    ///
+    /// Only files written by `CachedData` are deleted, the cache folder itself is left alone.
+    /// # Errors
+    ///
+    /// Returns an error if an existing cache file can't be deleted.
+    pub fn clear(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("clear: starting");
+        #[cfg(feature = "sqlite")]
+        {
+            self.db = None;
+        }
+        for path in self.cache_files() {
+            match fs::remove_file(path) {
+                Ok(()) => debug!("  removed {:?}", path),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        #[cfg(not(feature = "sqlite"))]
+        {
+            self.index = None;
+        }
+        self.pins = None;
+        self.tags = None;
+        self.meta = StoreMeta::default();
+        self.cache_files_valid = false;
+        Ok(())
+    }
+
     pub fn cache_ok(&self) -> bool {
         debug!("cache_ok: starting");
         self.cache_files_valid
@@ -160,102 +419,132 @@ impl<'pin> CachedData<'pin> {
 
     pub fn update_cache(&mut self, api: &api::Api) -> Result<(), Box<dyn std::error::Error>> {
         debug!("update_cache: starting");
-        // Fetch & write all pins
+        refresh_store(self, api, api.last_update_time())
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn write_cache_files(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("write_cache_files: starting");
+        let pins = self.pins.as_ref().ok_or("No pins to write to cache")?;
+        let tags = self.tags.as_ref().ok_or("No tags to write to cache")?;
+
+        debug!(" serializing pins");
+        let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        pins.serialize(&mut Serializer::new(&mut buf))?;
+        debug!(" writing to cache");
         let f = File::create(&self.pins_cache_file)?;
+        let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
+        writer.write_all(&buf)?;
 
-        // Sort pins in descending creation time order
-        api.all_pins()
-            .map(|mut pins| {
-                debug!(" sorting pins");
-                pins.sort_by(|pin1, pin2| pin1.time().cmp(&pin2.time()).reverse());
-                pins
-            })
-            .map(|pins: Vec<Pin>| {
-                // Lower case all fields of each pin
-                debug!(" unicode normalization and lowercasing fields");
-                pins.into_iter()
-                    .map(|pin| {
-                        let tags_lowered = pin.tags.nfkd().collect::<String>().to_lowercase();
-                        let title_lowered = pin.title.nfkd().collect::<String>().to_lowercase();
-                        let extended_lowered = pin
-                            .extended
-                            .as_ref()
-                            .map(|e| e.nfkd().collect::<String>().to_lowercase());
-                        CachedPin {
-                            tag_list: tags_lowered
-                                .split_whitespace()
-                                .map(std::string::ToString::to_string)
-                                .collect(),
-                            title_lowered,
-                            extended_lowered,
-                            pin,
-                        }
-                    })
-                    .collect()
-            })
-            .and_then(|pins: Vec<CachedPin>| {
-                debug!(" serializing pins");
-                let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
-                pins.serialize(&mut Serializer::new(&mut buf))?;
-                self.pins = Some(pins);
-                Ok(buf)
-            })
-            .and_then(|data| {
-                debug!(" writing to cache");
-                let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
-                writer.write_all(&data)?;
-                Ok(())
-            })?;
+        debug!("  serializing tags");
+        let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        tags.serialize(&mut Serializer::new(&mut buf))?;
+        debug!("  writing to cache");
+        let f = File::create(&self.tags_cache_file)?;
+        let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
+        writer.write_all(&buf)?;
 
         #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
         self.fix_cache_file_perm(&self.pins_cache_file);
+        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+        self.fix_cache_file_perm(&self.tags_cache_file);
 
-        assert!(self.pins.is_some());
-
-        // Fetch & write all tags
-        //
-        let f = File::create(&self.tags_cache_file)?;
+        let index = SearchIndex::build(pins);
+        self.write_index(&index)?;
+        self.index = Some(index);
+        Ok(())
+    }
 
-        // Sort tags by frequency before writing
-        api.tags_frequency()
-            .map(|mut tags| {
-                debug!("  sorting tags");
-                tags.sort_by(|t1, t2| t1.cmp(t2).reverse());
-                tags
-            })
-            .map(|tags| {
-                debug!(" unicode normalization and lowercasing");
-                tags.into_iter()
-                    .map(|tag| CachedTag {
-                        tag_lowered: tag.0.nfkd().collect::<String>().to_lowercase(),
-                        tag,
-                    })
-                    .collect()
-            })
-            .and_then(|cached_tags: Vec<CachedTag>| {
-                debug!("  serializing tags");
-                let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
-                cached_tags.serialize(&mut Serializer::new(&mut buf))?;
-                self.tags = Some(cached_tags);
-                Ok(buf)
+    /// Reads the search index saved along the pins, rebuilding it if it's missing or was
+    /// built from other pins. The index only speeds up searches, so errors are just logged.
+    #[cfg(not(feature = "sqlite"))]
+    fn load_index(&mut self) {
+        debug!("load_index: starting");
+        let Some(pins) = self.pins.as_ref() else {
+            self.index = None;
+            return;
+        };
+        let index = File::open(&self.index_cache_file)
+            .map_err(Box::<dyn std::error::Error>::from)
+            .and_then(|fp| {
+                rmps::from_read::<_, SearchIndex>(BufReader::with_capacity(FILE_BUF_SIZE, fp))
+                    .map_err(Into::into)
             })
-            .and_then(|data| {
-                debug!("  writing to cache");
-                let mut writer = BufWriter::with_capacity(FILE_BUF_SIZE, f);
-                writer.write_all(&data)?;
-                Ok(())
-            })?;
+            .ok()
+            .filter(|index| index.is_valid_for(pins));
+        self.index = Some(index.unwrap_or_else(|| {
+            debug!("  rebuilding search index");
+            let index = SearchIndex::build(pins);
+            if let Err(e) = self.write_index(&index) {
+                error!("Couldn't write search index: {:?}", e);
+            }
+            index
+        }));
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn write_index(&self, index: &SearchIndex) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("write_index: starting");
+        let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        index.serialize(&mut Serializer::new(&mut buf))?;
+        fs::write(&self.index_cache_file, buf)?;
 
         #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
-        self.fix_cache_file_perm(&self.pins_cache_file);
+        self.fix_cache_file_perm(&self.index_cache_file);
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn write_cache_files(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("write_cache_files: starting");
+        let pins = self.pins.as_ref().ok_or("No pins to write to cache")?;
+        let tags = self.tags.as_ref().ok_or("No tags to write to cache")?;
+        if self.db.is_none() {
+            self.db = Some(SqliteCache::open(&self.sqlite_cache_file)?);
+        }
+        if let Some(db) = self.db.as_mut() {
+            db.replace_all(pins, tags)?;
+        }
+
         #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
-        self.fix_cache_file_perm(&self.tags_cache_file);
+        self.fix_cache_file_perm(&self.sqlite_cache_file);
+        Ok(())
+    }
 
-        assert!(self.tags.is_some());
-        self.cache_files_valid = true;
+    #[cfg(not(feature = "sqlite"))]
+    fn write_cache_meta(&self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("write_cache_meta: starting");
+        let mut buf: Vec<u8> = Vec::with_capacity(CACHE_BUF_SIZE);
+        self.meta.serialize(&mut Serializer::new(&mut buf))?;
+        fs::write(&self.meta_cache_file, buf)?;
+
+        #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
+        self.fix_cache_file_perm(&self.meta_cache_file);
         Ok(())
     }
 
+    #[cfg(feature = "sqlite")]
+    fn write_cache_meta(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("write_cache_meta: starting");
+        if self.db.is_none() {
+            self.db = Some(SqliteCache::open(&self.sqlite_cache_file)?);
+        }
+        if let Some(db) = self.db.as_ref() {
+            db.save_meta(&self.meta)?;
+        }
+        Ok(())
+    }
+
+    /// Upserts `pin` into the loaded pins & recounts the loaded tags, returning where it was
+    /// inserted.
+    fn upsert_loaded(&mut self, pin: CachedPin<'pin>) -> usize {
+        let (idx, replaced) = upsert_sorted(self.pins.get_or_insert_with(Vec::new), pin);
+        if let (Some(tags), Some(pins)) = (self.tags.as_mut(), self.pins.as_ref()) {
+            recount_tags(tags, &replaced, pins.get(idx));
+        }
+        idx
+    }
+
     #[allow(clippy::unused_self)]
     #[cfg(any(target_os = "macos", target_os = "linux", target_os = "freebsd"))]
     fn fix_cache_file_perm(&self, p: &Path) {
@@ -274,6 +563,178 @@ impl<'pin> CachedData<'pin> {
     }
 }
 
+impl<'pin> BookmarkStore<'pin> for CachedData<'pin> {
+    fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.load_cache_data_from_file()
+    }
+
+    fn is_valid(&self) -> bool {
+        self.cache_ok()
+    }
+
+    fn pins(&self) -> Option<&[CachedPin<'pin>]> {
+        self.pins.as_deref()
+    }
+
+    fn tags(&self) -> Option<&[CachedTag]> {
+        self.tags.as_deref()
+    }
+
+    fn replace_all(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+        tags: Vec<CachedTag>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("replace_all: starting");
+        self.pins = Some(pins);
+        self.tags = Some(tags);
+        self.write_cache_files()?;
+        self.cache_files_valid = true;
+        Ok(())
+    }
+
+    fn upsert_pin(&mut self, pin: CachedPin<'pin>) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("upsert_pin: starting");
+        self.upsert_pins(vec![pin])
+    }
+
+    /// Cache files are written once all `pins` are upserted.
+    #[cfg(not(feature = "sqlite"))]
+    fn upsert_pins(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("upsert_pins: starting");
+        for pin in pins {
+            self.upsert_loaded(pin);
+        }
+        self.write_cache_files()
+    }
+
+    /// Only the rows of `pins` are written, tags are rewritten once all of them are upserted.
+    #[cfg(feature = "sqlite")]
+    fn upsert_pins(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("upsert_pins: starting");
+        let upserted = pins
+            .into_iter()
+            .map(|pin| (self.upsert_loaded(pin.clone()), pin))
+            .collect::<Vec<_>>();
+        match self.db.as_mut() {
+            Some(db) => {
+                for (position, pin) in &upserted {
+                    db.upsert_pin(*position, pin)?;
+                }
+                db.replace_tags(self.tags.as_deref().unwrap_or_default())
+            }
+            None => self.write_cache_files(),
+        }
+    }
+
+    fn delete_pin(
+        &mut self,
+        url: &str,
+    ) -> Result<Option<CachedPin<'pin>>, Box<dyn std::error::Error>> {
+        debug!("delete_pin: starting");
+        let Some((idx, removed)) = self.pins.as_mut().and_then(|pins| {
+            pins.iter()
+                .position(|p| p.pin.url.as_str() == url)
+                .map(|idx| (idx, pins.remove(idx)))
+        }) else {
+            return Ok(None);
+        };
+        debug!("  removed pin at {}", idx);
+        if let Some(tags) = self.tags.as_mut() {
+            recount_tags(tags, std::slice::from_ref(&removed), None);
+        }
+        #[cfg(feature = "sqlite")]
+        if let Some(db) = self.db.as_mut() {
+            db.delete_pin(idx)?;
+            db.replace_tags(self.tags.as_deref().unwrap_or_default())?;
+            return Ok(Some(removed));
+        }
+        self.write_cache_files()?;
+        Ok(Some(removed))
+    }
+
+    fn meta(&self) -> &StoreMeta {
+        &self.meta
+    }
+
+    fn set_meta(&mut self, meta: StoreMeta) -> Result<(), Box<dyn std::error::Error>> {
+        self.meta = meta;
+        self.write_cache_meta()
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn searchable_pins(&self, queries: &[String], fields: &[SearchType]) -> Vec<&CachedPin<'pin>> {
+        let Some(pins) = self.pins.as_ref() else {
+            return vec![];
+        };
+        match self
+            .index
+            .as_ref()
+            .and_then(|index| index.candidates(queries, fields))
+        {
+            Some(ids) => ids
+                .into_iter()
+                .filter_map(|id| pins.get(id as usize))
+                .collect(),
+            None => pins.iter().collect(),
+        }
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    fn pins_with_tag(&self, tag: &str) -> Vec<&CachedPin<'pin>> {
+        let Some(pins) = self.pins.as_ref() else {
+            return vec![];
+        };
+        match self.index.as_ref() {
+            Some(index) => index
+                .pins_with_tag(tag)
+                .iter()
+                .filter_map(|&id| pins.get(id as usize))
+                .collect(),
+            None => pins
+                .iter()
+                .filter(|p| p.tag_list.iter().any(|t| t == tag))
+                .collect(),
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn searchable_pins(&self, queries: &[String], fields: &[SearchType]) -> Vec<&CachedPin<'pin>> {
+        let Some(pins) = self.pins.as_ref() else {
+            return vec![];
+        };
+        if let Some(db) = self.db.as_ref() {
+            match db.matching_pin_ids(queries, fields) {
+                Ok(Some(ids)) => return ids.into_iter().filter_map(|id| pins.get(id)).collect(),
+                Ok(None) => {}
+                Err(e) => error!("Couldn't search sqlite cache: {:?}", e),
+            }
+        }
+        pins.iter().collect()
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn searchable_tags(&self, query: &str) -> Vec<&CachedTag> {
+        let Some(tags) = self.tags.as_ref() else {
+            return vec![];
+        };
+        if let Some(db) = self.db.as_ref() {
+            match db.matching_tag_ids(query) {
+                Ok(Some(ids)) => return ids.into_iter().filter_map(|id| tags.get(id)).collect(),
+                Ok(None) => {}
+                Err(e) => error!("Couldn't search sqlite cache: {:?}", e),
+            }
+        }
+        tags.iter().collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -289,11 +750,12 @@ mod tests {
             "https://danielkeep.github.io/tlborm/book/README.html",
             "The Little Book of Rust Macros آموزشی",
         )
-        .tags("지구 Rust macros")
-        .toread("yes")
-        .shared("no")
+        .tags(["지구", "Rust", "macros"])
+        .toread(true)
+        .shared(false)
         .description("지구")
-        .into_pin();
+        .into_pin()
+        .expect("valid url");
         pin.time = Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap();
 
         let tag_list = ["지구", "Rust", "macros"]
@@ -309,6 +771,7 @@ mod tests {
                 .collect::<String>()
                 .to_lowercase(),
             extended_lowered: Some("지구".nfkd().collect::<String>().to_lowercase()),
+            url_lowered: "https://danielkeep.github.io/tlborm/book/readme.html".to_string(),
         };
         // non-normalized "지구" should not be found in normalized tag list of the pin
         let r = format!(
@@ -351,11 +814,12 @@ mod tests {
             "https://danielkeep.github.io/tlborm/book/README.html",
             "The Little Book of Rust Macros",
         )
-        .tags("Rust macros")
-        .toread("yes")
-        .shared("no")
+        .tags(["Rust", "macros"])
+        .toread(true)
+        .shared(false)
         .description("WoW!!!")
-        .into_pin();
+        .into_pin()
+        .expect("valid url");
         pin.time = Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap();
 
         let cached_pin = CachedPin {
@@ -363,6 +827,7 @@ mod tests {
             tag_list: vec!["rust".into(), "macros".into()],
             title_lowered: "The Little Book of Rust Macros".to_lowercase(),
             extended_lowered: Some("WoW!!!".to_lowercase()),
+            url_lowered: "https://danielkeep.github.io/tlborm/book/readme.html".to_string(),
         };
 
         let mut buf: Vec<u8> = Vec::new();
@@ -370,7 +835,7 @@ mod tests {
         cached_pin
             .serialize(&mut Serializer::new(&mut buf))
             .expect("impossible");
-        assert_eq!(185, buf.len());
+        assert_eq!(241, buf.len());
 
         let mut de = Deserializer::from_read_ref(&buf);
         let new_cached: CachedPin =
@@ -381,8 +846,8 @@ mod tests {
             "https://danielkeep.github.io/tlborm/book/README.html",
             new_cached.pin.url.as_ref()
         );
-        assert_eq!("yes", new_cached.pin.toread);
-        assert_eq!("no", new_cached.pin.shared);
+        assert!(new_cached.pin.toread);
+        assert!(!new_cached.pin.shared);
         assert_eq!("WoW!!!", new_cached.pin.extended.unwrap());
         assert_eq!(
             Utc.with_ymd_and_hms(2017, 5, 22, 17, 46, 54).unwrap(),
@@ -393,4 +858,114 @@ mod tests {
             new_cached.tag_list
         );
     }
+
+    #[test]
+    fn upsert_delete_recounts_tags() {
+        let _ = env_logger::try_init();
+        debug!("upsert_delete_recounts_tags: starting");
+        let pin = |url: &str, tags: &[&str]| {
+            CachedPin::new(
+                PinBuilder::new(url.to_string(), "recount".to_string())
+                    .tags(tags.iter().copied())
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        };
+        let dir = crate::tests::rand_temp_path();
+        let mut data = CachedData::init(Some(&dir)).expect("Couldn't init cache");
+        data.replace_all(
+            vec![
+                pin("https://a.com/", &["rust", "git"]),
+                pin("https://b.com/", &["rust"]),
+            ],
+            vec![
+                CachedTag::new(Tag::new("rust".to_string(), 2)),
+                CachedTag::new(Tag::new("git".to_string(), 1)),
+            ],
+        )
+        .expect("Couldn't write cache");
+
+        data.upsert_pins(vec![
+            pin("https://a.com/", &["rust", "zfs"]),
+            pin("https://c.com/", &["zfs"]),
+        ])
+        .expect("Couldn't upsert pins");
+        data.delete_pin("https://b.com/")
+            .expect("Couldn't delete pin");
+        let expected = vec![
+            Tag::new("zfs".to_string(), 2),
+            Tag::new("rust".to_string(), 1),
+        ];
+        let tags = |data: &CachedData| {
+            data.tags()
+                .expect("tags are cached")
+                .iter()
+                .map(|t| t.tag.clone())
+                .collect::<Vec<Tag>>()
+        };
+        assert_eq!(expected, tags(&data));
+        assert!(tags(&data).iter().zip(&expected).all(|(t, e)| t.1 == e.1));
+
+        // Recounted tags are persisted
+        let data = CachedData::new(Some(&dir)).expect("Couldn't load cache");
+        assert_eq!(expected, tags(&data));
+        assert!(tags(&data).iter().zip(&expected).all(|(t, e)| t.1 == e.1));
+        assert_eq!(2, data.pins().expect("pins are cached").len());
+    }
+
+    #[cfg(not(feature = "sqlite"))]
+    #[test]
+    fn search_index_persisted() {
+        let _ = env_logger::try_init();
+        debug!("search_index_persisted: starting");
+        let pins: Vec<Pin> = serde_json::from_str(include_str!("../../sample.json"))
+            .expect("Couldn't read sample.json");
+        let pins: Vec<CachedPin> = pins.into_iter().map(CachedPin::new).collect();
+        let dir = crate::tests::rand_temp_path();
+        let mut data = CachedData::init(Some(&dir)).expect("Couldn't init cache");
+        data.replace_all(pins.clone(), vec![])
+            .expect("Couldn't write cache");
+        assert!(data.index_cache_file.exists());
+
+        let queries = vec!["series".to_string(), "blog".to_string()];
+        let fields = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let linear = pins
+            .iter()
+            .filter(|p| {
+                queries.iter().all(|q| {
+                    p.title_lowered.contains(q)
+                        || p.tag_list.iter().any(|t| t.contains(q))
+                        || p.url_lowered.contains(q)
+                        || p.extended_lowered.as_ref().is_some_and(|e| e.contains(q))
+                })
+            })
+            .collect::<Vec<&CachedPin>>();
+        assert!(!linear.is_empty());
+
+        // A stale index file is rebuilt when loading
+        fs::write(&data.index_cache_file, b"garbage").expect("Couldn't write index");
+        let mut data = CachedData::new(Some(&dir)).expect("Couldn't load cache");
+        assert!(data.index.is_some());
+        let candidates = data.searchable_pins(&queries, &fields);
+        assert!(candidates.len() < pins.len());
+        assert!(linear.iter().all(|p| candidates.contains(p)));
+        let written = fs::read(&data.index_cache_file).expect("Couldn't read index");
+        assert_ne!(b"garbage".to_vec(), written);
+
+        let tagged = data.pins_with_tag("blog");
+        assert_eq!(
+            pins.iter()
+                .filter(|p| p.tag_list.contains(&"blog".to_string()))
+                .count(),
+            tagged.len()
+        );
+
+        data.clear().expect("Couldn't clear cache");
+        assert!(!data.index_cache_file.exists());
+    }
 }
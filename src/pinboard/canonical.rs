@@ -0,0 +1,125 @@
+//! Canonical form of urls, used to find the same page bookmarked under different urls.
+//!
+//! Canonicalization:
+//! - treats `http` as `https`,
+//! - lowercases the host, converts international domain names to punycode & drops `www.`,
+//! - drops default ports,
+//! - drops a trailing `/` from the path,
+//! - drops tracking query parameters (`utm_*`, `fbclid`, ...).
+//!
+//! The fragment and the case of the path & query are kept.
+use url::Url;
+
+use super::pin::Pin;
+
+/// Pins bookmarked under the same canonical url (the first element).
+pub type DuplicatePins<'pin> = Vec<(String, Vec<&'pin Pin<'pin>>)>;
+
+/// Query parameters that only track where visitors came from.
+const TRACKING_PARAMS: [&str; 10] = [
+    "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "igshid", "yclid", "_hsenc", "_hsmi",
+];
+/// Prefix of tracking query parameters.
+const TRACKING_PREFIX: &str = "utm_";
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.starts_with(TRACKING_PREFIX) || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+/// Returns the canonical form of `url`.
+///
+/// ```
+/// use rusty_pin::pinboard::canonical::canonicalize;
+/// use url::Url;
+///
+/// let a = Url::parse("http://www.example.com:80/a/?utm_source=feed&id=1").unwrap();
+/// let b = Url::parse("https://example.com/a?id=1").unwrap();
+/// assert_eq!(canonicalize(&a), canonicalize(&b));
+/// ```
+#[must_use]
+pub fn canonicalize(url: &Url) -> Url {
+    let mut canonical = url.clone();
+    if canonical.scheme() == "http" {
+        // Both are special schemes so this can't fail
+        let _ = canonical.set_scheme("https");
+    }
+    // An explicit `:80` of http urls is already dropped when parsing
+    if canonical.port() == Some(443) && canonical.scheme() == "https" {
+        let _ = canonical.set_port(None);
+    }
+
+    if let Some(host) = canonical.host_str().map(str::to_lowercase) {
+        if let Some(stripped) = host.strip_prefix("www.").filter(|h| h.contains('.')) {
+            let stripped = stripped.to_string();
+            let _ = canonical.set_host(Some(&stripped));
+        }
+    }
+
+    let path = canonical.path();
+    if path.len() > 1 && path.ends_with('/') {
+        let trimmed = path.trim_end_matches('/').to_string();
+        canonical.set_path(&trimmed);
+    }
+
+    if canonical.query().is_some() {
+        let kept = canonical
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_param(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect::<Vec<(String, String)>>();
+        if kept.is_empty() {
+            canonical.set_query(None);
+        } else {
+            canonical.query_pairs_mut().clear().extend_pairs(kept);
+        }
+    }
+    canonical
+}
+
+/// Parses `url` and returns its canonical form, `None` if it can't be parsed.
+#[must_use]
+pub fn canonical_str(url: &str) -> Option<String> {
+    Url::parse(url).ok().map(|u| canonicalize(&u).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(url: &str) -> String {
+        canonical_str(url).expect("valid url")
+    }
+
+    #[test]
+    fn canonical_urls() {
+        let _ = env_logger::try_init();
+        debug!("canonical_urls: starting");
+        let expected = "https://x.com/a";
+        for url in [
+            "http://x.com/a",
+            "https://www.x.com/a/",
+            "https://x.com/a?utm_source=feed&utm_medium=rss",
+            "HTTP://WWW.X.COM:80/a",
+            "https://x.com:443/a?fbclid=123",
+        ] {
+            assert_eq!(expected, canonical(url), "{url}");
+        }
+
+        assert_eq!("https://x.com/", canonical("http://www.x.com"));
+        assert_eq!("https://x.com:8080/A", canonical("http://x.com:8080/A/"));
+        assert_eq!("https://x.com:80/a", canonical("https://x.com:80/a"));
+        assert_eq!(
+            "https://x.com/a?id=1&b=2#top",
+            canonical("https://x.com/a?utm_campaign=x&id=1&b=2#top")
+        );
+        assert_eq!(
+            "https://xn--e1afmkfd.com/",
+            canonical("https://www.пример.com")
+        );
+        // `www.` alone is a host name
+        assert_eq!("https://www.com/", canonical("https://www.com"));
+        assert_eq!("ftp://x.com/a", canonical("ftp://x.com/a/"));
+        assert_eq!(None, canonical_str("not a url"));
+    }
+}
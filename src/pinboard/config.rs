@@ -1,22 +1,161 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use super::filter::SearchFilter;
+use super::paths::{self, PathError};
+use super::rank::RankWeights;
+
+/// Name of the settings file within the config folder.
+pub const SETTINGS_FN: &str = "settings.json";
+
+/// Settings of a [`Pinboard`](super::Pinboard).
+///
+/// Settings can be saved to a file shared by all tools on the machine, see
+/// [`Config::settings_file`]. The search filter is not saved.
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[non_exhaustive]
 pub struct Config {
     pub tag_only_search: bool,
     pub fuzzy_search: bool,
+    /// New pins are private unless they are explicitly shared.
     pub private_new_pin: bool,
+    /// New pins are marked to read unless they explicitly aren't.
     pub toread_new_pin: bool,
+    /// Look up urls on the server, not only in the cache, before adding them.
+    pub check_server_on_add: bool,
+    pub rank_weights: RankWeights,
+    /// Separator of tag hierarchies like `lang/rust`, `None` disables the hierarchy view.
+    pub tag_separator: Option<char>,
+    #[serde(skip)]
+    pub search_filter: SearchFilter,
     // _private: (), // Force instantiation through Config::new()
 }
 
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
 impl Config {
+    #[must_use]
     pub fn new() -> Self {
         Config {
             tag_only_search: false,
             fuzzy_search: false,
             private_new_pin: true,
             toread_new_pin: false,
+            check_server_on_add: false,
+            rank_weights: RankWeights::default(),
+            tag_separator: None,
+            search_filter: SearchFilter::default(),
             // _private: (),
         }
     }
+
+    /// Path of the settings file shared by all tools: `settings.json` within
+    /// [`paths::default_config_dir`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found.
+    pub fn settings_file() -> Result<PathBuf, PathError> {
+        Ok(paths::default_config_dir()?.join(SETTINGS_FN))
+    }
+
+    /// Reads settings from `path`, settings missing from the file (or a missing file) get
+    /// their default values.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed.
+    pub fn load_from<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        debug!("load_from: starting.");
+        if !path.as_ref().exists() {
+            return Ok(Config::new());
+        }
+        let fp = File::open(path)?;
+        Ok(serde_json::from_reader(BufReader::new(fp))?)
+    }
+
+    /// Writes settings to `path`, creating its folder if needed.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be written.
+    pub fn save_to<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("save_to: starting.");
+        if let Some(dir) = path.as_ref().parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let fp = File::create(path)?;
+        serde_json::to_writer_pretty(BufWriter::new(fp), self)?;
+        Ok(())
+    }
+
+    /// Reads the shared settings file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found or the file can't be read.
+    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
+        Config::load_from(Config::settings_file()?)
+    }
+
+    /// Reads the shared settings file, falling back to default settings if it can't be read.
+    pub(crate) fn load_shared() -> Self {
+        Config::load().unwrap_or_else(|e| {
+            info!("couldn't load settings, using defaults: {}", e);
+            Config::new()
+        })
+    }
+
+    /// Writes the shared settings file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if no config folder can be found or the file can't be written.
+    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
+        self.save_to(Config::settings_file()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::rand_temp_path;
+
+    #[test]
+    fn save_and_load_settings() {
+        let _ = env_logger::try_init();
+        debug!("save_and_load_settings: starting");
+        let path = rand_temp_path().join("nested").join(SETTINGS_FN);
+        assert_eq!(
+            Config::new(),
+            Config::load_from(&path).expect("missing file")
+        );
+
+        let mut cfg = Config::new();
+        cfg.private_new_pin = false;
+        cfg.toread_new_pin = true;
+        cfg.rank_weights.title = 5.0;
+        cfg.search_filter = SearchFilter::new().toread(true);
+        cfg.save_to(&path).expect("couldn't save settings");
+
+        let loaded = Config::load_from(&path).expect("couldn't load settings");
+        assert!(!loaded.private_new_pin);
+        assert!(loaded.toread_new_pin);
+        assert!((loaded.rank_weights.title - 5.0).abs() < f64::EPSILON);
+        assert!(loaded.search_filter.is_empty());
+
+        // Missing settings get default values
+        fs::write(&path, r#"{"toread_new_pin": true}"#).expect("couldn't write settings");
+        let loaded = Config::load_from(&path).expect("couldn't load settings");
+        assert!(loaded.private_new_pin);
+        assert!(loaded.toread_new_pin);
+        assert_eq!(RankWeights::default(), loaded.rank_weights);
+    }
 }
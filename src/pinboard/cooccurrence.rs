@@ -0,0 +1,281 @@
+//! How often tags are used together, computed from cached pins.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use chrono::prelude::*;
+
+use super::cached_data::CachedPin;
+use super::tag::TagFreq;
+
+/// Tags used along with a tag & the number of bookmarks using both, most often first.
+pub type RelatedTags = Vec<(String, usize)>;
+
+/// A tag & the number of bookmarks using it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagGraphNode {
+    pub tag: String,
+    pub freq: TagFreq,
+}
+
+/// Two tags & the number of bookmarks using both.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagGraphEdge {
+    pub source: String,
+    pub target: String,
+    pub weight: usize,
+}
+
+/// Tags linked by how often they are used together, see [`Pinboard::tag_graph`].
+///
+/// [`Pinboard::tag_graph`]: super::Pinboard::tag_graph
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagGraph {
+    /// Most used first.
+    pub nodes: Vec<TagGraphNode>,
+    /// Heaviest first, each pair of tags once.
+    pub edges: Vec<TagGraphEdge>,
+}
+
+impl TagGraph {
+    /// # Errors
+    ///
+    /// Returns an error if the graph can't be serialized.
+    pub fn to_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string_pretty(self)
+    }
+
+    /// Graphviz DOT (undirected graph), nodes labeled with their count.
+    #[must_use]
+    pub fn to_dot(&self) -> String {
+        fn quoted(s: &str) -> String {
+            format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+        }
+        let mut dot = String::from("graph tags {\n");
+        for node in &self.nodes {
+            let label = format!("{} ({})", node.tag, node.freq);
+            let _ = writeln!(dot, "  {} [label={}];", quoted(&node.tag), quoted(&label));
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                dot,
+                "  {} -- {} [weight={}];",
+                quoted(&edge.source),
+                quoted(&edge.target),
+                edge.weight
+            );
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+/// Tag usage & co-occurrence counts, keyed by unicode normalized (nfkd) & lowercased tags.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct TagCooccurrence {
+    /// Tag as written in the first pin using it.
+    names: HashMap<String, String>,
+    counts: HashMap<String, usize>,
+    /// Creation time of the newest pin using each tag.
+    last_used: HashMap<String, DateTime<Utc>>,
+    /// Symmetric: `pairs[a][b] == pairs[b][a]`.
+    pairs: HashMap<String, HashMap<String, usize>>,
+}
+
+impl TagCooccurrence {
+    pub(crate) fn new<'a, 'pin: 'a, I>(pins: I) -> Self
+    where
+        I: IntoIterator<Item = &'a CachedPin<'pin>>,
+    {
+        debug!("TagCooccurrence::new: starting.");
+        let mut cooccurrence = TagCooccurrence::default();
+        for cached_pin in pins {
+            let mut tags: Vec<&str> = vec![];
+            for (i, tag) in cached_pin.tag_list.iter().enumerate() {
+                if tags.contains(&tag.as_str()) {
+                    continue;
+                }
+                tags.push(tag);
+                let name = cached_pin
+                    .pin
+                    .tags
+                    .get(i)
+                    .filter(|_| cached_pin.pin.tags.len() == cached_pin.tag_list.len())
+                    .unwrap_or(tag);
+                cooccurrence
+                    .names
+                    .entry(tag.clone())
+                    .or_insert_with(|| name.clone());
+                *cooccurrence.counts.entry(tag.clone()).or_default() += 1;
+                let last_used = cooccurrence
+                    .last_used
+                    .entry(tag.clone())
+                    .or_insert(cached_pin.pin.time);
+                *last_used = (*last_used).max(cached_pin.pin.time);
+            }
+            for tag in &tags {
+                let with = cooccurrence.pairs.entry((*tag).to_string()).or_default();
+                for other in tags.iter().filter(|t| *t != tag) {
+                    *with.entry((*other).to_string()).or_default() += 1;
+                }
+            }
+        }
+        cooccurrence
+    }
+
+    /// Tag as written by the user.
+    pub(crate) fn name<'a>(&'a self, tag: &'a str) -> &'a str {
+        self.names.get(tag).map_or(tag, String::as_str)
+    }
+
+    /// Number of pins using `tag`.
+    pub(crate) fn count(&self, tag: &str) -> usize {
+        self.counts.get(tag).copied().unwrap_or_default()
+    }
+
+    /// All tags used by pins, in no particular order.
+    pub(crate) fn tags(&self) -> impl Iterator<Item = &str> {
+        self.counts.keys().map(String::as_str)
+    }
+
+    /// Creation time of the newest pin using `tag`.
+    pub(crate) fn last_used(&self, tag: &str) -> Option<DateTime<Utc>> {
+        self.last_used.get(tag).copied()
+    }
+
+    /// Tags used along with `tag` & the number of pins using both, in no particular order.
+    pub(crate) fn with(&self, tag: &str) -> impl Iterator<Item = (&str, usize)> {
+        self.pairs
+            .get(tag)
+            .into_iter()
+            .flatten()
+            .map(|(other, n)| (other.as_str(), *n))
+    }
+
+    /// Tags used along with `tag`, most often first.
+    pub(crate) fn related(&self, tag: &str) -> Vec<(&str, usize)> {
+        let mut related = self.with(tag).collect::<Vec<_>>();
+        related.sort_by(|(t1, n1), (t2, n2)| n2.cmp(n1).then_with(|| t1.cmp(t2)));
+        related
+    }
+
+    /// Graph of all tags, without edges of less than `min_weight` bookmarks.
+    pub(crate) fn graph(&self, min_weight: usize) -> TagGraph {
+        let mut tags = self.counts.iter().collect::<Vec<_>>();
+        tags.sort_by(|(t1, n1), (t2, n2)| n2.cmp(n1).then_with(|| t1.cmp(t2)));
+        let mut edges = self
+            .pairs
+            .iter()
+            .flat_map(|(tag, with)| with.iter().map(move |(other, n)| (tag, other, *n)))
+            .filter(|(tag, other, n)| tag < other && *n >= min_weight)
+            .collect::<Vec<_>>();
+        edges
+            .sort_by(|(s1, t1, n1), (s2, t2, n2)| n2.cmp(n1).then_with(|| (s1, t1).cmp(&(s2, t2))));
+        TagGraph {
+            nodes: tags
+                .into_iter()
+                .map(|(tag, n)| TagGraphNode {
+                    tag: self.name(tag).to_string(),
+                    freq: TagFreq::Used(*n),
+                })
+                .collect(),
+            edges: edges
+                .into_iter()
+                .map(|(source, target, weight)| TagGraphEdge {
+                    source: self.name(source).to_string(),
+                    target: self.name(target).to_string(),
+                    weight,
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn tag_cooccurrence() {
+        let _ = env_logger::try_init();
+        debug!("tag_cooccurrence: starting");
+        let pins = [
+            vec!["Rust", "cli"],
+            vec!["rust", "web"],
+            vec!["rust", "cli", "cli"],
+        ]
+        .into_iter()
+        .map(|tags| {
+            CachedPin::new(
+                PinBuilder::new("https://example.com/", "pin")
+                    .tags(tags)
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        })
+        .collect::<Vec<_>>();
+        let cooccurrence = TagCooccurrence::new(&pins);
+        assert_eq!(3, cooccurrence.count("rust"));
+        assert_eq!(0, cooccurrence.count("go"));
+        assert_eq!("Rust", cooccurrence.name("rust"));
+        assert_eq!("go", cooccurrence.name("go"));
+        let mut with_rust = cooccurrence.with("rust").collect::<Vec<_>>();
+        with_rust.sort_unstable();
+        assert_eq!(vec![("cli", 2), ("web", 1)], with_rust);
+        assert_eq!(
+            vec![("rust", 2)],
+            cooccurrence.with("cli").collect::<Vec<_>>()
+        );
+        assert_eq!(vec![("cli", 2), ("web", 1)], cooccurrence.related("rust"));
+
+        let graph = cooccurrence.graph(2);
+        assert_eq!(
+            vec![("Rust", TagFreq::Used(3)), ("cli", TagFreq::Used(2))],
+            graph.nodes[..2]
+                .iter()
+                .map(|n| (n.tag.as_str(), n.freq.clone()))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![TagGraphEdge {
+                source: "cli".to_string(),
+                target: "Rust".to_string(),
+                weight: 2
+            }],
+            graph.edges
+        );
+        assert_eq!(2, cooccurrence.graph(1).edges.len());
+    }
+
+    #[test]
+    fn tag_graph_formats() {
+        let graph = TagGraph {
+            nodes: vec![
+                TagGraphNode {
+                    tag: "rust".to_string(),
+                    freq: TagFreq::Used(3),
+                },
+                TagGraphNode {
+                    tag: "say \"hi\"".to_string(),
+                    freq: TagFreq::Used(1),
+                },
+            ],
+            edges: vec![TagGraphEdge {
+                source: "rust".to_string(),
+                target: "say \"hi\"".to_string(),
+                weight: 1,
+            }],
+        };
+        assert_eq!(
+            "graph tags {\n  \"rust\" [label=\"rust (3)\"];\n  \"say \\\"hi\\\"\" \
+             [label=\"say \\\"hi\\\" (1)\"];\n  \"rust\" -- \"say \\\"hi\\\"\" [weight=1];\n}\n",
+            graph.to_dot()
+        );
+        let json = graph.to_json().expect("serializable graph");
+        assert_eq!(
+            graph,
+            serde_json::from_str::<TagGraph>(&json).expect("valid json")
+        );
+        assert!(json.contains(r#""weight": 1"#));
+    }
+}
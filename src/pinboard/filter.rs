@@ -0,0 +1,157 @@
+//! Filters on bookmarks' metadata applied on top of text searches.
+use chrono::prelude::*;
+use url::Url;
+
+use super::cached_data::CachedPin;
+
+/// Restricts search results by creation time, flags, domain & number of tags.
+///
+/// All constraints that are set must hold for a bookmark to be returned. The default filter
+/// doesn't restrict anything.
+///
+/// ```
+/// use rusty_pin::pinboard::SearchFilter;
+/// use chrono::prelude::*;
+///
+/// let filter = SearchFilter::new()
+///     .since(Utc.with_ymd_and_hms(2020, 1, 1, 0, 0, 0).unwrap())
+///     .toread(true)
+///     .shared(false)
+///     .domain("github.com")
+///     .min_tags(1);
+/// ```
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SearchFilter {
+    /// Bookmarks created at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Bookmarks created before this time.
+    pub until: Option<DateTime<Utc>>,
+    pub toread: Option<bool>,
+    pub shared: Option<bool>,
+    /// Bookmarks whose host is this domain or one of its subdomains.
+    pub domain: Option<String>,
+    pub min_tags: Option<usize>,
+    pub max_tags: Option<usize>,
+}
+
+impl SearchFilter {
+    #[must_use]
+    pub fn new() -> Self {
+        SearchFilter::default()
+    }
+
+    #[must_use]
+    pub fn since(mut self, t: DateTime<Utc>) -> Self {
+        self.since = Some(t);
+        self
+    }
+
+    #[must_use]
+    pub fn until(mut self, t: DateTime<Utc>) -> Self {
+        self.until = Some(t);
+        self
+    }
+
+    #[must_use]
+    pub fn toread(mut self, v: bool) -> Self {
+        self.toread = Some(v);
+        self
+    }
+
+    #[must_use]
+    pub fn shared(mut self, v: bool) -> Self {
+        self.shared = Some(v);
+        self
+    }
+
+    #[must_use]
+    pub fn domain<S: Into<String>>(mut self, d: S) -> Self {
+        self.domain = Some(d.into().to_lowercase());
+        self
+    }
+
+    #[must_use]
+    pub fn min_tags(mut self, n: usize) -> Self {
+        self.min_tags = Some(n);
+        self
+    }
+
+    #[must_use]
+    pub fn max_tags(mut self, n: usize) -> Self {
+        self.max_tags = Some(n);
+        self
+    }
+
+    /// Returns true if the filter doesn't restrict anything.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == SearchFilter::default()
+    }
+
+    /// Returns true if `cached_pin` satisfies all constraints.
+    #[must_use]
+    pub fn matches(&self, cached_pin: &CachedPin) -> bool {
+        let pin = &cached_pin.pin;
+        let tag_count = cached_pin.tag_list.len();
+        self.since.is_none_or(|t| pin.time >= t)
+            && self.until.is_none_or(|t| pin.time < t)
+            && self.toread.is_none_or(|v| pin.toread == v)
+            && self.shared.is_none_or(|v| pin.shared == v)
+            && self
+                .domain
+                .as_ref()
+                .is_none_or(|d| domain_matches(&pin.url, d))
+            && self.min_tags.is_none_or(|n| tag_count >= n)
+            && self.max_tags.is_none_or(|n| tag_count <= n)
+    }
+}
+
+/// Returns true if the host of `url` is `domain` (lowercase) or one of its subdomains.
+pub(crate) fn domain_matches(url: &Url, domain: &str) -> bool {
+    url.host_str().map(str::to_lowercase).is_some_and(|host| {
+        host == domain
+            || host
+                .strip_suffix(domain)
+                .is_some_and(|rest| rest.ends_with('.'))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn filter_pins() {
+        let _ = env_logger::try_init();
+        debug!("filter_pins: starting");
+        let mut pin = PinBuilder::new("https://blog.GitHub.com/post", "title")
+            .tags(["a", "b"])
+            .toread(true)
+            .shared(false)
+            .into_pin()
+            .expect("valid url");
+        pin.time = Utc.with_ymd_and_hms(2020, 6, 1, 0, 0, 0).unwrap();
+        let pin = CachedPin::new(pin);
+        let y = |year| Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+
+        assert!(SearchFilter::new().is_empty());
+        assert!(SearchFilter::new().matches(&pin));
+        assert!(SearchFilter::new()
+            .since(y(2020))
+            .until(y(2021))
+            .matches(&pin));
+        assert!(!SearchFilter::new().since(y(2021)).matches(&pin));
+        assert!(!SearchFilter::new().until(y(2020)).matches(&pin));
+        assert!(SearchFilter::new().toread(true).shared(false).matches(&pin));
+        assert!(!SearchFilter::new().toread(false).matches(&pin));
+        assert!(!SearchFilter::new().shared(true).matches(&pin));
+        assert!(SearchFilter::new().domain("GitHub.com").matches(&pin));
+        assert!(SearchFilter::new().domain("blog.github.com").matches(&pin));
+        assert!(!SearchFilter::new().domain("hub.com").matches(&pin));
+        assert!(SearchFilter::new().min_tags(2).max_tags(2).matches(&pin));
+        assert!(!SearchFilter::new().min_tags(3).matches(&pin));
+        assert!(!SearchFilter::new().max_tags(1).matches(&pin));
+    }
+}
@@ -0,0 +1,183 @@
+//! Tags encoding a hierarchy with a separator, like `lang/rust` or `lang:rust`.
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+use super::cached_data::CachedTag;
+use super::tag::TagFreq;
+
+/// A tag (or a path shared by tags) within the tag hierarchy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagNode {
+    /// Last segment of the path, as written in the most used tag.
+    pub name: String,
+    /// Unicode normalized (nfkd) & lowercased path from the root.
+    pub path: String,
+    /// Number of bookmarks tagged with exactly this path.
+    pub count: usize,
+    /// `count` plus the `total` of all children.
+    pub total: usize,
+    /// Most used first.
+    pub children: Vec<TagNode>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum HierarchyError {
+    #[error("tag hierarchy is disabled, no tag separator is set")]
+    Disabled,
+}
+
+fn segments(tag: &str, separator: char) -> Vec<&str> {
+    tag.split(separator).filter(|s| !s.is_empty()).collect()
+}
+
+/// Builds the forest of tag paths from `tags`, most used first.
+pub(crate) fn build_tree(tags: &[CachedTag], separator: char) -> Vec<TagNode> {
+    debug!("build_tree: starting.");
+    let mut roots: Vec<TagNode> = vec![];
+    for cached_tag in tags {
+        let count = match cached_tag.tag.1 {
+            TagFreq::Used(n) => n,
+            _ => 0,
+        };
+        let keys = segments(&cached_tag.tag_lowered, separator);
+        let names = segments(&cached_tag.tag.0, separator);
+        let mut level = &mut roots;
+        let mut path = String::new();
+        for (i, key) in keys.iter().enumerate() {
+            if i > 0 {
+                path.push(separator);
+            }
+            path.push_str(key);
+            let idx = level
+                .iter()
+                .position(|node| node.path == path)
+                .unwrap_or_else(|| {
+                    // Normalization may split the original tag differently
+                    let name = names.get(i).filter(|_| names.len() == keys.len());
+                    level.push(TagNode {
+                        name: name.unwrap_or(key).to_string(),
+                        path: path.clone(),
+                        count: 0,
+                        total: 0,
+                        children: vec![],
+                    });
+                    level.len() - 1
+                });
+            let node = &mut level[idx];
+            node.total += count;
+            if i + 1 == keys.len() {
+                node.count += count;
+            }
+            level = &mut node.children;
+        }
+    }
+    sort_nodes(&mut roots);
+    roots
+}
+
+fn sort_nodes(nodes: &mut [TagNode]) {
+    nodes.sort_by(|n1, n2| n2.total.cmp(&n1.total).then_with(|| n1.path.cmp(&n2.path)));
+    for node in nodes {
+        sort_nodes(&mut node.children);
+    }
+}
+
+/// `true` if the normalized `tag` is `prefix` or one of its descendants.
+pub(crate) fn in_subtree(tag: &str, prefix: &str, separator: char) -> bool {
+    let prefix = prefix.trim_end_matches(separator);
+    tag.strip_prefix(prefix)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(separator))
+}
+
+/// Returns `tag` with its leading path `old` replaced with `new`, `None` if `tag` isn't within
+/// `old`. Paths are compared unicode normalized (nfkd) & lowercased, like [`in_subtree`].
+pub(crate) fn renamed(tag: &str, old: &str, new: &str, separator: char) -> Option<String> {
+    let normalized = |s: &str| s.nfkd().collect::<String>().to_lowercase();
+    let old = normalized(old.trim_end_matches(separator));
+    let new = new.trim_end_matches(separator);
+    // Normalization may change the length of the path, so try each segment boundary
+    tag.match_indices(separator)
+        .map(|(i, _)| i)
+        .chain([tag.len()])
+        .find(|&i| normalized(&tag[..i]) == old)
+        .map(|i| format!("{new}{}", &tag[i..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::tag::Tag;
+
+    #[test]
+    fn tag_tree() {
+        let _ = env_logger::try_init();
+        debug!("tag_tree: starting");
+        let tags = [
+            ("lang/rust", 5),
+            ("lang/go", 3),
+            ("Lang/Rust/async", 2),
+            ("lang", 1),
+            ("tools", 4),
+            ("/editors//vim/", 1),
+        ]
+        .into_iter()
+        .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+        .collect::<Vec<_>>();
+        let tree = build_tree(&tags, '/');
+        assert_eq!(
+            vec![("lang", 11), ("tools", 4), ("editors", 1)],
+            tree.iter()
+                .map(|n| (n.path.as_str(), n.total))
+                .collect::<Vec<_>>()
+        );
+        let lang = &tree[0];
+        assert_eq!(1, lang.count);
+        assert_eq!(
+            vec![("lang/rust", 7, 5), ("lang/go", 3, 3)],
+            lang.children
+                .iter()
+                .map(|n| (n.path.as_str(), n.total, n.count))
+                .collect::<Vec<_>>()
+        );
+        let rust_async = &lang.children[0].children[0];
+        assert_eq!("async", rust_async.name);
+        assert_eq!("lang/rust/async", rust_async.path);
+        assert_eq!("vim", tree[2].children[0].name);
+
+        // Other separators
+        let tree = build_tree(&tags, ':');
+        assert_eq!(6, tree.len());
+    }
+
+    #[test]
+    fn tag_subtrees() {
+        assert!(in_subtree("lang/rust", "lang", '/'));
+        assert!(in_subtree("lang/rust", "lang/", '/'));
+        assert!(in_subtree("lang", "lang", '/'));
+        assert!(!in_subtree("language", "lang", '/'));
+        assert!(!in_subtree("lang", "lang/rust", '/'));
+
+        assert_eq!(
+            Some("code/rust".to_string()),
+            renamed("Lang/rust", "lang", "code", '/')
+        );
+        assert_eq!(
+            Some("code:rust:async".to_string()),
+            renamed("lang:rust:async", "lang:", "code:", ':')
+        );
+        assert_eq!(
+            Some("code".to_string()),
+            renamed("lang", "lang", "code", '/')
+        );
+        assert_eq!(None, renamed("language", "lang", "code", '/'));
+        // Compatibility forms are renamed like find_tag_subtree finds them
+        assert_eq!(
+            Some("doc/x".to_string()),
+            renamed("\u{fb01}le/x", "file", "doc", '/')
+        );
+        assert_eq!(
+            Some("code/rust".to_string()),
+            renamed("\u{ff2c}ang/rust", "lang", "code", '/')
+        );
+    }
+}
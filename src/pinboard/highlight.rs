@@ -0,0 +1,212 @@
+//! Positions of search matches within bookmarks, e.g. to highlight them in a UI.
+//!
+//! Searches run on unicode normalized (nfkd) & lowercased text, which can have a different
+//! number of characters than the original text. Matched positions are mapped back so that
+//! ranges always refer to characters of the original [`Pin`] fields.
+use std::borrow::Cow;
+use std::ops::Range;
+
+use fuzzy_matcher::FuzzyMatcher;
+use unicode_normalization::UnicodeNormalization;
+
+use super::pin::Pin;
+use super::{SearchType, MATCHER};
+
+/// Field of a bookmark a query matched in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MatchField {
+    Title,
+    /// Tags joined by spaces, see [`Pin::tag_string`].
+    Tags,
+    /// The url as returned by [`Pin::decoded_url`].
+    Url,
+    Description,
+}
+
+/// Matched character ranges within one field.
+///
+/// Ranges are sorted, don't overlap and count `char`s (not bytes) of the original field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldMatch {
+    pub field: MatchField,
+    pub ranges: Vec<Range<usize>>,
+}
+
+/// A bookmark found by [`Pinboard::search_highlighted`](super::Pinboard::search_highlighted)
+/// along with where the queries matched.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlighted<'pin> {
+    pub pin: &'pin Pin<'pin>,
+    pub matches: Vec<FieldMatch>,
+}
+
+/// Normalized & lowercased text along with, for each of its chars, the index of the original
+/// char it came from.
+struct MappedText {
+    text: String,
+    origin: Vec<usize>,
+}
+
+fn normalize_mapped(s: &str) -> MappedText {
+    let mut text = String::with_capacity(s.len());
+    let mut origin = Vec::with_capacity(s.len());
+    for (idx, c) in s.chars().enumerate() {
+        for lowered in c.nfkd().flat_map(char::to_lowercase) {
+            text.push(lowered);
+            origin.push(idx);
+        }
+    }
+    MappedText { text, origin }
+}
+
+/// Char indices (within `mapped.text`) of all non-overlapping occurrences of `query`.
+fn substring_indices(mapped: &MappedText, query: &str) -> Vec<usize> {
+    if query.is_empty() {
+        return vec![];
+    }
+    let query_chars = query.chars().count();
+    mapped
+        .text
+        .match_indices(query)
+        .flat_map(|(byte_idx, _)| {
+            let start = mapped.text[..byte_idx].chars().count();
+            start..start + query_chars
+        })
+        .collect()
+}
+
+/// Merges original char indices into sorted, non-overlapping ranges.
+fn to_ranges(mut indices: Vec<usize>) -> Vec<Range<usize>> {
+    indices.sort_unstable();
+    indices.dedup();
+    let mut ranges: Vec<Range<usize>> = vec![];
+    for idx in indices {
+        match ranges.last_mut() {
+            Some(last) if last.end == idx => last.end = idx + 1,
+            _ => ranges.push(idx..idx + 1),
+        }
+    }
+    ranges
+}
+
+/// Returns where `queries` (normalized & lowercased) match within `fields` of `pin`.
+pub(crate) fn highlight_pin(
+    pin: &Pin,
+    queries: &[String],
+    fields: &[SearchType],
+    fuzzy: bool,
+) -> Vec<FieldMatch> {
+    let mut wanted = fields
+        .iter()
+        .flat_map(|field| match field {
+            SearchType::TitleOnly => vec![MatchField::Title],
+            SearchType::TagOnly => vec![MatchField::Tags],
+            SearchType::UrlOnly => vec![MatchField::Url],
+            SearchType::DescriptionOnly => vec![MatchField::Description],
+            SearchType::TagTitleOnly => vec![MatchField::Title, MatchField::Tags],
+        })
+        .collect::<Vec<MatchField>>();
+    wanted.sort();
+    wanted.dedup();
+
+    wanted
+        .into_iter()
+        .filter_map(|field| {
+            let original: Cow<str> = match field {
+                MatchField::Title => Cow::Borrowed(&pin.title),
+                MatchField::Tags => Cow::Owned(pin.tag_string()),
+                MatchField::Url => Cow::Owned(pin.decoded_url()),
+                MatchField::Description => Cow::Borrowed(pin.extended.as_deref()?),
+            };
+            let mapped = normalize_mapped(&original);
+            let indices = queries
+                .iter()
+                .flat_map(|query| {
+                    if fuzzy {
+                        MATCHER
+                            .fuzzy_indices(&mapped.text, query)
+                            .map(|(_, indices)| indices)
+                            .unwrap_or_default()
+                    } else {
+                        substring_indices(&mapped, query)
+                    }
+                })
+                .filter_map(|idx| mapped.origin.get(idx).copied())
+                .collect::<Vec<usize>>();
+            let ranges = to_ranges(indices);
+            if ranges.is_empty() {
+                None
+            } else {
+                Some(FieldMatch { field, ranges })
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[allow(clippy::single_range_in_vec_init)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn ranges_in_original_text() {
+        let _ = env_logger::try_init();
+        debug!("ranges_in_original_text: starting");
+        // 'ﬁ' (one char) normalizes to "fi" & 'é' to "e" + combining accent
+        let pin = PinBuilder::new("https://example.com/Café", "Ｒust ﬁle Café")
+            .tags(["rust", "café"])
+            .into_pin()
+            .expect("valid url");
+        let fields = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+        ];
+        let queries = vec!["rust".to_string(), "fi".to_string(), "cafe".to_string()];
+        let matches = highlight_pin(&pin, &queries, &fields, false);
+        assert_eq!(
+            vec![
+                FieldMatch {
+                    field: MatchField::Title,
+                    ranges: vec![0..4, 5..6, 9..13],
+                },
+                FieldMatch {
+                    field: MatchField::Tags,
+                    ranges: vec![0..4, 5..9],
+                },
+                FieldMatch {
+                    field: MatchField::Url,
+                    ranges: vec![20..24],
+                },
+            ],
+            matches
+        );
+        let title: Vec<char> = pin.title.chars().collect();
+        assert_eq!("Café", title[9..13].iter().collect::<String>());
+    }
+
+    #[test]
+    fn fuzzy_ranges() {
+        let _ = env_logger::try_init();
+        debug!("fuzzy_ranges: starting");
+        let pin = PinBuilder::new("https://crates.io/crates/failure", "failure - Cargo")
+            .description("Ｅrror handling")
+            .into_pin()
+            .expect("valid url");
+        let fields = [SearchType::TitleOnly, SearchType::DescriptionOnly];
+        let matches = highlight_pin(&pin, &["fcargo".to_string()], &fields, true);
+        assert_eq!(
+            vec![FieldMatch {
+                field: MatchField::Title,
+                ranges: vec![0..1, 10..15],
+            }],
+            matches
+        );
+        let matches = highlight_pin(&pin, &["err".to_string()], &fields, true);
+        assert_eq!(MatchField::Description, matches[0].field);
+        assert_eq!(vec![0..3], matches[0].ranges);
+        assert!(to_ranges(vec![]).is_empty());
+        assert_eq!(vec![1..3, 5..6], to_ranges(vec![5, 2, 1, 2]));
+    }
+}
@@ -0,0 +1,233 @@
+//! In-memory inverted index over cached pins.
+//!
+//! For each searchable field the index maps every trigram (3 consecutive chars) of the
+//! lowercased text to the ids (positions) of the pins containing it. A substring query of at
+//! least 3 chars can only match pins that contain all of its trigrams, so searches only need
+//! to check those pins. Tags are indexed as one space separated string, the way searches match
+//! them, so a query can span tags. Whole tags are also mapped to pins to find pins with an
+//! exact tag.
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use super::cached_data::CachedPin;
+use super::SearchType;
+
+/// Bumped whenever the layout of the index changes so old index files are rebuilt.
+const INDEX_VERSION: u32 = 2;
+
+type Postings = HashMap<u64, Vec<u32>>;
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct SearchIndex {
+    version: u32,
+    /// Identifies the pins the index was built from.
+    fingerprint: u64,
+    title: Postings,
+    tags: Postings,
+    url: Postings,
+    description: Postings,
+    exact_tags: HashMap<String, Vec<u32>>,
+}
+
+/// Packs 3 chars into one key, each char takes at most 21 bits.
+fn trigram_key(a: char, b: char, c: char) -> u64 {
+    (u64::from(a) << 42) | (u64::from(b) << 21) | u64::from(c)
+}
+
+fn trigrams(text: &str) -> Vec<u64> {
+    let chars: Vec<char> = text.chars().collect();
+    chars
+        .windows(3)
+        .map(|w| trigram_key(w[0], w[1], w[2]))
+        .collect()
+}
+
+fn add_postings(postings: &mut Postings, text: &str, id: u32) {
+    for key in trigrams(text) {
+        let ids = postings.entry(key).or_default();
+        // Pins are added in increasing id order so a duplicate can only be the last entry
+        if ids.last() != Some(&id) {
+            ids.push(id);
+        }
+    }
+}
+
+/// Returns sorted ids of pins whose text (in `postings`) may contain `query`.
+fn lookup(postings: &Postings, query_trigrams: &[u64]) -> Vec<u32> {
+    let mut lists = query_trigrams
+        .iter()
+        .map(|key| postings.get(key).map_or(&[][..], Vec::as_slice))
+        .collect::<Vec<&[u32]>>();
+    lists.sort_by_key(|l| l.len());
+    let Some((first, rest)) = lists.split_first() else {
+        return vec![];
+    };
+    first
+        .iter()
+        .filter(|id| rest.iter().all(|l| l.binary_search(id).is_ok()))
+        .copied()
+        .collect()
+}
+
+fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    merged.extend_from_slice(a);
+    merged.extend_from_slice(b);
+    merged.sort_unstable();
+    merged.dedup();
+    merged
+}
+
+fn intersection(a: &[u32], b: &[u32]) -> Vec<u32> {
+    a.iter()
+        .filter(|id| b.binary_search(id).is_ok())
+        .copied()
+        .collect()
+}
+
+impl SearchIndex {
+    /// Builds the index for `pins`, ids are positions within `pins`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are more than `u32::MAX` pins.
+    #[must_use]
+    pub fn build(pins: &[CachedPin]) -> Self {
+        debug!("build: starting");
+        let mut index = SearchIndex {
+            version: INDEX_VERSION,
+            fingerprint: SearchIndex::fingerprint(pins),
+            ..SearchIndex::default()
+        };
+        for (id, cached_pin) in pins.iter().enumerate() {
+            let id = u32::try_from(id).expect("too many pins to index");
+            add_postings(&mut index.title, &cached_pin.title_lowered, id);
+            add_postings(&mut index.url, &cached_pin.url_lowered, id);
+            if let Some(ref extended) = cached_pin.extended_lowered {
+                add_postings(&mut index.description, extended, id);
+            }
+            add_postings(&mut index.tags, &cached_pin.tag_list.join(" "), id);
+            for tag in &cached_pin.tag_list {
+                let ids = index.exact_tags.entry(tag.clone()).or_default();
+                if ids.last() != Some(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        index
+    }
+
+    /// Identifies `pins` by their urls, titles, tags & times.
+    #[must_use]
+    pub fn fingerprint(pins: &[CachedPin]) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        pins.len().hash(&mut hasher);
+        for cached_pin in pins {
+            cached_pin.pin.url.hash(&mut hasher);
+            cached_pin.pin.title.hash(&mut hasher);
+            cached_pin.pin.tags.hash(&mut hasher);
+            cached_pin.pin.extended.hash(&mut hasher);
+            cached_pin.pin.time.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Returns true if the index was built (by this version) from `pins`.
+    #[must_use]
+    pub fn is_valid_for(&self, pins: &[CachedPin]) -> bool {
+        self.version == INDEX_VERSION && self.fingerprint == SearchIndex::fingerprint(pins)
+    }
+
+    /// Returns sorted ids of pins that may contain every one of `queries` in at least one of
+    /// `fields`, or `None` if the index can't narrow the search down (all queries are shorter
+    /// than 3 chars).
+    ///
+    /// `queries` must be normalized & lowercased. Callers still need to check each pin.
+    #[must_use]
+    pub fn candidates(&self, queries: &[String], fields: &[SearchType]) -> Option<Vec<u32>> {
+        let mut result: Option<Vec<u32>> = None;
+        for query in queries {
+            let query_trigrams = trigrams(query);
+            if query_trigrams.is_empty() {
+                continue;
+            }
+            let mut ids: Vec<u32> = vec![];
+            for field in fields {
+                let postings: &[&Postings] = match field {
+                    SearchType::TitleOnly => &[&self.title],
+                    SearchType::TagOnly => &[&self.tags],
+                    SearchType::UrlOnly => &[&self.url],
+                    SearchType::DescriptionOnly => &[&self.description],
+                    SearchType::TagTitleOnly => &[&self.title, &self.tags],
+                };
+                for p in postings {
+                    ids = union(&ids, &lookup(p, &query_trigrams));
+                }
+            }
+            result = Some(match result {
+                Some(prev) => intersection(&prev, &ids),
+                None => ids,
+            });
+        }
+        result
+    }
+
+    /// Returns sorted ids of pins that have `tag` (normalized & lowercased).
+    #[must_use]
+    pub fn pins_with_tag(&self, tag: &str) -> &[u32] {
+        self.exact_tags.get(tag).map_or(&[], Vec::as_slice)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    fn cached_pin(url: &str, title: &str, tags: &str) -> CachedPin<'static> {
+        CachedPin::new(
+            PinBuilder::new(url.to_string(), title.to_string())
+                .tags(tags.split_whitespace())
+                .into_pin()
+                .expect("valid url"),
+        )
+    }
+
+    #[test]
+    fn index_candidates() {
+        let _ = env_logger::try_init();
+        debug!("index_candidates: starting");
+        let pins = vec![
+            cached_pin("https://rust-lang.org/", "Rust language", "rust lang"),
+            cached_pin("https://go.dev/", "Go language", "go lang"),
+            cached_pin("https://GitHub.com/rust", "Trust issues", "misc"),
+        ];
+        let index = SearchIndex::build(&pins);
+        assert!(index.is_valid_for(&pins));
+        assert!(!index.is_valid_for(&pins[1..]));
+
+        let all = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let q = |qs: &[&str], fields: &[SearchType]| {
+            let qs = qs.iter().map(|s| (*s).to_string()).collect::<Vec<String>>();
+            index.candidates(&qs, fields)
+        };
+        assert_eq!(Some(vec![0, 2]), q(&["rust"], &all));
+        assert_eq!(Some(vec![0]), q(&["rust"], &[SearchType::TagOnly]));
+        assert_eq!(Some(vec![0]), q(&["rust lang"], &[SearchType::TagOnly]));
+        assert_eq!(Some(vec![2]), q(&["github"], &[SearchType::UrlOnly]));
+        assert_eq!(Some(vec![0, 1]), q(&["lang", "uage"], &all));
+        assert_eq!(Some(vec![]), q(&["rust", "go.dev"], &all));
+        // Short queries can't use the index
+        assert_eq!(None, q(&["go"], &all));
+        assert_eq!(Some(vec![1]), q(&["go", "go.dev"], &all));
+
+        assert_eq!(&[0, 1], index.pins_with_tag("lang"));
+        assert!(index.pins_with_tag("la").is_empty());
+    }
+}
@@ -0,0 +1,173 @@
+//! Undo journal: the state of bookmarks before each mutating operation, one JSON entry per
+//! line.
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use thiserror::Error;
+
+use super::pin::Pin;
+
+/// Name of the journal file within the cache folder.
+pub const JOURNAL_FN: &str = "journal.jsonl";
+
+/// A journaled operation.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalEntry<'pin> {
+    pub id: u64,
+    pub time: DateTime<Utc>,
+    /// Operation & its arguments, e.g. `delete_tag rust`.
+    pub operation: String,
+    /// Bookmarks as they were before the operation.
+    pub before: Vec<Pin<'pin>>,
+    /// Urls the operation bookmarked for the first time.
+    #[serde(default)]
+    pub created: Vec<String>,
+    #[serde(default)]
+    pub undone: bool,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum JournalError {
+    #[error("undo journal is disabled")]
+    Disabled,
+    #[error("journal entry not found: {0}")]
+    NotFound(u64),
+    #[error("journal entry already undone: {0}")]
+    AlreadyUndone(u64),
+}
+
+#[derive(Debug, Clone)]
+pub struct Journal {
+    path: PathBuf,
+}
+
+impl Journal {
+    #[must_use]
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        Journal {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// All entries, oldest first. A missing journal has no entries.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the journal can't be read or parsed.
+    pub fn entries(&self) -> Result<Vec<JournalEntry<'static>>, Box<dyn std::error::Error>> {
+        debug!("entries: starting.");
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        let mut entries = vec![];
+        for line in BufReader::new(File::open(&self.path)?).lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(serde_json::from_str(&line)?);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Appends an entry, returning its id.
+    pub(crate) fn append(
+        &self,
+        operation: String,
+        before: Vec<Pin>,
+        created: Vec<String>,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        debug!("append: starting.");
+        let id = self.entries()?.last().map_or(1, |e| e.id + 1);
+        let entry = JournalEntry {
+            id,
+            time: Utc::now(),
+            operation,
+            before,
+            created,
+            undone: false,
+        };
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut fp = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(fp, "{}", serde_json::to_string(&entry)?)?;
+        Ok(id)
+    }
+
+    /// Marks entry `id` as undone.
+    pub(crate) fn mark_undone(&self, id: u64) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("mark_undone: starting.");
+        let mut entries = self.entries()?;
+        entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or(JournalError::NotFound(id))?
+            .undone = true;
+        let mut fp = BufWriter::new(File::create(&self.path)?);
+        for entry in &entries {
+            writeln!(fp, "{}", serde_json::to_string(entry)?)?;
+        }
+        fp.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::tests::rand_temp_path;
+
+    #[test]
+    fn journal_entries() {
+        let _ = env_logger::try_init();
+        debug!("journal_entries: starting");
+        let journal = Journal::new(rand_temp_path().join(JOURNAL_FN));
+        assert!(journal.entries().expect("missing journal").is_empty());
+
+        let pin = PinBuilder::new("https://example.com/", "Example")
+            .tags(["rust"])
+            .description("notes")
+            .shared(false)
+            .toread(true)
+            .into_pin()
+            .expect("valid url");
+        let id = journal
+            .append("delete_tag rust".to_string(), vec![pin.clone()], vec![])
+            .expect("couldn't append");
+        assert_eq!(1, id);
+        let id = journal
+            .append(
+                "add https://new.com/".to_string(),
+                vec![],
+                vec!["https://new.com/".to_string()],
+            )
+            .expect("couldn't append");
+        assert_eq!(2, id);
+
+        journal.mark_undone(1).expect("couldn't mark undone");
+        let entries = journal.entries().expect("couldn't read journal");
+        assert_eq!(2, entries.len());
+        assert!(entries[0].undone && !entries[1].undone);
+        assert_eq!(vec![pin], entries[0].before);
+        assert_eq!(vec!["https://new.com/"], entries[1].created);
+        assert_eq!(
+            JournalError::NotFound(3),
+            *journal
+                .mark_undone(3)
+                .expect_err("no such entry")
+                .downcast::<JournalError>()
+                .expect("journal error")
+        );
+    }
+}
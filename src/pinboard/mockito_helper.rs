@@ -10,7 +10,7 @@ pub trait MockBodyGenerate {
 }
 
 #[cfg(test)]
-impl<'a> MockBodyGenerate for &'a str {
+impl MockBodyGenerate for &str {
     fn create_mockito_server(self, endpoint: &str, status: usize) -> Mock {
         mock("GET", Matcher::Regex(endpoint.to_string()))
             .with_status(status)
@@ -42,6 +42,7 @@ pub fn start_mockito_server<T: MockBodyGenerate>(endpoint: &str, status: usize,
 pub fn create_mockito_servers() -> (Mock, Mock) {
     let _ = env_logger::try_init();
     debug!("create_mockito_servers: starting.");
+    crate::tests::isolate_config_dir();
     let m1 = mock("GET", Matcher::Regex(r"^/posts/all.*$".to_string()))
         .with_status(200)
         .with_header("content-type", "application/json")
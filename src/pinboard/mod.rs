@@ -1,8 +1,13 @@
 use std::borrow::Cow;
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
+#[cfg(not(feature = "sqlite"))]
 use std::fs::File;
+use std::marker::PhantomData;
 use std::path::{Path, PathBuf};
 use unicode_normalization::{is_nfkd_quick, IsNormalized, UnicodeNormalization};
 
+#[cfg(any(test, not(feature = "sqlite")))]
 use rmps::Serializer;
 // use serde::Deserialize;
 
@@ -15,9 +20,30 @@ use fuzzy_matcher::FuzzyMatcher;
 use env_logger;
 use lazy_static::lazy_static;
 
+pub mod accounts;
+mod add;
+mod aliases;
 mod api;
+mod bulk;
 mod cached_data;
+pub mod canonical;
 mod config;
+mod cooccurrence;
+mod filter;
+mod hierarchy;
+mod highlight;
+#[cfg(not(feature = "sqlite"))]
+mod index;
+mod journal;
+mod patch;
+pub mod paths;
+mod plan;
+pub mod query;
+mod rank;
+#[cfg(feature = "sqlite")]
+mod sqlite_cache;
+mod store;
+mod suggest;
 
 #[cfg(test)]
 mod mockito_helper;
@@ -26,23 +52,48 @@ mod tests;
 
 pub mod pin;
 pub mod tag;
+mod tag_report;
+mod tag_variants;
 
-use self::cached_data::{CachedData, CachedPin};
-use self::config::Config;
+pub use self::canonical::DuplicatePins;
+pub use self::config::Config;
+pub use self::cooccurrence::{RelatedTags, TagGraph, TagGraphEdge, TagGraphNode};
 
-pub use self::pin::{Pin, PinBuilder};
+pub use self::add::{AddError, AddMode};
+pub use self::aliases::{TagAliases, ALIASES_FN};
+use self::bulk::BulkEdit;
+pub use self::bulk::{BulkEditOutcome, BulkOp, BulkStatus};
+pub use self::cached_data::{CacheFile, CacheInfo, CachedData, CachedPin, CachedTag};
+pub use self::filter::SearchFilter;
+pub use self::hierarchy::{HierarchyError, TagNode};
+pub use self::highlight::{FieldMatch, Highlighted, MatchField};
+pub use self::journal::{Journal, JournalEntry, JournalError, JOURNAL_FN};
+pub use self::patch::{PatchError, PinPatch};
+pub use self::pin::{Pin, PinBuilder, PinValidationError, PinViolation};
+pub use self::plan::{ChangePlan, DryRun, PinChange, TagCountChange};
+pub use self::query::{Query, QueryError};
+pub use self::rank::{RankWeights, RankedPins, RankedTags};
+pub use self::store::{BookmarkStore, MemoryStore, StoreMeta};
 pub use self::tag::{Tag, TagFreq};
+pub use self::tag_report::{StaleTag, TagCountMismatch, TagReport};
+pub use self::tag_variants::TagVariants;
 
 lazy_static! {
     /// Fuzzy matcher used in all search function.
     static ref MATCHER: SkimMatcherV2 = SkimMatcherV2::default().ignore_case();
 }
 
+/// Pinboard client that searches bookmarks kept in a [`BookmarkStore`].
+///
+/// By default bookmarks are cached in files (see [`CachedData`]).
 #[derive(Debug)]
-pub struct Pinboard<'api, 'pin> {
+pub struct Pinboard<'api, 'pin, B = CachedData<'pin>> {
     api: api::Api<'api>,
     cfg: Config,
-    cached_data: CachedData<'pin>,
+    aliases: TagAliases,
+    cached_data: B,
+    journal: Option<Journal>,
+    _pin: PhantomData<Pin<'pin>>,
 }
 
 pub enum CacheState {
@@ -50,8 +101,8 @@ pub enum CacheState {
     Cold,
 }
 #[allow(clippy::module_name_repetitions)]
-pub struct PinboardResult<'api, 'pin> {
-    pub pinboard: Pinboard<'api, 'pin>,
+pub struct PinboardResult<'api, 'pin, B = CachedData<'pin>> {
+    pub pinboard: Pinboard<'api, 'pin, B>,
     pub cache_state: CacheState,
 }
 
@@ -73,16 +124,87 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     {
         let _ = env_logger::try_init();
         let api = api::Api::new(auth_token);
-        let cfg = Config::new();
+        let cfg = Config::load_shared();
 
+        let cached_dir = match (cached_dir, api.user()) {
+            (Some(dir), _) => dir.as_ref().to_path_buf(),
+            (None, Some(user)) => accounts::account_cache_dir(user, None::<PathBuf>)?,
+            (None, None) => paths::default_cache_dir()?,
+        };
         debug!("pinb::new: calling CachedData::new");
-        let mut cached_data = CachedData::new(cached_dir)?;
-        let cache_state = if cached_data.cache_ok() {
+        let cached_data = CachedData::new(Some(&cached_dir))?;
+        let mut result = Pinboard::from_parts(api, cfg, cached_data)?;
+        result.pinboard.aliases = TagAliases::load_shared();
+        result.pinboard.journal = Some(Journal::new(cached_dir.join(JOURNAL_FN)));
+        Ok(result)
+    }
+
+    /// # Errors
+    ///
+    /// Returns an error on file error, or if cached data is readable.
+    pub fn set_cache_dir<P: AsRef<Path>>(
+        &mut self,
+        p: &P,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("set_cache_dir: starting.");
+        self.cached_data.set_cache_dir(p)?;
+        if self.journal.is_some() {
+            self.journal = Some(Journal::new(self.cached_data.cache_dir.join(JOURNAL_FN)));
+        }
+        self.cached_data.load_cache_data_from_file()
+    }
+
+    /// Returns statistics about the local cache.
+    #[must_use]
+    pub fn cache_info(&self) -> CacheInfo {
+        debug!("cache_info: starting.");
+        self.cached_data.info()
+    }
+
+    /// Deletes local cache files.
+    ///
+    /// Searches fail until the cache is filled again by [`Pinboard::update_cache`].
+    /// # Errors
+    ///
+    /// Returns an error if cache files can't be deleted.
+    pub fn clear_cache(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("clear_cache: starting.");
+        self.cached_data.clear()
+    }
+}
+
+impl<'api, 'pin, B: BookmarkStore<'pin>> Pinboard<'api, 'pin, B> {
+    /// Creates a `Pinboard` that keeps bookmarks in `store`.
+    ///
+    /// If `store` doesn't hold valid data, it is filled with bookmarks fetched from the server.
+    /// Unlike [`Pinboard::new`] it starts with default settings and no tag aliases, see
+    /// [`Pinboard::set_config`] & [`Pinboard::set_aliases`] to use the shared ones
+    /// ([`Config::load`] & [`TagAliases::load`]).
+    /// # Errors
+    ///
+    /// Returns an error if network/server or store error occurs.
+    pub fn with_store<S>(
+        auth_token: S,
+        store: B,
+    ) -> Result<PinboardResult<'api, 'pin, B>, Box<dyn std::error::Error>>
+    where
+        S: Into<Cow<'api, str>>,
+    {
+        let _ = env_logger::try_init();
+        Pinboard::from_parts(api::Api::new(auth_token), Config::new(), store)
+    }
+
+    fn from_parts(
+        api: api::Api<'api>,
+        cfg: Config,
+        mut store: B,
+    ) -> Result<PinboardResult<'api, 'pin, B>, Box<dyn std::error::Error>> {
+        let cache_state = if store.is_valid() {
             debug!("pinb::new: cache not missing");
             CacheState::Cold
         } else {
             debug!("pinb::new: cache file missing, calling update");
-            cached_data.update_cache(&api)?;
+            cached_data::refresh_store(&mut store, &api, api.last_update_time())?;
             debug!("pinb::new:   update done.");
             CacheState::Hot
         };
@@ -90,7 +212,10 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         let pinboard = Pinboard {
             api,
             cfg,
-            cached_data,
+            aliases: TagAliases::new(),
+            cached_data: store,
+            journal: None,
+            _pin: PhantomData,
         };
         Ok(PinboardResult {
             pinboard,
@@ -98,16 +223,73 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         })
     }
 
+    /// Username of the account this instance works with.
+    #[must_use]
+    pub fn user(&self) -> Option<&str> {
+        self.api.user()
+    }
+
+    /// Returns the store holding bookmarks & tags.
+    pub fn store(&self) -> &B {
+        &self.cached_data
+    }
+
+    /// Settings used by this instance, loaded from the shared settings file by
+    /// [`Pinboard::new`].
+    #[must_use]
+    pub fn config(&self) -> &Config {
+        &self.cfg
+    }
+
+    pub fn set_config(&mut self, cfg: Config) {
+        debug!("set_config: starting.");
+        self.cfg = cfg;
+    }
+
+    /// Saves current settings to the shared settings file so other tools use them too.
+    ///
     /// # Errors
     ///
-    /// Returns an error on file error, or if cached data is readable.
-    pub fn set_cache_dir<P: AsRef<Path>>(
-        &mut self,
-        p: &P,
-    ) -> Result<(), Box<dyn std::error::Error>> {
-        debug!("set_cache_dir: starting.");
-        self.cached_data.set_cache_dir(p)?;
-        self.cached_data.load_cache_data_from_file()
+    /// Returns an error if the settings file can't be written.
+    pub fn save_config(&self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("save_config: starting.");
+        self.cfg.save()
+    }
+
+    /// Tag aliases used by tag searches, loaded from the shared aliases file by
+    /// [`Pinboard::new`].
+    #[must_use]
+    pub fn aliases(&self) -> &TagAliases {
+        &self.aliases
+    }
+
+    pub fn set_aliases(&mut self, aliases: TagAliases) {
+        debug!("set_aliases: starting.");
+        self.aliases = aliases;
+    }
+
+    /// Saves current tag aliases to the shared aliases file.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the aliases file can't be written.
+    pub fn save_aliases(&self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("save_aliases: starting.");
+        self.aliases.save()
+    }
+
+    /// Returns `p` with aliases among its tags replaced with the tags they stand for, to call
+    /// before adding new bookmarks.
+    #[must_use]
+    pub fn apply_tag_aliases<'p>(&self, p: Pin<'p>) -> Pin<'p> {
+        debug!("apply_tag_aliases: starting.");
+        self.aliases.canonicalize_pin(p)
+    }
+
+    /// Also look up urls on the server (not only in the local cache) when adding bookmarks.
+    pub fn enable_server_check_on_add(&mut self, v: bool) {
+        debug!("enable_server_check_on_add: starting.");
+        self.cfg.check_server_on_add = v;
     }
 
     pub fn enable_tag_only_search(&mut self, v: bool) {
@@ -120,6 +302,30 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cfg.fuzzy_search = v;
     }
 
+    /// Sets weights used by [`Pinboard::search_ranked`].
+    pub fn set_rank_weights(&mut self, weights: RankWeights) {
+        debug!("set_rank_weights: starting.");
+        self.cfg.rank_weights = weights;
+    }
+
+    /// Sets the filter search functions apply to their results, it stays in place for all
+    /// later searches until it is replaced.
+    ///
+    /// `find_url` & `find_tag` are not affected. Use `SearchFilter::default()` to remove it, or
+    /// the `*_filtered` variants (e.g. [`Pinboard::search_filtered`]) to pass a filter for a
+    /// single search instead.
+    pub fn set_search_filter(&mut self, filter: SearchFilter) {
+        debug!("set_search_filter: starting.");
+        self.cfg.search_filter = filter;
+    }
+
+    /// Sets the separator of tag hierarchies (`/` for `lang/rust`), `None` disables the
+    /// hierarchy view.
+    pub fn set_tag_separator(&mut self, separator: Option<char>) {
+        debug!("set_tag_separator: starting.");
+        self.cfg.tag_separator = separator;
+    }
+
     pub fn enable_private_new_pin(&mut self, v: bool) {
         debug!("enable_private_new_pin: starting.");
         self.cfg.private_new_pin = v;
@@ -130,13 +336,194 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         self.cfg.toread_new_pin = v;
     }
 
+    /// Returns the mutating operations in dry-run mode: they return a [`ChangePlan`] computed
+    /// against the cached data and send nothing to the server.
+    #[must_use]
+    pub fn dry_run(&self) -> DryRun<'_, 'api, 'pin, B> {
+        DryRun { pinboard: self }
+    }
+
+    /// Adds (or replaces) a bookmark, same as [`Pinboard::add_pin_with_mode`] with
+    /// [`AddMode::Replace`].
+    ///
     /// # Errors
     ///
-    /// If the pin being added has an invalid url, an error will be returned.
-    pub fn add_pin(&self, p: Pin) -> Result<(), Box<dyn std::error::Error>> {
+    /// Returns an error if network/server error occurs.
+    pub fn add_pin(&mut self, p: Pin<'pin>) -> Result<(), Box<dyn std::error::Error>> {
         debug!("add_pin: starting.");
-        let _r = Url::parse(&p.url)?;
-        self.api.add_url(p)
+        self.add_pin_with_mode(p, AddMode::Replace)
+    }
+
+    /// Adds a bookmark, `mode` decides what happens if its url is already bookmarked.
+    ///
+    /// The url is looked up in the local cache and, if enabled with
+    /// [`Pinboard::enable_server_check_on_add`], on the server. A replaced or merged bookmark
+    /// keeps its creation time. The added bookmark is cached too.
+    ///
+    /// `shared` & `toread` flags not set on the [`PinBuilder`] keep the values of the existing
+    /// bookmark, or get the defaults set by [`Pinboard::enable_private_new_pin`] &
+    /// [`Pinboard::enable_toread_new_pin`] for new bookmarks.
+    /// # Errors
+    ///
+    /// Returns [`AddError::AlreadyExists`] if the url is bookmarked and `mode` is
+    /// [`AddMode::FailIfExists`], or an error if network/server error occurs.
+    pub fn add_pin_with_mode(
+        &mut self,
+        p: Pin<'pin>,
+        mode: AddMode,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("add_pin_with_mode: starting.");
+        let existing = self.existing_pin(&p.url)?;
+        let created = match existing {
+            Some(_) => vec![],
+            None => vec![p.url.to_string()],
+        };
+        let p = self.pin_to_add(p, mode, existing.clone())?;
+        let operation = format!("add {}", p.url);
+        self.api.add_url(p.clone(), mode != AddMode::FailIfExists)?;
+        self.journal(operation, existing.into_iter().collect(), created);
+        self.cached_data.upsert_pin(CachedPin::new(p))
+    }
+
+    /// Returns the bookmark [`Pinboard::add_pin_with_mode`] sends for `p` given the
+    /// `existing` bookmark of its url.
+    fn pin_to_add<'p>(
+        &self,
+        p: Pin<'p>,
+        mode: AddMode,
+        existing: Option<Pin>,
+    ) -> Result<Pin<'p>, AddError> {
+        Ok(match (mode, existing) {
+            (_, None) => {
+                let mut p = p;
+                p.apply_flag_defaults(!self.cfg.private_new_pin, self.cfg.toread_new_pin);
+                p
+            }
+            (AddMode::FailIfExists, Some(_)) => {
+                return Err(AddError::AlreadyExists(p.url.to_string()))
+            }
+            // Flags left unset keep their existing values
+            (AddMode::Replace, Some(existing)) => {
+                let mut p = Pin {
+                    time: existing.time,
+                    ..p
+                };
+                p.apply_flag_defaults(existing.shared, existing.toread);
+                p
+            }
+            (AddMode::Merge, Some(existing)) => add::merge_pins(&existing, p),
+        })
+    }
+
+    /// Finds the bookmark of `url` in the cache or, if enabled, on the server.
+    fn existing_pin(&self, url: &Url) -> Result<Option<Pin<'pin>>, Box<dyn std::error::Error>> {
+        match self.cached_pin(url) {
+            None if self.cfg.check_server_on_add => self.api.get_pin(url.as_str()),
+            cached => Ok(cached),
+        }
+    }
+
+    fn cached_pin(&self, url: &Url) -> Option<Pin<'pin>> {
+        self.cached_data
+            .pins()
+            .and_then(|pins| pins.iter().find(|cached_pin| cached_pin.pin.url == *url))
+            .map(|cached_pin| cached_pin.pin.clone())
+    }
+
+    /// Each url of `selection` once, with its cached bookmark before & after `ops`.
+    fn bulk_edits(&self, selection: &[Url], ops: &[BulkOp]) -> Vec<(Url, BulkEdit<'pin>)> {
+        let mut seen = HashSet::new();
+        selection
+            .iter()
+            .filter(|url| seen.insert(*url))
+            .map(|url| {
+                let edit = self
+                    .cached_pin(url)
+                    .ok_or_else(|| PatchError::NotFound(url.to_string()))
+                    .and_then(|p| {
+                        let edited = bulk::apply_ops(&p, ops)?;
+                        Ok((p, edited))
+                    });
+                (url.clone(), edit)
+            })
+            .collect()
+    }
+
+    /// Edits the bookmark of `url` in place.
+    ///
+    /// The bookmark is read from the cache, or from the server if it isn't cached, and sent
+    /// back in full with `patch` applied so its creation time and other fields are kept. The
+    /// cached pin is updated too.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` can't be parsed, [`PatchError::NotFound`] if it isn't
+    /// bookmarked, [`PatchError::EmptyTitle`] if `patch` clears the title, or an error if
+    /// network/server or store error occurs.
+    pub fn update_pin<T: AsRef<str>>(
+        &mut self,
+        url: T,
+        patch: &PinPatch,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("update_pin: starting.");
+        let url = Url::parse(url.as_ref())?;
+        let existing = match self.cached_pin(&url) {
+            Some(p) => p,
+            None => self
+                .api
+                .get_pin(url.as_str())?
+                .ok_or_else(|| PatchError::NotFound(url.to_string()))?,
+        };
+        let p = patch.apply(&existing)?;
+        self.api.add_url(p.clone(), true)?;
+        self.journal(format!("update_pin {url}"), vec![existing], vec![]);
+        self.cached_data.upsert_pin(CachedPin::new(p))
+    }
+
+    /// Applies `ops` to the cached bookmark of each url of `selection`, returning what happened
+    /// to each of them.
+    ///
+    /// Urls are taken rather than pins so results of e.g. [`Pinboard::search`] or
+    /// [`Pinboard::find_tag`] can be selected while `self` is borrowed by them:
+    /// `pins.iter().map(|p| p.url.clone()).collect()`. Urls that aren't cached get
+    /// [`PatchError::NotFound`].
+    ///
+    /// Only bookmarks changed by `ops` are sent to the server, one call at a time as
+    /// Pinboard's rate limit requires. Each url is edited once even if it is selected more
+    /// than once. Operations apply in the given order, so `[AddTag("x"), RemoveTag("x")]`
+    /// leaves bookmarks without `x`. A failure doesn't stop the remaining bookmarks from being
+    /// edited, the updated ones are also updated in the cache.
+    #[must_use]
+    pub fn bulk_edit(&mut self, selection: &[Url], ops: &[BulkOp]) -> Vec<BulkEditOutcome> {
+        debug!("bulk_edit: starting.");
+        let edits = self.bulk_edits(selection, ops);
+        let mut before = vec![];
+        let mut updated = vec![];
+        let outcomes = edits
+            .into_iter()
+            .map(|(url, edit)| {
+                let status = edit.map_err(Into::into).and_then(|(p, edited)| {
+                    if edited == p {
+                        return Ok(BulkStatus::Unchanged);
+                    }
+                    self.api.throttle();
+                    self.api.add_url(edited.clone(), true)?;
+                    before.push(p);
+                    updated.push(edited);
+                    Ok(BulkStatus::Updated)
+                });
+                if let Err(e) = &status {
+                    info!("bulk_edit: couldn't edit {}: {}", url, e);
+                }
+                BulkEditOutcome { url, status }
+            })
+            .collect();
+        self.journal(format!("bulk_edit {ops:?}"), before, vec![]);
+        let updated = updated.into_iter().map(CachedPin::new).collect();
+        if let Err(e) = self.cached_data.upsert_pins(updated) {
+            info!("bulk_edit: couldn't update cached pins: {}", e);
+        }
+        outcomes
     }
 
     /// # Errors
@@ -144,7 +531,22 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     /// Returns an error if network/server error occurs.
     pub fn delete<T: AsRef<str>>(&self, url: T) -> Result<(), Box<dyn std::error::Error>> {
         debug!("delete: starting.");
-        self.api.delete(url)
+        let before = Url::parse(url.as_ref()).ok().and_then(|u| {
+            self.cached_pin(&u).or_else(|| {
+                // Don't lose bookmarks missing from the cache
+                self.journal.as_ref().and_then(|_| {
+                    self.api
+                        .get_pin(u.as_str())
+                        .map_err(|e| debug!("  couldn't get bookmark: {:?}", e))
+                        .ok()
+                        .flatten()
+                })
+            })
+        });
+        let operation = format!("delete {}", url.as_ref());
+        self.api.delete(url)?;
+        self.journal(operation, before.into_iter().collect(), vec![]);
+        Ok(())
     }
 
     /// # Errors
@@ -164,7 +566,11 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     /// Returns an error if network/server error occurs.
     pub fn delete_tag<T: AsRef<str>>(&self, tag: T) -> Result<(), Box<dyn std::error::Error>> {
         debug!("delete_tag: starting.");
-        self.api.tag_delete(tag)
+        let plan = self.dry_run().delete_tag(tag.as_ref());
+        let operation = format!("delete_tag {}", tag.as_ref());
+        self.api.tag_delete(tag)?;
+        self.journal_plan(operation, plan);
+        Ok(())
     }
 
     /// Rename a tag
@@ -177,16 +583,258 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         new: T,
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!("rename_tag: starting.");
-        self.api.tag_rename(old, new)
+        let plan = self.dry_run().rename_tag(old.as_ref(), new.as_ref());
+        let operation = format!("rename_tag {} {}", old.as_ref(), new.as_ref());
+        self.api.tag_rename(old, new)?;
+        self.journal_plan(operation, plan);
+        Ok(())
+    }
+
+    /// Groups cached tags that only differ by case or unicode normalization and, if
+    /// `max_distance` isn't 0, tags of at least 4 characters that are at most `max_distance`
+    /// edits apart (`pyhton` & `python`).
+    ///
+    /// Each group lists its tags most used first, see [`Pinboard::merge_tags`] to merge them.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn tag_variants(
+        &self,
+        max_distance: usize,
+    ) -> Result<Option<Vec<TagVariants>>, Box<dyn std::error::Error>> {
+        debug!("tag_variants: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let variants =
+            tag_variants::group_variants(self.cached_data.tags().unwrap_or_default(), max_distance);
+        match variants.len() {
+            0 => Ok(None),
+            _ => Ok(Some(variants)),
+        }
+    }
+
+    /// Merges tags `from` into `into` by renaming each of them on the server, then updates
+    /// cached pins & tags the same way.
+    ///
+    /// Renames stop at the first failure, the cache is still updated with the renames that
+    /// succeeded.
+    /// # Errors
+    ///
+    /// Returns an error if network/server error occurs or the cache can't be saved.
+    pub fn merge_tags<T: AsRef<str>>(
+        &mut self,
+        into: &str,
+        from: &[T],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("merge_tags: starting.");
+        let mut merged: Vec<&str> = vec![];
+        let mut result = Ok(());
+        for tag in from.iter().map(AsRef::as_ref).filter(|t| *t != into) {
+            self.api.throttle();
+            if let Err(e) = self.api.tag_rename(tag, into) {
+                result = Err(e);
+                break;
+            }
+            merged.push(tag);
+        }
+        // Only renames that went through can be undone
+        self.journal_plan(
+            format!("merge_tags {into} {}", merged.join(" ")),
+            self.dry_run().merge_tags(into, &merged),
+        );
+
+        if let (false, Some(pins), Some(tags)) = (
+            merged.is_empty(),
+            self.cached_data.pins(),
+            self.cached_data.tags(),
+        ) {
+            let (pins, tags) = tag_variants::merge_cached(pins, tags, into, &merged);
+            self.cached_data.replace_all(pins, tags)?;
+        }
+        result
+    }
+
+    /// Renames tag `old` and every tag under it (`old/...`) so they're under `new` instead,
+    /// using the tag separator set with [`Pinboard::set_tag_separator`].
+    ///
+    /// Cached tags are renamed one at a time with [`Pinboard::rename_tag`], the `(old, new)`
+    /// names are returned. Cached pins & tags are updated for the renames that went through,
+    /// even if a later one fails.
+    /// # Errors
+    ///
+    /// Returns [`HierarchyError::Disabled`] if no tag separator is set, or an error if
+    /// network/server error occurs.
+    pub fn rename_tag_subtree(
+        &mut self,
+        old: &str,
+        new: &str,
+    ) -> Result<Vec<(String, String)>, Box<dyn std::error::Error>> {
+        debug!("rename_tag_subtree: starting.");
+        let separator = self.cfg.tag_separator.ok_or(HierarchyError::Disabled)?;
+        let renames: Vec<(String, String)> = self
+            .cached_data
+            .tags()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|t| {
+                hierarchy::renamed(&t.tag.0, old, new, separator).map(|r| (t.tag.0.clone(), r))
+            })
+            .filter(|(from, to)| from != to)
+            .collect();
+        let mut result = Ok(());
+        let mut renamed = 0;
+        for (from, to) in &renames {
+            self.api.throttle();
+            if let Err(e) = self.rename_tag(from.as_str(), to.as_str()) {
+                result = Err(e);
+                break;
+            }
+            renamed += 1;
+        }
+
+        if let (true, Some(pins), Some(tags)) = (
+            renamed > 0,
+            self.cached_data.pins(),
+            self.cached_data.tags(),
+        ) {
+            let (pins, tags) = renames[..renamed].iter().fold(
+                (pins.to_vec(), tags.to_vec()),
+                |(pins, tags), (from, to)| tag_variants::merge_cached(&pins, &tags, to, &[from]),
+            );
+            self.cached_data.replace_all(pins, tags)?;
+        }
+        result.map(|()| renames)
+    }
+
+    /// Journals mutating operations to `path`, or stops journaling if `path` is `None`.
+    /// Operations are journaled once the server carried them out, failed calls aren't.
+    ///
+    /// [`Pinboard::new`] journals to [`JOURNAL_FN`] in the cache folder, `Pinboard`s created
+    /// with [`Pinboard::with_store`] don't journal unless a path is set.
+    pub fn set_journal_path<P: AsRef<Path>>(&mut self, path: Option<P>) {
+        debug!("set_journal_path: starting.");
+        self.journal = path.map(Journal::new);
+    }
+
+    /// Journaled operations, oldest first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JournalError::Disabled`] if operations aren't journaled, or an error if the
+    /// journal can't be read.
+    pub fn journal_entries(
+        &self,
+    ) -> Result<Vec<JournalEntry<'static>>, Box<dyn std::error::Error>> {
+        debug!("journal_entries: starting.");
+        self.journal
+            .as_ref()
+            .ok_or(JournalError::Disabled)?
+            .entries()
+    }
+
+    /// Undoes the most recent journaled operation that hasn't been undone, returning it.
+    ///
+    /// # Errors
+    ///
+    /// See [`Pinboard::undo`].
+    pub fn undo_last(
+        &mut self,
+    ) -> Result<Option<JournalEntry<'static>>, Box<dyn std::error::Error>> {
+        debug!("undo_last: starting.");
+        match self
+            .journal_entries()?
+            .into_iter()
+            .rev()
+            .find(|e| !e.undone)
+        {
+            Some(entry) => self.undo(entry.id).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Undoes journaled operation `id`: bookmarks it changed or deleted are added back as they
+    /// were, with their original creation time, and bookmarks it created are deleted. The
+    /// cached pins are updated too.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`JournalError::Disabled`] if operations aren't journaled,
+    /// [`JournalError::NotFound`] or [`JournalError::AlreadyUndone`] if `id` can't be undone,
+    /// or an error if network/server error occurs.
+    pub fn undo(&mut self, id: u64) -> Result<JournalEntry<'static>, Box<dyn std::error::Error>> {
+        debug!("undo: starting.");
+        let journal = self.journal.clone().ok_or(JournalError::Disabled)?;
+        let mut entry = journal
+            .entries()?
+            .into_iter()
+            .find(|e| e.id == id)
+            .ok_or(JournalError::NotFound(id))?;
+        if entry.undone {
+            return Err(JournalError::AlreadyUndone(id).into());
+        }
+        for url in &entry.created {
+            self.api.throttle();
+            self.api.delete(url)?;
+            self.cached_data.delete_pin(url)?;
+        }
+        // Cache restored bookmarks at once, even if restoring a later one fails
+        let mut restored = vec![];
+        let result = entry.before.iter().try_for_each(|pin| {
+            self.api.throttle();
+            self.api.add_url(pin.clone(), true)?;
+            restored.push(CachedPin::new(pin.clone()));
+            Ok::<_, Box<dyn std::error::Error>>(())
+        });
+        self.cached_data.upsert_pins(restored)?;
+        result?;
+        journal.mark_undone(id)?;
+        entry.undone = true;
+        Ok(entry)
+    }
+
+    /// Appends the state of bookmarks `before` an operation the server carried out to the
+    /// undo journal, if enabled.
+    ///
+    /// The operation already went through so a journal error is only logged.
+    fn journal(&self, operation: String, before: Vec<Pin>, created: Vec<String>) {
+        match &self.journal {
+            Some(journal) if !before.is_empty() || !created.is_empty() => {
+                match journal.append(operation, before, created) {
+                    Ok(id) => debug!("  journaled as {}", id),
+                    Err(e) => info!("couldn't write undo journal: {}", e),
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn journal_plan(&self, operation: String, plan: ChangePlan) {
+        let created = plan
+            .pins
+            .iter()
+            .filter(|change| change.before.is_none())
+            .filter_map(|change| change.after.as_ref().map(|p| p.url.to_string()))
+            .collect();
+        let before = plan
+            .pins
+            .into_iter()
+            .filter_map(|change| change.before)
+            .collect();
+        self.journal(operation, before, created)
     }
 
     /// Update local cache
+    ///
+    /// The server's update time found by the last [`Pinboard::is_cache_outdated`] call is
+    /// saved along with the cache (see [`CacheInfo`]), the server isn't asked for it again.
     /// # Errors
     ///
     /// Returns error if cache cannot be updated due to file errors or serde erros
     pub fn update_cache(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         debug!("update_cache: starting.");
-        self.cached_data.update_cache(&self.api)
+        let server_update = self.api.last_update_time();
+        cached_data::refresh_store(&mut self.cached_data, &self.api, server_update)
     }
 
     /// Returns list of all Tags (tag, frequency)
@@ -194,18 +842,16 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     pub fn list_tag_pairs(&self) -> Option<Vec<&Tag>> {
         debug!("list_tag_pairs: starting.");
         self.cached_data
-            .tags
-            .as_ref()
+            .tags()
             .map(|t| t.iter().map(|d| &d.tag).collect())
     }
 
     /// Returns list of all bookmarks
     #[must_use]
-    pub fn list_bookmarks(&self) -> Option<Vec<&Pin>> {
+    pub fn list_bookmarks(&self) -> Option<Vec<&Pin<'_>>> {
         debug!("list_bookmarks: starting.");
         self.cached_data
-            .pins
-            .as_ref()
+            .pins()
             .map(|v| v.iter().map(|p| &p.pin).collect())
     }
 
@@ -221,6 +867,99 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         let _r = Url::parse(url.as_ref())?;
         self.api.suggest_tags(url)
     }
+
+    /// Suggests tags for a bookmark from the user's own cached bookmarks, without calling the
+    /// server: tags of bookmarks from the same domain or with similar titles, tags spelled out
+    /// in `title` or `description` and tags often used along with those. Best suggestion first.
+    /// # Errors
+    ///
+    /// Returns error if `url` cannot be parsed or cached data is not readable.
+    pub fn suggest_tags_local<T: AsRef<str>>(
+        &self,
+        url: T,
+        title: &str,
+        description: &str,
+    ) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        debug!("suggest_tags_local: starting.");
+        let url = Url::parse(url.as_ref())?;
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let pins = self.cached_data.pins().unwrap_or_default();
+        Ok(suggest::suggest_tags(pins, &url, title, description)
+            .into_iter()
+            .map(|(_, tag)| tag)
+            .collect())
+    }
+
+    /// Returns up to `n` tags most often used along with `tag` in cached bookmarks, with the
+    /// number of bookmarks using both. Tags are compared after unicode normalization, ignoring
+    /// case.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn related_tags<S: AsRef<str>>(
+        &self,
+        tag: S,
+        n: usize,
+    ) -> Result<Option<RelatedTags>, Box<dyn std::error::Error>> {
+        debug!("related_tags: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let tag = tag.as_ref().nfkd().collect::<String>().to_lowercase();
+        let cooccurrence =
+            cooccurrence::TagCooccurrence::new(self.cached_data.pins().unwrap_or_default());
+        let r = cooccurrence
+            .related(&tag)
+            .into_iter()
+            .take(n)
+            .map(|(t, count)| (cooccurrence.name(t).to_string(), count))
+            .collect::<RelatedTags>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
+    /// Builds the graph of cached tags: nodes count the bookmarks using each tag, edges the
+    /// bookmarks using both tags. Edges of less than `min_weight` bookmarks are left out.
+    ///
+    /// See [`TagGraph::to_json`] & [`TagGraph::to_dot`] to export it.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn tag_graph(&self, min_weight: usize) -> Result<TagGraph, Box<dyn std::error::Error>> {
+        debug!("tag_graph: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        Ok(
+            cooccurrence::TagCooccurrence::new(self.cached_data.pins().unwrap_or_default())
+                .graph(min_weight),
+        )
+    }
+
+    /// Reports tags worth cleaning up: tags no cached bookmark uses, tags used only once, tags
+    /// whose count in the tags cache disagrees with cached bookmarks and, if `since` is given,
+    /// tags not used by any bookmark created since then.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn tag_report(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<TagReport, Box<dyn std::error::Error>> {
+        debug!("tag_report: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        Ok(tag_report::tag_report(
+            self.cached_data.pins().unwrap_or_default(),
+            self.cached_data.tags().unwrap_or_default(),
+            since,
+        ))
+    }
 }
 
 #[derive(Debug)]
@@ -233,47 +972,77 @@ pub enum SearchType {
 }
 
 // Search functions
-impl<'api, 'pin> Pinboard<'api, 'pin> {
+impl<'api, 'pin, B: BookmarkStore<'pin>> Pinboard<'api, 'pin, B> {
     /// Searches all the fields within bookmarks to filter them.
     /// This function honors `[pinboard::config::Config]` settings for fuzzy search & `tag_only` search.
+    ///
+    /// Results are restricted by the filter set with [`Pinboard::set_search_filter`], which
+    /// stays in place between calls. See [`Pinboard::search_items_filtered`] to pass one.
     /// # Errors
     ///
     /// If cached data is not readable an error is returned.
     pub fn search_items(
         &self,
         query: &str,
-    ) -> Result<Option<Vec<&Pin>>, Box<dyn std::error::Error>> {
+    ) -> Result<Option<Vec<&Pin<'_>>>, Box<dyn std::error::Error>> {
         debug!("search_items: starting.");
+        self.search_items_filtered(query, &self.cfg.search_filter)
+    }
+
+    /// Same as [`Pinboard::search_items`] but restricted by `filter` instead of the one set
+    /// with [`Pinboard::set_search_filter`].
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn search_items_filtered(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+    ) -> Result<Option<Vec<&Pin<'_>>>, Box<dyn std::error::Error>> {
+        debug!("search_items_filtered: starting.");
         let query = if is_nfkd_quick(query.chars()) == IsNormalized::Yes {
             query.into()
         } else {
             query.chars().nfkd().collect::<String>()
         };
         let q = &query.to_lowercase();
-        if self.cached_data.cache_ok() {
+        if self.cached_data.is_valid() {
+            let fields: &[SearchType] = if self.cfg.tag_only_search {
+                &[SearchType::TagOnly]
+            } else {
+                &[
+                    SearchType::TitleOnly,
+                    SearchType::TagOnly,
+                    SearchType::UrlOnly,
+                    SearchType::DescriptionOnly,
+                ]
+            };
+            // Fuzzy matches can't be narrowed down by the search index
+            let indexed_queries = if self.cfg.fuzzy_search {
+                vec![]
+            } else {
+                vec![q.clone()]
+            };
             let r = self
                 .cached_data
-                .pins
-                .as_ref()
-                .map(|p| {
-                    p.iter()
-                        .filter(|item: &&CachedPin| {
-                            if self.cfg.tag_only_search {
-                                if self.cfg.fuzzy_search {
-                                    item.pin.tag_contains(&query, Some(&MATCHER))
-                                } else {
-                                    item.pin.tag_contains(q, None)
-                                }
-                            } else if self.cfg.fuzzy_search {
-                                item.pin.contains_fuzzy(&query, &MATCHER)
-                            } else {
-                                item.pin.contains(q)
-                            }
-                        })
-                        .map(|item| &item.pin)
-                        .collect::<Vec<&Pin>>()
+                .searchable_pins(&indexed_queries, fields)
+                .into_iter()
+                .filter(|item| filter.matches(item))
+                .filter(|item: &&CachedPin| {
+                    if self.cfg.tag_only_search {
+                        if self.cfg.fuzzy_search {
+                            item.pin.tag_contains(&query, Some(&MATCHER))
+                        } else {
+                            item.pin.tag_contains(q, None)
+                        }
+                    } else if self.cfg.fuzzy_search {
+                        item.pin.contains_fuzzy(&query, &MATCHER)
+                    } else {
+                        item.pin.contains(q)
+                    }
                 })
-                .unwrap_or_default();
+                .map(|item| &item.pin)
+                .collect::<Vec<&Pin>>();
             match r.len() {
                 0 => Ok(None),
                 _ => Ok(Some(r)),
@@ -284,8 +1053,64 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         }
     }
 
+    /// Searches bookmarks using the [query language](query).
+    ///
+    /// `search_query("tag:rust -tag:old title:macro site:github.com")`
+    ///
+    /// Fuzzy search & `tag_only` settings don't apply to these queries. Results are restricted
+    /// by the filter set with [`Pinboard::set_search_filter`], which stays in place between
+    /// calls. See [`Pinboard::search_query_filtered`] to pass one.
+    /// # Errors
+    ///
+    /// Returns a [`QueryError`] if `query` can't be parsed or an error if cached data is not
+    /// readable.
+    pub fn search_query(
+        &self,
+        query: &str,
+    ) -> Result<Option<Vec<&Pin<'_>>>, Box<dyn std::error::Error>> {
+        debug!("search_query: starting.");
+        self.search_query_filtered(query, &self.cfg.search_filter)
+    }
+
+    /// Same as [`Pinboard::search_query`] but restricted by `filter` instead of the one set
+    /// with [`Pinboard::set_search_filter`].
+    /// # Errors
+    ///
+    /// Returns a [`QueryError`] if `query` can't be parsed or an error if cached data is not
+    /// readable.
+    pub fn search_query_filtered(
+        &self,
+        query: &str,
+        filter: &SearchFilter,
+    ) -> Result<Option<Vec<&Pin<'_>>>, Box<dyn std::error::Error>> {
+        debug!("search_query_filtered: starting.");
+        let query = Query::parse(query)?;
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let all_fields = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let r = self
+            .cached_data
+            .searchable_pins(&query.required_terms(), &all_fields)
+            .into_iter()
+            .filter(|cached_pin| filter.matches(cached_pin))
+            .filter(|cached_pin| query.matches(cached_pin))
+            .map(|cached_pin| &cached_pin.pin)
+            .collect::<Vec<&Pin>>();
+        match r.len() {
+            0 => Ok(None),
+            _ => Ok(Some(r)),
+        }
+    }
+
     /// Search tags for `query` (uses cached tags).
-    /// Returns all tags that _contain_ query.
+    /// Returns all tags that _contain_ query, or any tag it is an alias of or alias for (see
+    /// [`TagAliases`]).
     /// This function honors [`pinboard::config::Config`] settings for fuzzy search.
     /// # Errors
     ///
@@ -295,30 +1120,34 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         query: &str,
     ) -> Result<Option<Vec<&Tag>>, Box<dyn std::error::Error>> {
         debug!("search_list_of_tags: starting.");
-        if self.cached_data.cache_ok() {
+        if self.cached_data.is_valid() {
             let query = if is_nfkd_quick(query.chars()) == IsNormalized::Yes {
                 query.into()
             } else {
                 query.chars().nfkd().collect::<String>()
             };
             let q = &query.to_lowercase();
+            let queries = self.aliases.expand(q);
+            let indexed_query = if self.cfg.fuzzy_search || queries.len() > 1 {
+                ""
+            } else {
+                q
+            };
             let r = self
                 .cached_data
-                .tags
-                .as_ref()
-                .map(|t| {
-                    t.iter()
-                        .filter(|item| {
-                            if self.cfg.fuzzy_search {
-                                MATCHER.fuzzy_match(&item.tag.0, &query).is_some()
-                            } else {
-                                item.tag_lowered.contains(q)
-                            }
-                        })
-                        .map(|ct| &ct.tag)
-                        .collect::<Vec<&Tag>>()
+                .searchable_tags(indexed_query)
+                .into_iter()
+                .filter(|item| {
+                    if self.cfg.fuzzy_search {
+                        queries
+                            .iter()
+                            .any(|q| MATCHER.fuzzy_match(&item.tag.0, q).is_some())
+                    } else {
+                        queries.iter().any(|q| item.tag_lowered.contains(q))
+                    }
                 })
-                .unwrap_or_default();
+                .map(|ct| &ct.tag)
+                .collect::<Vec<&Tag>>();
             match r.len() {
                 0 => Ok(None),
                 _ => Ok(Some(r)),
@@ -333,10 +1162,11 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
     // https://github.com/sharkdp/hexyl
     // should be considered identical (?!)
 
-    /// Finds all pins whose url is an exact match of the `q`
+    /// Finds all pins whose url is the same as `q` once both are canonicalized (see
+    /// [`canonical`]), ignoring case.
     ///
-    /// `find_url("http://google.com/public")` will match
-    /// <http://google.com/public>
+    /// `find_url("http://www.google.com/public/?utm_source=feed")` will match
+    /// <https://google.com/public>
     /// but not
     /// <http://google.com/public#fragment>
     ///
@@ -351,18 +1181,22 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         S: AsRef<str>,
     {
         debug!("find_url: starting.");
-        if !self.cached_data.cache_ok() {
+        if !self.cached_data.is_valid() {
             return Err("Cache data is invalid.".into());
         }
-        let query = &q.as_ref().to_lowercase();
+        let Some(query) = canonical::canonical_str(q.as_ref()).map(|c| c.to_lowercase()) else {
+            return Ok(None);
+        };
         let results = self
             .cached_data
-            .pins
-            .as_ref()
-            .map(|p: &Vec<CachedPin<'pin>>| {
+            .pins()
+            .map(|p: &[CachedPin<'pin>]| {
                 p.iter()
                     .filter(|cached_pin: &&CachedPin<'pin>| {
-                        cached_pin.pin.url.to_lowercase().as_str() == query
+                        canonical::canonicalize(&cached_pin.pin.url)
+                            .as_str()
+                            .to_lowercase()
+                            == query
                     })
                     .map(|p| &p.pin)
                     .collect::<Vec<&'pin Pin>>()
@@ -374,7 +1208,40 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         }
     }
 
-    /// Finds all pins with an exact tag of 'query'
+    /// Groups cached pins whose urls have the same canonical form (see [`canonical`]).
+    ///
+    /// Only groups of at least two pins are returned, in the order their first pin is cached.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn find_duplicates(
+        &'pin self,
+    ) -> Result<Option<DuplicatePins<'pin>>, Box<dyn std::error::Error>> {
+        debug!("find_duplicates: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let mut groups: DuplicatePins<'pin> = vec![];
+        let mut group_of: HashMap<String, usize> = HashMap::new();
+        for cached_pin in self.cached_data.pins().unwrap_or_default() {
+            let canonical_url = canonical::canonicalize(&cached_pin.pin.url);
+            match group_of.entry(canonical_url.as_str().to_lowercase()) {
+                Entry::Occupied(e) => groups[*e.get()].1.push(&cached_pin.pin),
+                Entry::Vacant(e) => {
+                    e.insert(groups.len());
+                    groups.push((canonical_url.into(), vec![&cached_pin.pin]));
+                }
+            }
+        }
+        groups.retain(|(_, pins)| pins.len() > 1);
+        match groups.len() {
+            0 => Ok(None),
+            _ => Ok(Some(groups)),
+        }
+    }
+
+    /// Finds all pins with an exact tag of 'query', or of any tag it is an alias of or alias
+    /// for (see [`TagAliases`]).
     ///
     /// # Errors
     ///
@@ -387,7 +1254,7 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         S: AsRef<str>,
     {
         debug!("find_tag: starting.");
-        if !self.cached_data.cache_ok() {
+        if !self.cached_data.is_valid() {
             return Err("Cache data is invalid.".into());
         }
 
@@ -401,17 +1268,78 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
                 .collect::<String>()
                 .to_lowercase()
         };
+        let tags = self.aliases.expand(&query);
+        let mut seen = HashSet::new();
+        let mut results = tags
+            .iter()
+            .flat_map(|tag| self.cached_data.pins_with_tag(tag))
+            .map(|p| &p.pin)
+            .filter(|p| seen.insert(std::ptr::from_ref(*p)))
+            .collect::<Vec<&'pin Pin>>();
+        if tags.len() > 1 {
+            results.sort_by_key(|p| std::cmp::Reverse(p.time));
+        }
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+
+    /// Builds the tag hierarchy from cached tags, splitting them with the separator set with
+    /// [`Pinboard::set_tag_separator`]. Each node counts the bookmarks of its tag & all tags
+    /// under it.
+    /// # Errors
+    ///
+    /// Returns [`HierarchyError::Disabled`] if no tag separator is set, or an error if cached
+    /// data is not readable.
+    pub fn tag_tree(&self) -> Result<Option<Vec<TagNode>>, Box<dyn std::error::Error>> {
+        debug!("tag_tree: starting.");
+        let separator = self.cfg.tag_separator.ok_or(HierarchyError::Disabled)?;
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let tree = hierarchy::build_tree(self.cached_data.tags().unwrap_or_default(), separator);
+        match tree.len() {
+            0 => Ok(None),
+            _ => Ok(Some(tree)),
+        }
+    }
+
+    /// Finds all pins tagged with `prefix` or any tag under it: `find_tag_subtree("lang")`
+    /// finds pins tagged `lang`, `lang/rust` or `lang/rust/async` but not `language`.
+    ///
+    /// Like [`Pinboard::find_tag`], tags are compared after unicode normalization, ignoring
+    /// case.
+    /// # Errors
+    ///
+    /// Returns [`HierarchyError::Disabled`] if no tag separator is set, or an error if cached
+    /// data is not readable.
+    pub fn find_tag_subtree<S>(
+        &'pin self,
+        prefix: S,
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Box<dyn std::error::Error>>
+    where
+        S: AsRef<str>,
+    {
+        debug!("find_tag_subtree: starting.");
+        let separator = self.cfg.tag_separator.ok_or(HierarchyError::Disabled)?;
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let prefix = prefix.as_ref().nfkd().collect::<String>().to_lowercase();
         let results = self
             .cached_data
-            .pins
-            .as_ref()
-            .map(|p: &Vec<CachedPin<'pin>>| {
-                p.iter()
-                    .filter(|cached_pin: &&CachedPin<'pin>| cached_pin.tag_list.contains(&query))
-                    .map(|p| &p.pin)
-                    .collect::<Vec<&'pin Pin>>()
+            .pins()
+            .unwrap_or_default()
+            .iter()
+            .filter(|cached_pin| {
+                cached_pin
+                    .tag_list
+                    .iter()
+                    .any(|t| hierarchy::in_subtree(t, &prefix, separator))
             })
-            .unwrap_or_default();
+            .map(|cached_pin| &cached_pin.pin)
+            .collect::<Vec<&'pin Pin>>();
         match results.len() {
             0 => Ok(None),
             _ => Ok(Some(results)),
@@ -420,11 +1348,13 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
 
     /// Searches the selected `fields` within bookmarks to filter them.
     /// It will return bookmarks that have ALL of search queries provided in 'q' somewhere in the
-    /// specified 'fields' of the bookmark.
+    /// specified 'fields' of the bookmark. Tags also match queries through [`TagAliases`].
     /// This function honors [`pinboard::config::Config`] settings for fuzzy search only.
+    ///
+    /// Results are restricted by the filter set with [`Pinboard::set_search_filter`], which
+    /// stays in place between calls. See [`Pinboard::search_filtered`] to pass one.
     /// # Errors
     /// If cached data is not readable an error will be returned.
-    #[allow(clippy::too_many_lines)]
     pub fn search<'b, I, S>(
         &'pin self,
         q: &'b I,
@@ -435,7 +1365,26 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
         S: AsRef<str>,
     {
         debug!("search: starting.");
-        if !self.cached_data.cache_ok() {
+        self.search_filtered(q, fields, &self.cfg.search_filter)
+    }
+
+    /// Same as [`Pinboard::search`] but restricted by `filter` instead of the one set with
+    /// [`Pinboard::set_search_filter`].
+    /// # Errors
+    /// If cached data is not readable an error will be returned.
+    #[allow(clippy::too_many_lines)]
+    pub fn search_filtered<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+        filter: &SearchFilter,
+    ) -> Result<Option<Vec<&'pin Pin<'pin>>>, Box<dyn std::error::Error>>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_filtered: starting.");
+        if !self.cached_data.is_valid() {
             return Err("Cache data is invalid.".into());
         }
         // When no field is specified, search everywhere
@@ -457,81 +1406,107 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
             .into_iter()
             .map(|s| s.as_ref().chars().nfkd().collect::<String>().to_lowercase())
             .collect::<Vec<String>>();
+        let tag_queries = normalized_queires
+            .iter()
+            .map(|query| {
+                if search_fields
+                    .iter()
+                    .any(|f| matches!(f, SearchType::TagOnly | SearchType::TagTitleOnly))
+                {
+                    self.aliases.expand(query)
+                } else {
+                    vec![query.clone()]
+                }
+            })
+            .collect::<Vec<Vec<String>>>();
+        // Pins matching through an alias may not contain the query itself
+        let indexed_queries = normalized_queires
+            .iter()
+            .zip(&tag_queries)
+            .filter(|(_, tag_query)| tag_query.len() == 1)
+            .map(|(query, _)| query.clone())
+            .collect::<Vec<String>>();
         #[allow(clippy::if_not_else)]
         let results = if !self.cfg.fuzzy_search {
             self.cached_data
-                .pins
-                .as_ref()
-                .map(|p: &Vec<CachedPin<'pin>>| {
-                    p.iter()
-                        .filter(|cached_pin: &&CachedPin<'pin>| {
-                            normalized_queires.iter().all(|query| {
-                                search_fields.iter().any(|search_type| match *search_type {
-                                    SearchType::TitleOnly => {
-                                        cached_pin.title_lowered.contains(query)
-                                    }
-                                    SearchType::TagOnly => {
-                                        cached_pin.tag_list.iter().any(|tag| tag.contains(query))
-                                    }
-                                    SearchType::UrlOnly => {
-                                        cached_pin.pin.url.as_ref().to_lowercase().contains(query)
+                .searchable_pins(&indexed_queries, search_fields)
+                .into_iter()
+                .filter(|cached_pin| filter.matches(cached_pin))
+                .filter(|cached_pin: &&CachedPin<'pin>| {
+                    normalized_queires
+                        .iter()
+                        .zip(&tag_queries)
+                        .all(|(query, tag_query)| {
+                            search_fields.iter().any(|search_type| match *search_type {
+                                SearchType::TitleOnly => cached_pin.title_lowered.contains(query),
+                                SearchType::TagOnly => cached_pin
+                                    .tag_list
+                                    .iter()
+                                    .any(|tag| tag_query.iter().any(|q| tag.contains(q))),
+                                SearchType::UrlOnly => cached_pin.url_lowered.contains(query),
+                                SearchType::DescriptionOnly => {
+                                    if let Some(ref extended) = cached_pin.extended_lowered {
+                                        extended.contains(query)
+                                    } else {
+                                        false
                                     }
-                                    SearchType::DescriptionOnly => {
-                                        if let Some(ref extended) = cached_pin.extended_lowered {
-                                            extended.contains(query)
-                                        } else {
-                                            false
-                                        }
-                                    }
-                                    SearchType::TagTitleOnly => {
-                                        cached_pin.title_lowered.contains(query)
-                                            || cached_pin.tag_list.contains(query)
-                                    }
-                                })
+                                }
+                                SearchType::TagTitleOnly => {
+                                    cached_pin.title_lowered.contains(query)
+                                        || tag_query.iter().any(|q| cached_pin.tag_list.contains(q))
+                                }
                             })
                         })
-                        .map(|p| &p.pin)
-                        .collect::<Vec<&'pin Pin>>()
                 })
-                .unwrap_or_default()
+                .map(|p| &p.pin)
+                .collect::<Vec<&'pin Pin>>()
         } else {
             self.cached_data
-                .pins
-                .as_ref()
+                .pins()
                 .map(|p| {
                     p.iter()
+                        .filter(|cached_pin| filter.matches(cached_pin))
                         .filter(|cached_pin: &&CachedPin| {
-                            normalized_queires.iter().all(|qi| {
-                                search_fields.iter().any(|search_type| match *search_type {
-                                    SearchType::TitleOnly => MATCHER
-                                        .fuzzy_match(&cached_pin.title_lowered, qi.as_ref())
-                                        .is_some(),
-                                    SearchType::TagOnly => cached_pin
-                                        .tag_list
-                                        .iter()
-                                        .any(|t| MATCHER.fuzzy_match(t, qi.as_ref()).is_some()),
-                                    SearchType::UrlOnly => MATCHER
-                                        .fuzzy_match(cached_pin.pin.url.as_ref(), qi.as_ref())
-                                        .is_some(),
-                                    SearchType::DescriptionOnly => {
-                                        if let Some(ref extended) = cached_pin.extended_lowered {
+                            normalized_queires
+                                .iter()
+                                .zip(&tag_queries)
+                                .all(|(qi, tag_qi)| {
+                                    search_fields.iter().any(|search_type| match *search_type {
+                                        SearchType::TitleOnly => MATCHER
+                                            .fuzzy_match(&cached_pin.title_lowered, qi.as_ref())
+                                            .is_some(),
+                                        SearchType::TagOnly => {
+                                            cached_pin.tag_list.iter().any(|t| {
+                                                tag_qi
+                                                    .iter()
+                                                    .any(|q| MATCHER.fuzzy_match(t, q).is_some())
+                                            })
+                                        }
+                                        SearchType::UrlOnly => MATCHER
+                                            .fuzzy_match(&cached_pin.url_lowered, qi.as_ref())
+                                            .is_some(),
+                                        SearchType::DescriptionOnly => {
+                                            if let Some(ref extended) = cached_pin.extended_lowered
+                                            {
+                                                MATCHER
+                                                    .fuzzy_match(extended.as_str(), qi.as_ref())
+                                                    .is_some()
+                                            } else {
+                                                false
+                                            }
+                                        }
+                                        SearchType::TagTitleOnly => {
                                             MATCHER
-                                                .fuzzy_match(extended.as_str(), qi.as_ref())
+                                                .fuzzy_match(&cached_pin.title_lowered, qi.as_ref())
                                                 .is_some()
-                                        } else {
-                                            false
+                                                || cached_pin.tag_list.iter().any(|t| {
+                                                    tag_qi.iter().any(|q| {
+                                                        MATCHER.fuzzy_match(t, q).is_some()
+                                                    })
+                                                })
                                         }
-                                    }
-                                    SearchType::TagTitleOnly => {
-                                        MATCHER
-                                            .fuzzy_match(&cached_pin.title_lowered, qi.as_ref())
-                                            .is_some()
-                                            || cached_pin.tag_list.iter().any(|t| {
-                                                MATCHER.fuzzy_match(t, qi.as_ref()).is_some()
-                                            })
-                                    }
+                                    })
                                 })
-                            })
                         })
                         .map(|p| &p.pin)
                         .collect::<Vec<&Pin>>()
@@ -544,4 +1519,183 @@ impl<'api, 'pin> Pinboard<'api, 'pin> {
             _ => Ok(Some(results)),
         }
     }
+
+    /// Same as [`Pinboard::search`] but also returns where queries matched within each
+    /// bookmark.
+    ///
+    /// Matched ranges are character offsets in the original (not normalized) bookmark fields.
+    /// This function honors [`pinboard::config::Config`] settings for fuzzy search only.
+    ///
+    /// Like [`Pinboard::search`], results are restricted by the filter set with
+    /// [`Pinboard::set_search_filter`], which stays in place between calls.
+    /// # Errors
+    /// If cached data is not readable an error will be returned.
+    pub fn search_highlighted<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+    ) -> Result<Option<Vec<Highlighted<'pin>>>, Box<dyn std::error::Error>>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_highlighted: starting.");
+        let normalized_queries = q
+            .into_iter()
+            .map(|s| s.as_ref().chars().nfkd().collect::<String>().to_lowercase())
+            .collect::<Vec<String>>();
+        let all_fields = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let search_fields = if fields.is_empty() {
+            all_fields.as_slice()
+        } else {
+            fields
+        };
+        Ok(self
+            .search::<Vec<String>, &String>(&normalized_queries, search_fields)?
+            .map(|pins| {
+                pins.into_iter()
+                    .map(|pin| Highlighted {
+                        pin,
+                        matches: highlight::highlight_pin(
+                            pin,
+                            &normalized_queries,
+                            search_fields,
+                            self.cfg.fuzzy_search,
+                        ),
+                    })
+                    .collect()
+            }))
+    }
+
+    /// Same as [`Pinboard::search`] but returns bookmarks along with their score, best match
+    /// first.
+    ///
+    /// Matches in each field are weighted by [`RankWeights`] (see
+    /// [`Pinboard::set_rank_weights`]) and scores of all queries are added up. Bookmarks with
+    /// equal scores are kept newest first.
+    /// This function honors [`pinboard::config::Config`] settings for fuzzy search only.
+    ///
+    /// Results are restricted by the filter set with [`Pinboard::set_search_filter`], which
+    /// stays in place between calls. See [`Pinboard::search_ranked_filtered`] to pass one.
+    /// # Errors
+    /// If cached data is not readable an error will be returned.
+    pub fn search_ranked<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+    ) -> Result<Option<RankedPins<'pin>>, Box<dyn std::error::Error>>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_ranked: starting.");
+        self.search_ranked_filtered(q, fields, &self.cfg.search_filter)
+    }
+
+    /// Same as [`Pinboard::search_ranked`] but restricted by `filter` instead of the one set
+    /// with [`Pinboard::set_search_filter`].
+    /// # Errors
+    /// If cached data is not readable an error will be returned.
+    pub fn search_ranked_filtered<'b, I, S>(
+        &'pin self,
+        q: &'b I,
+        fields: &[SearchType],
+        filter: &SearchFilter,
+    ) -> Result<Option<RankedPins<'pin>>, Box<dyn std::error::Error>>
+    where
+        &'b I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        debug!("search_ranked_filtered: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Cache data is invalid.".into());
+        }
+        let all_fields = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let search_fields = if fields.is_empty() {
+            all_fields.as_slice()
+        } else {
+            fields
+        };
+        let normalized_queries = q
+            .into_iter()
+            .map(|s| s.as_ref().chars().nfkd().collect::<String>().to_lowercase())
+            .collect::<Vec<String>>();
+        let indexed_queries: &[String] = if self.cfg.fuzzy_search {
+            &[]
+        } else {
+            &normalized_queries
+        };
+        let now = Utc::now();
+        let mut results = self
+            .cached_data
+            .searchable_pins(indexed_queries, search_fields)
+            .into_iter()
+            .filter(|cached_pin| filter.matches(cached_pin))
+            .filter_map(|cached_pin| {
+                rank::score_pin(
+                    cached_pin,
+                    &normalized_queries,
+                    search_fields,
+                    &self.cfg.rank_weights,
+                    self.cfg.fuzzy_search,
+                    now,
+                )
+                .map(|score| (score, &cached_pin.pin))
+            })
+            .collect::<Vec<(f64, &'pin Pin)>>();
+        results.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
+
+    /// Same as [`Pinboard::search_list_of_tags`] but returns tags along with their score, best
+    /// match first. Tags with equal scores are kept most used first.
+    /// # Errors
+    ///
+    /// If cached data is not readable an error is returned.
+    pub fn search_list_of_tags_ranked(
+        &self,
+        query: &str,
+    ) -> Result<Option<RankedTags<'_>>, Box<dyn std::error::Error>> {
+        debug!("search_list_of_tags_ranked: starting.");
+        if !self.cached_data.is_valid() {
+            return Err("Tags cache data is invalid".into());
+        }
+        let query = if is_nfkd_quick(query.chars()) == IsNormalized::Yes {
+            query.into()
+        } else {
+            query.chars().nfkd().collect::<String>()
+        };
+        let (query, indexed_query) = if self.cfg.fuzzy_search {
+            (query, String::new())
+        } else {
+            let q = query.to_lowercase();
+            (q.clone(), q)
+        };
+        let mut results = self
+            .cached_data
+            .searchable_tags(&indexed_query)
+            .into_iter()
+            .filter_map(|ct| {
+                rank::score_tag(ct, &query, self.cfg.fuzzy_search).map(|score| (score, &ct.tag))
+            })
+            .collect::<Vec<(f64, &Tag)>>();
+        results.sort_by(|(s1, _), (s2, _)| s2.total_cmp(s1));
+        match results.len() {
+            0 => Ok(None),
+            _ => Ok(Some(results)),
+        }
+    }
 }
@@ -0,0 +1,194 @@
+//! Partial edits of an existing bookmark.
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use super::pin::Pin;
+
+/// Changes to apply to a bookmark with
+/// [`Pinboard::update_pin`](super::Pinboard::update_pin).
+///
+/// Fields that aren't set keep their current value.
+///
+/// ```
+/// use rusty_pin::pinboard::PinPatch;
+///
+/// let patch = PinPatch::new()
+///     .title("Rust book")
+///     .clear_description()
+///     .toread(false)
+///     .add_tag("rust")
+///     .remove_tag("todo");
+/// assert!(!patch.is_empty());
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PinPatch {
+    title: Option<String>,
+    /// `Some(None)` clears the description.
+    description: Option<Option<String>>,
+    shared: Option<bool>,
+    toread: Option<bool>,
+    add_tags: Vec<String>,
+    remove_tags: Vec<String>,
+}
+
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
+pub enum PatchError {
+    #[error("bookmark not found: {0}")]
+    NotFound(String),
+    #[error("bookmark title can't be empty")]
+    EmptyTitle,
+}
+
+impl PinPatch {
+    #[must_use]
+    pub fn new() -> Self {
+        PinPatch::default()
+    }
+
+    #[must_use]
+    pub fn title<T: Into<String>>(mut self, title: T) -> Self {
+        self.title = Some(title.into());
+        self
+    }
+
+    #[must_use]
+    pub fn description<T: Into<String>>(mut self, description: T) -> Self {
+        self.description = Some(Some(description.into()));
+        self
+    }
+
+    #[must_use]
+    pub fn clear_description(mut self) -> Self {
+        self.description = Some(None);
+        self
+    }
+
+    #[must_use]
+    pub fn shared(mut self, shared: bool) -> Self {
+        self.shared = Some(shared);
+        self
+    }
+
+    #[must_use]
+    pub fn toread(mut self, toread: bool) -> Self {
+        self.toread = Some(toread);
+        self
+    }
+
+    /// Adds `tag` unless the bookmark already has it (ignoring case).
+    #[must_use]
+    pub fn add_tag<T: Into<String>>(mut self, tag: T) -> Self {
+        self.add_tags.push(tag.into());
+        self
+    }
+
+    /// Removes `tag` (ignoring case), tags are removed before new ones are added.
+    #[must_use]
+    pub fn remove_tag<T: Into<String>>(mut self, tag: T) -> Self {
+        self.remove_tags.push(tag.into());
+        self
+    }
+
+    /// `true` if applying the patch wouldn't change anything.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        *self == PinPatch::default()
+    }
+
+    /// Returns a copy of `pin` with the patch applied, keeping its url & creation time.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`PatchError::EmptyTitle`] if the patch sets an empty title.
+    pub fn apply<'pin>(&self, pin: &Pin<'pin>) -> Result<Pin<'pin>, PatchError> {
+        debug!("apply: starting.");
+        let mut patched = pin.clone();
+        if let Some(title) = &self.title {
+            if title.trim().is_empty() {
+                return Err(PatchError::EmptyTitle);
+            }
+            patched.title = Cow::Owned(title.clone());
+        }
+        if let Some(description) = &self.description {
+            patched.extended = description.clone().map(Cow::Owned);
+        }
+        if let Some(shared) = self.shared {
+            patched.shared = shared;
+        }
+        if let Some(toread) = self.toread {
+            patched.toread = toread;
+        }
+
+        patched.tags.retain(|tag| {
+            !self
+                .remove_tags
+                .iter()
+                .any(|removed| removed.to_lowercase() == tag.to_lowercase())
+        });
+        for tag in &self.add_tags {
+            if !patched
+                .tags
+                .iter()
+                .any(|t| t.to_lowercase() == tag.to_lowercase())
+            {
+                patched.tags.push(tag.clone());
+            }
+        }
+        Ok(patched)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn apply_patch() {
+        let _ = env_logger::try_init();
+        debug!("apply_patch: starting");
+        let mut pin = PinBuilder::new("https://example.com/", "Example")
+            .tags(["rust", "Todo", "lang"])
+            .description("notes")
+            .shared(true)
+            .toread(true)
+            .into_pin()
+            .expect("valid url");
+        pin.time -= chrono::Duration::days(10);
+
+        let patched = PinPatch::new().apply(&pin).expect("empty patch");
+        assert_eq!(pin, patched);
+
+        let patched = PinPatch::new()
+            .title("New title")
+            .clear_description()
+            .toread(false)
+            .remove_tag("todo")
+            .add_tag("Rust")
+            .add_tag("book")
+            .apply(&pin)
+            .expect("valid patch");
+        assert_eq!("New title", patched.title);
+        assert_eq!(None, patched.extended);
+        assert!(patched.shared);
+        assert!(!patched.toread);
+        assert_eq!(vec!["rust", "lang", "book"], patched.tags);
+        assert_eq!(pin.url, patched.url);
+        assert_eq!(pin.time, patched.time);
+
+        let patched = PinPatch::new()
+            .description("other notes")
+            .apply(&pin)
+            .expect("valid patch");
+        assert_eq!(Some("other notes"), patched.extended.as_deref());
+        assert_eq!(pin.title, patched.title);
+
+        assert_eq!(
+            Err(PatchError::EmptyTitle),
+            PinPatch::new().title(" ").apply(&pin)
+        );
+        assert!(PinPatch::new().is_empty());
+        assert!(!PinPatch::new().shared(false).is_empty());
+    }
+}
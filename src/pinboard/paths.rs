@@ -0,0 +1,75 @@
+//! Default folders used to keep cached data & settings.
+//!
+//! Cached data goes to the platform cache folder (`$XDG_CACHE_HOME/rusty-pin` or
+//! `~/.cache/rusty-pin` on Linux) and settings go to the platform config folder. Both can be
+//! overridden with the `RUSTY_PIN_CACHE_DIR` and `RUSTY_PIN_CONFIG_DIR` environment variables.
+use std::ffi::OsString;
+use std::path::PathBuf;
+
+use thiserror::Error;
+
+const APP_DIR: &str = "rusty-pin";
+pub const CACHE_DIR_ENV: &str = "RUSTY_PIN_CACHE_DIR";
+pub const CONFIG_DIR_ENV: &str = "RUSTY_PIN_CONFIG_DIR";
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PathError {
+    #[error("can't find a cache folder, set {CACHE_DIR_ENV} or pass a cache folder explicitly")]
+    NoCacheDir,
+    #[error("can't find a config folder, set {CONFIG_DIR_ENV}")]
+    NoConfigDir,
+}
+
+/// Returns the default folder for cached data.
+///
+/// # Errors
+///
+/// Returns an error if `RUSTY_PIN_CACHE_DIR` is not set and the platform has no cache folder.
+pub fn default_cache_dir() -> Result<PathBuf, PathError> {
+    debug!("default_cache_dir: starting.");
+    resolve_dir(std::env::var_os(CACHE_DIR_ENV), dirs::cache_dir()).ok_or(PathError::NoCacheDir)
+}
+
+/// Returns the default folder for settings.
+///
+/// # Errors
+///
+/// Returns an error if `RUSTY_PIN_CONFIG_DIR` is not set and the platform has no config folder.
+pub fn default_config_dir() -> Result<PathBuf, PathError> {
+    debug!("default_config_dir: starting.");
+    resolve_dir(std::env::var_os(CONFIG_DIR_ENV), dirs::config_dir()).ok_or(PathError::NoConfigDir)
+}
+
+/// A non-empty `env_override` wins, otherwise `rusty-pin` within `platform_dir` is used.
+fn resolve_dir(env_override: Option<OsString>, platform_dir: Option<PathBuf>) -> Option<PathBuf> {
+    env_override
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| platform_dir.map(|dir| dir.join(APP_DIR)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_dirs() {
+        let _ = env_logger::try_init();
+        debug!("resolve_dirs: starting");
+        let platform = Some(PathBuf::from("/home/user/.cache"));
+        assert_eq!(
+            Some(PathBuf::from("/home/user/.cache/rusty-pin")),
+            resolve_dir(None, platform.clone())
+        );
+        assert_eq!(
+            Some(PathBuf::from("/tmp/pins")),
+            resolve_dir(Some("/tmp/pins".into()), platform.clone())
+        );
+        assert_eq!(
+            Some(PathBuf::from("/home/user/.cache/rusty-pin")),
+            resolve_dir(Some("".into()), platform)
+        );
+        assert_eq!(None, resolve_dir(None, None));
+    }
+}
@@ -3,21 +3,118 @@
 use std::borrow::Cow;
 
 use chrono::prelude::*;
+use percent_encoding::percent_decode_str;
+use thiserror::Error;
+use url::Url;
 
 use fuzzy_matcher::{skim::SkimMatcherV2, FuzzyMatcher};
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+/// A bookmark.
+///
+/// (De)serializes to Pinboard's format: flags are "yes"/"no" and tags are separated by
+/// spaces.
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Pin<'pin> {
     #[serde(rename = "href")]
-    pub url: Cow<'pin, str>,
+    pub url: Url,
     #[serde(rename = "description")]
     pub title: Cow<'pin, str>,
-    pub tags: Cow<'pin, str>,
-    pub shared: Cow<'pin, str>,
-    pub toread: Cow<'pin, str>,
+    #[serde(with = "space_separated", default)]
+    pub tags: Vec<String>,
+    #[serde(with = "yes_no")]
+    pub shared: bool,
+    #[serde(with = "yes_no")]
+    pub toread: bool,
     pub extended: Option<Cow<'pin, str>>,
+    /// Changes whenever the bookmark is edited on the server.
+    #[serde(default)]
+    pub meta: Option<Cow<'pin, str>>,
+    /// Hash of the url, set by the server.
+    #[serde(default)]
+    pub hash: Option<Cow<'pin, str>>,
     #[serde(default = "Utc::now")]
     pub time: DateTime<Utc>,
+    /// Flags left unset by [`PinBuilder`], they get the account's defaults when the pin is
+    /// added.
+    #[serde(skip)]
+    pub(crate) unset_flags: UnsetFlags,
+}
+
+#[derive(Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub(crate) struct UnsetFlags {
+    pub shared: bool,
+    pub toread: bool,
+}
+
+/// Compares bookmark fields only, flags left unset by [`PinBuilder`] are ignored.
+impl PartialEq for Pin<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.url == other.url
+            && self.title == other.title
+            && self.tags == other.tags
+            && self.shared == other.shared
+            && self.toread == other.toread
+            && self.extended == other.extended
+            && self.meta == other.meta
+            && self.hash == other.hash
+            && self.time == other.time
+    }
+}
+
+impl Eq for Pin<'_> {}
+
+/// Decodes the (punycode) host & percent-encoded characters of `url`.
+pub(crate) fn decode_url(url: &Url) -> String {
+    let mut decoded = url.as_str().to_string();
+    if let Some(host) = url.host_str() {
+        let (unicode_host, result) = idna::domain_to_unicode(host);
+        if result.is_ok() && unicode_host != host {
+            decoded = decoded.replacen(host, &unicode_host, 1);
+        }
+    }
+    percent_decode_str(&decoded)
+        .decode_utf8_lossy()
+        .into_owned()
+}
+
+/// (De)serializes flags as "yes"/"no".
+mod yes_no {
+    use serde::de::{self, Deserialize, Deserializer, Unexpected};
+    use serde::Serializer;
+
+    #[allow(clippy::trivially_copy_pass_by_ref)]
+    pub fn serialize<S: Serializer>(flag: &bool, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(if *flag { "yes" } else { "no" })
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        match s.as_str() {
+            "yes" => Ok(true),
+            "no" => Ok(false),
+            other => Err(de::Error::invalid_value(
+                Unexpected::Str(other),
+                &"\"yes\" or \"no\"",
+            )),
+        }
+    }
+}
+
+/// (De)serializes a list of tags as one space separated string.
+mod space_separated {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    #[allow(clippy::ptr_arg)]
+    pub fn serialize<S: Serializer>(tags: &Vec<String>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&tags.join(" "))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        Ok(s.split_whitespace().map(ToString::to_string).collect())
+    }
 }
 
 use unicode_normalization::{is_nfkd_quick, IsNormalized};
@@ -27,6 +124,31 @@ impl<'pin> Pin<'pin> {
         self.time
     }
 
+    /// Url with its host & percent-encoded characters decoded, as users type & read it.
+    ///
+    /// Searches match against this form of the url.
+    #[must_use]
+    pub fn decoded_url(&self) -> String {
+        decode_url(&self.url)
+    }
+
+    /// Sets `shared` & `toread` to the given defaults unless they were set explicitly.
+    pub(crate) fn apply_flag_defaults(&mut self, shared: bool, toread: bool) {
+        if self.unset_flags.shared {
+            self.shared = shared;
+        }
+        if self.unset_flags.toread {
+            self.toread = toread;
+        }
+        self.unset_flags = UnsetFlags::default();
+    }
+
+    /// Tags separated by spaces, the way Pinboard sends & expects them.
+    #[must_use]
+    pub fn tag_string(&self) -> String {
+        self.tags.join(" ")
+    }
+
     /// # Panics
     ///
     /// It pancis if the `q` is not a normalized unicode (nfk)
@@ -34,8 +156,8 @@ impl<'pin> Pin<'pin> {
     pub fn contains(&self, q: &str) -> bool {
         assert!(is_nfkd_quick(q.chars()) == IsNormalized::Yes);
         self.title.to_lowercase().contains(q)
-            || self.tags.to_lowercase().contains(q)
-            || self.url.as_ref().contains(q)
+            || self.tag_string().to_lowercase().contains(q)
+            || self.decoded_url().contains(q)
             || if let Some(ref extended) = self.extended {
                 extended.to_lowercase().contains(q)
             } else {
@@ -59,9 +181,9 @@ impl<'pin> Pin<'pin> {
     pub fn tag_contains(&self, q: &str, matcher: Option<&SkimMatcherV2>) -> bool {
         assert!(is_nfkd_quick(q.chars()) == IsNormalized::Yes);
         if let Some(matcher) = matcher {
-            matcher.fuzzy_match(&self.tags, q).is_some()
+            matcher.fuzzy_match(&self.tag_string(), q).is_some()
         } else {
-            self.tags.to_lowercase().contains(q)
+            self.tag_string().to_lowercase().contains(q)
         }
     }
 
@@ -72,9 +194,9 @@ impl<'pin> Pin<'pin> {
     pub fn url_contains(&self, q: &str, matcher: Option<&SkimMatcherV2>) -> bool {
         assert!(is_nfkd_quick(q.chars()) == IsNormalized::Yes);
         if let Some(matcher) = matcher {
-            matcher.fuzzy_match(&self.url, q).is_some()
+            matcher.fuzzy_match(&self.decoded_url(), q).is_some()
         } else {
-            self.url.to_lowercase().contains(q)
+            self.decoded_url().to_lowercase().contains(q)
         }
     }
 
@@ -100,9 +222,9 @@ impl<'pin> Pin<'pin> {
     /// It pancis if the `q` is not a normalized unicode (nfk)
     pub fn contains_fuzzy(&self, q: &str, matcher: &SkimMatcherV2) -> bool {
         assert!(is_nfkd_quick(q.chars()) == IsNormalized::Yes);
-        matcher.fuzzy_match(&self.tags, q).is_some()
+        matcher.fuzzy_match(&self.tag_string(), q).is_some()
             || matcher.fuzzy_match(&self.title, q).is_some()
-            || matcher.fuzzy_match(self.url.as_ref(), q).is_some()
+            || matcher.fuzzy_match(&self.decoded_url(), q).is_some()
             || if let Some(ref extended) = self.extended {
                 matcher.fuzzy_match(extended, q).is_some()
             } else {
@@ -111,10 +233,66 @@ impl<'pin> Pin<'pin> {
     }
 }
 
+/// Url schemes Pinboard accepts.
+pub const ALLOWED_SCHEMES: [&str; 7] = [
+    "http",
+    "https",
+    "javascript",
+    "mailto",
+    "ftp",
+    "file",
+    "feed",
+];
+/// Maximum number of characters in a title.
+pub const MAX_TITLE_CHARS: usize = 255;
+/// Maximum number of characters in a description.
+pub const MAX_EXTENDED_CHARS: usize = 65_536;
+/// Maximum number of tags of a bookmark.
+pub const MAX_TAGS: usize = 100;
+/// Maximum number of characters in a tag.
+pub const MAX_TAG_CHARS: usize = 255;
+
+/// A field of a bookmark breaking one of Pinboard's rules.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum PinViolation {
+    #[error("invalid url: {0}")]
+    InvalidUrl(url::ParseError),
+    #[error("unsupported url scheme: {0}")]
+    UnsupportedScheme(String),
+    #[error("title is empty")]
+    EmptyTitle,
+    #[error("title has {0} characters, at most {MAX_TITLE_CHARS} are allowed")]
+    TitleTooLong(usize),
+    #[error("{0} tags, at most {MAX_TAGS} are allowed")]
+    TooManyTags(usize),
+    #[error("tag is empty")]
+    EmptyTag,
+    #[error("tag `{0}` has more than {MAX_TAG_CHARS} characters")]
+    TagTooLong(String),
+    #[error("tag `{0}` has a comma or whitespace")]
+    InvalidTagCharacter(String),
+    #[error("description has {0} characters, at most {MAX_EXTENDED_CHARS} are allowed")]
+    DescriptionTooLong(usize),
+}
+
+/// Error returned by [`PinBuilder::try_build`] listing every violation found.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid bookmark: {}", .violations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "))]
+pub struct PinValidationError {
+    pub violations: Vec<PinViolation>,
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug)]
 pub struct PinBuilder<'pin> {
-    pin: Pin<'pin>,
+    url: Cow<'pin, str>,
+    title: Cow<'pin, str>,
+    tags: Vec<String>,
+    shared: Option<bool>,
+    toread: Option<bool>,
+    extended: Option<Cow<'pin, str>>,
 }
 
 impl<'pin> PinBuilder<'pin> {
@@ -122,47 +300,120 @@ impl<'pin> PinBuilder<'pin> {
     where
         S: Into<Cow<'pin, str>>,
     {
-        let pin = Pin {
+        PinBuilder {
             url: url.into(),
             title: title.into(),
-            time: Utc::now(),
-            tags: Cow::from(""),
-            shared: Cow::from(""),
-            toread: Cow::from(""),
+            tags: vec![],
+            shared: None,
+            toread: None,
             extended: None,
-        };
-        PinBuilder { pin }
+        }
     }
 }
 
 impl<'pin> PinBuilder<'pin> {
     #[must_use]
-    pub fn tags<S: Into<Cow<'pin, str>>>(mut self, t: S) -> Self {
-        self.pin.tags = t.into();
+    pub fn tags<I, T>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<String>,
+    {
+        self.tags = tags.into_iter().map(Into::into).collect();
         self
     }
 
+    /// If not set, the pin is shared unless the account makes new pins private.
     #[must_use]
-    pub fn shared<S: Into<Cow<'pin, str>>>(mut self, f: S) -> Self {
-        self.pin.shared = f.into();
+    pub fn shared(mut self, f: bool) -> Self {
+        self.shared = Some(f);
         self
     }
 
+    /// If not set, the account's default for new pins is used.
     #[must_use]
-    pub fn toread<S: Into<Cow<'pin, str>>>(mut self, f: S) -> Self {
-        self.pin.toread = f.into();
+    pub fn toread(mut self, f: bool) -> Self {
+        self.toread = Some(f);
         self
     }
 
     #[must_use]
     pub fn description<S: Into<Cow<'pin, str>>>(mut self, x: S) -> Self {
-        self.pin.extended = Some(x.into());
+        self.extended = Some(x.into());
         self
     }
 
-    #[must_use]
-    pub fn into_pin(self) -> Pin<'pin> {
-        self.pin
+    /// Checks all fields against Pinboard's limits before building the pin.
+    ///
+    /// # Errors
+    ///
+    /// Returns all violations found if any field is invalid.
+    pub fn try_build(self) -> Result<Pin<'pin>, PinValidationError> {
+        debug!("try_build: starting");
+        let mut violations = vec![];
+        match Url::parse(&self.url) {
+            Ok(url) if !ALLOWED_SCHEMES.contains(&url.scheme()) => {
+                violations.push(PinViolation::UnsupportedScheme(url.scheme().to_string()));
+            }
+            Ok(_) => {}
+            Err(e) => violations.push(PinViolation::InvalidUrl(e)),
+        }
+
+        let title_chars = self.title.chars().count();
+        if self.title.trim().is_empty() {
+            violations.push(PinViolation::EmptyTitle);
+        } else if title_chars > MAX_TITLE_CHARS {
+            violations.push(PinViolation::TitleTooLong(title_chars));
+        }
+
+        if self.tags.len() > MAX_TAGS {
+            violations.push(PinViolation::TooManyTags(self.tags.len()));
+        }
+        for tag in &self.tags {
+            if tag.is_empty() {
+                violations.push(PinViolation::EmptyTag);
+            } else if tag.chars().count() > MAX_TAG_CHARS {
+                violations.push(PinViolation::TagTooLong(tag.clone()));
+            } else if tag.chars().any(|c| c == ',' || c.is_whitespace()) {
+                violations.push(PinViolation::InvalidTagCharacter(tag.clone()));
+            }
+        }
+
+        if let Some(extended_chars) = self.extended.as_ref().map(|e| e.chars().count()) {
+            if extended_chars > MAX_EXTENDED_CHARS {
+                violations.push(PinViolation::DescriptionTooLong(extended_chars));
+            }
+        }
+
+        if violations.is_empty() {
+            self.into_pin().map_err(|e| PinValidationError {
+                violations: vec![PinViolation::InvalidUrl(e)],
+            })
+        } else {
+            Err(PinValidationError { violations })
+        }
+    }
+
+    /// Builds the pin without checking Pinboard's limits, see [`PinBuilder::try_build`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the url can't be parsed.
+    pub fn into_pin(self) -> Result<Pin<'pin>, url::ParseError> {
+        Ok(Pin {
+            url: Url::parse(&self.url)?,
+            title: self.title,
+            tags: self.tags,
+            shared: self.shared.unwrap_or(false),
+            toread: self.toread.unwrap_or(false),
+            extended: self.extended,
+            meta: None,
+            hash: None,
+            time: Utc::now(),
+            unset_flags: UnsetFlags {
+                shared: self.shared.is_none(),
+                toread: self.toread.is_none(),
+            },
+        })
     }
 }
 
@@ -179,16 +430,86 @@ mod tests {
         let _ = env_logger::try_init();
         debug!("test_builder: starting");
         let p = PinBuilder::new("https://githuуй.com/Здравствуйт?q=13#fragment", "title")
-            .tags("tag1 tag2")
-            .into_pin();
+            .tags(["tag1", "tag2"])
+            .into_pin()
+            .expect("valid url");
         assert_eq!(p.title, "title");
         assert_eq!(
-            &p.url,
-            "https://githuуй.com/Здравствуйт?q=13#fragment" // Url::parse("https://githuуй.com/Здравствуйт?q=13#fragment")
-                                                            //     .expect("impossible")
-                                                            //     .as_str()
+            p.url,
+            Url::parse("https://githuуй.com/Здравствуйт?q=13#fragment").expect("impossible")
+        );
+        assert_eq!(
+            p.decoded_url(),
+            "https://githuуй.com/Здравствуйт?q=13#fragment"
+        );
+        assert_eq!(p.tags, vec!["tag1", "tag2"]);
+        assert_eq!(p.tag_string(), "tag1 tag2");
+        assert!(!p.shared && !p.toread);
+        assert_eq!(
+            UnsetFlags {
+                shared: true,
+                toread: true
+            },
+            p.unset_flags
+        );
+        // Unset flags don't leak into comparisons, e.g. with the pin sent back by the server
+        let round_trip: Pin =
+            serde_json::from_str(&serde_json::to_string(&p).expect("serializable pin"))
+                .expect("valid pin");
+        assert_eq!(p, round_trip);
+
+        let mut p = PinBuilder::new("https://example.com", "title")
+            .toread(false)
+            .into_pin()
+            .expect("valid url");
+        p.apply_flag_defaults(true, true);
+        assert!(p.shared);
+        assert!(!p.toread);
+        assert!(PinBuilder::new("not a url", "title").into_pin().is_err());
+    }
+
+    #[test]
+    fn pin_builder_validation() {
+        let _ = env_logger::try_init();
+        debug!("pin_builder_validation: starting");
+        let pin = PinBuilder::new("https://example.com", "title")
+            .tags(["rust", ".private"])
+            .description("description")
+            .try_build()
+            .expect("valid pin");
+        assert_eq!(pin.tags, vec!["rust", ".private"]);
+
+        let long_tag = "t".repeat(MAX_TAG_CHARS + 1);
+        let err = PinBuilder::new("gopher://example.com", " ")
+            .tags(vec!["a,b".to_string(), String::new(), long_tag.clone()])
+            .description("d".repeat(MAX_EXTENDED_CHARS + 1))
+            .try_build()
+            .expect_err("invalid pin");
+        assert_eq!(
+            vec![
+                PinViolation::UnsupportedScheme("gopher".to_string()),
+                PinViolation::EmptyTitle,
+                PinViolation::InvalidTagCharacter("a,b".to_string()),
+                PinViolation::EmptyTag,
+                PinViolation::TagTooLong(long_tag),
+                PinViolation::DescriptionTooLong(MAX_EXTENDED_CHARS + 1),
+            ],
+            err.violations
+        );
+
+        let err = PinBuilder::new("not a url".to_string(), "t".repeat(MAX_TITLE_CHARS + 1))
+            .tags((0..=MAX_TAGS).map(|i| i.to_string()))
+            .try_build()
+            .expect_err("invalid pin");
+        assert_eq!(
+            vec![
+                PinViolation::InvalidUrl(url::ParseError::RelativeUrlWithoutBase),
+                PinViolation::TitleTooLong(MAX_TITLE_CHARS + 1),
+                PinViolation::TooManyTags(MAX_TAGS + 1),
+            ],
+            err.violations
         );
-        assert_eq!(p.tags, "tag1 tag2");
+        assert!(err.to_string().starts_with("invalid bookmark: invalid url"));
     }
 
     #[test]
@@ -199,8 +520,9 @@ mod tests {
             "http://правительство.рф",
             "An open source ecosystem for IoT development · PlatformIO",
         )
-        .tags("tag1 tag2")
-        .into_pin();
+        .tags(["tag1", "tag2"])
+        .into_pin()
+        .expect("valid url");
 
         assert!(p.contains("·"));
         assert!(p.contains("· PlatformIO".to_lowercase().as_str()));
@@ -305,7 +627,7 @@ mod tests {
             assert!(pins.is_some());
             let pins = pins.unwrap();
             assert_eq!(pins.len(), 1);
-            assert_eq!(&pins[0].url, "https://crates.io/crates/failure");
+            assert_eq!(pins[0].url.as_str(), "https://crates.io/crates/failure");
         }
 
         {
@@ -317,7 +639,7 @@ mod tests {
             assert!(pins.is_some());
             let pins = pins.unwrap();
             assert_eq!(pins.len(), 1);
-            assert_eq!(&pins[0].url, "https://crates.io/crates/failure");
+            assert_eq!(pins[0].url.as_str(), "https://crates.io/crates/failure");
         }
     }
 }
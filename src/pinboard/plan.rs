@@ -0,0 +1,287 @@
+//! Dry runs: what mutating operations would change, computed against the cached data.
+use std::collections::BTreeMap;
+
+use url::Url;
+
+use super::add::AddMode;
+use super::bulk::BulkOp;
+use super::cached_data::CachedTag;
+use super::patch::{PatchError, PinPatch};
+use super::pin::Pin;
+use super::store::BookmarkStore;
+use super::tag::TagFreq;
+use super::tag_variants;
+use super::Pinboard;
+
+/// A bookmark before & after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PinChange<'pin> {
+    /// `None` if the bookmark would be added.
+    pub before: Option<Pin<'pin>>,
+    /// `None` if the bookmark would be deleted.
+    pub after: Option<Pin<'pin>>,
+}
+
+/// Number of bookmarks using a tag before & after an operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCountChange {
+    pub tag: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// What an operation would change.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangePlan<'pin> {
+    /// Affected bookmarks, in the order the operation would send them.
+    pub pins: Vec<PinChange<'pin>>,
+    /// Tags whose count would change, sorted by name.
+    pub tags: Vec<TagCountChange>,
+    /// Bookmarks the operation would fail to change, in the order it would send them. Only
+    /// [`DryRun::bulk_edit`] lists them, other operations return the error instead.
+    pub errors: Vec<(Url, PatchError)>,
+}
+
+impl<'pin> ChangePlan<'pin> {
+    /// Builds a plan from pin changes, counting tags before the operation from `tags`.
+    fn new(pins: Vec<PinChange<'pin>>, tags: &[CachedTag]) -> Self {
+        let mut deltas: BTreeMap<&str, isize> = BTreeMap::new();
+        for change in &pins {
+            for tag in change.before.iter().flat_map(|p| &p.tags) {
+                *deltas.entry(tag).or_default() -= 1;
+            }
+            for tag in change.after.iter().flat_map(|p| &p.tags) {
+                *deltas.entry(tag).or_default() += 1;
+            }
+        }
+        let tags = deltas
+            .into_iter()
+            .filter(|(_, delta)| *delta != 0)
+            .map(|(tag, delta)| {
+                let before = tags
+                    .iter()
+                    .find(|t| t.tag.0 == tag)
+                    .map_or(0, |t| match t.tag.1 {
+                        TagFreq::Used(n) => n,
+                        _ => 0,
+                    });
+                TagCountChange {
+                    tag: tag.to_string(),
+                    before,
+                    after: before.saturating_add_signed(delta),
+                }
+            })
+            .collect();
+        ChangePlan {
+            pins,
+            tags,
+            errors: vec![],
+        }
+    }
+
+    /// `true` if the operation wouldn't change anything.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.pins.is_empty()
+    }
+}
+
+/// Mutating operations of a [`Pinboard`] that only return what they would change, see
+/// [`Pinboard::dry_run`].
+///
+/// Nothing is sent to the server and urls are only looked up in the cache.
+pub struct DryRun<'a, 'api, 'pin, B> {
+    pub(crate) pinboard: &'a Pinboard<'api, 'pin, B>,
+}
+
+impl<'api, 'pin, B: BookmarkStore<'pin>> DryRun<'_, 'api, 'pin, B> {
+    fn cached_pins(&self) -> impl Iterator<Item = &Pin<'pin>> {
+        self.pinboard
+            .cached_data
+            .pins()
+            .unwrap_or_default()
+            .iter()
+            .map(|cached_pin| &cached_pin.pin)
+    }
+
+    fn plan(&self, pins: Vec<PinChange<'pin>>) -> ChangePlan<'pin> {
+        ChangePlan::new(pins, self.pinboard.cached_data.tags().unwrap_or_default())
+    }
+
+    /// See [`Pinboard::add_pin_with_mode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`AddError::AlreadyExists`](super::AddError::AlreadyExists) if the url is
+    /// cached and `mode` is [`AddMode::FailIfExists`].
+    pub fn add_pin(
+        &self,
+        p: Pin<'pin>,
+        mode: AddMode,
+    ) -> Result<ChangePlan<'pin>, Box<dyn std::error::Error>> {
+        debug!("add_pin: starting.");
+        let before = self.pinboard.cached_pin(&p.url);
+        let after = self.pinboard.pin_to_add(p, mode, before.clone())?;
+        Ok(self.plan(vec![PinChange {
+            before,
+            after: Some(after),
+        }]))
+    }
+
+    /// See [`Pinboard::update_pin`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `url` can't be parsed, [`PatchError::NotFound`] if it isn't cached
+    /// or [`PatchError::EmptyTitle`] if `patch` clears the title.
+    pub fn update_pin<T: AsRef<str>>(
+        &self,
+        url: T,
+        patch: &PinPatch,
+    ) -> Result<ChangePlan<'pin>, Box<dyn std::error::Error>> {
+        debug!("update_pin: starting.");
+        let url = Url::parse(url.as_ref())?;
+        let before = self
+            .pinboard
+            .cached_pin(&url)
+            .ok_or_else(|| PatchError::NotFound(url.to_string()))?;
+        let after = patch.apply(&before)?;
+        Ok(self.plan(vec![PinChange {
+            before: Some(before),
+            after: Some(after),
+        }]))
+    }
+
+    /// See [`Pinboard::bulk_edit`], bookmarks the operations don't change aren't listed.
+    /// Those `bulk_edit` would report an error for (e.g. urls that aren't cached) are listed
+    /// in [`ChangePlan::errors`].
+    #[must_use]
+    pub fn bulk_edit(&self, selection: &[Url], ops: &[BulkOp]) -> ChangePlan<'pin> {
+        debug!("bulk_edit: starting.");
+        let mut changes = vec![];
+        let mut errors = vec![];
+        for (url, edit) in self.pinboard.bulk_edits(selection, ops) {
+            match edit {
+                Ok((p, edited)) if p != edited => changes.push(PinChange {
+                    before: Some(p),
+                    after: Some(edited),
+                }),
+                Ok(_) => {}
+                Err(e) => errors.push((url, e)),
+            }
+        }
+        ChangePlan {
+            errors,
+            ..self.plan(changes)
+        }
+    }
+
+    /// See [`Pinboard::delete`], the plan is empty if `url` isn't cached.
+    #[must_use]
+    pub fn delete<T: AsRef<str>>(&self, url: T) -> ChangePlan<'pin> {
+        debug!("delete: starting.");
+        let before = Url::parse(url.as_ref())
+            .ok()
+            .and_then(|url| self.pinboard.cached_pin(&url));
+        self.plan(
+            before
+                .map(|before| PinChange {
+                    before: Some(before),
+                    after: None,
+                })
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    /// See [`Pinboard::delete_tag`].
+    #[must_use]
+    pub fn delete_tag<T: AsRef<str>>(&self, tag: T) -> ChangePlan<'pin> {
+        debug!("delete_tag: starting.");
+        let changes = self
+            .cached_pins()
+            .filter(|p| p.tags.iter().any(|t| t == tag.as_ref()))
+            .map(|p| {
+                let mut after = p.clone();
+                after.tags.retain(|t| t != tag.as_ref());
+                PinChange {
+                    before: Some(p.clone()),
+                    after: Some(after),
+                }
+            })
+            .collect();
+        self.plan(changes)
+    }
+
+    /// See [`Pinboard::rename_tag`].
+    #[must_use]
+    pub fn rename_tag<T: AsRef<str>>(&self, old: T, new: T) -> ChangePlan<'pin> {
+        debug!("rename_tag: starting.");
+        self.merge_tags(new.as_ref(), &[old])
+    }
+
+    /// See [`Pinboard::merge_tags`].
+    #[must_use]
+    pub fn merge_tags<T: AsRef<str>>(&self, into: &str, from: &[T]) -> ChangePlan<'pin> {
+        debug!("merge_tags: starting.");
+        let from: Vec<&str> = from
+            .iter()
+            .map(AsRef::as_ref)
+            .filter(|t| *t != into)
+            .collect();
+        let changes = self
+            .cached_pins()
+            .filter_map(|p| {
+                tag_variants::rename_tags(p, into, &from).map(|after| PinChange {
+                    before: Some(p.clone()),
+                    after: Some(after),
+                })
+            })
+            .collect();
+        self.plan(changes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::pinboard::tag::Tag;
+
+    #[test]
+    fn plan_tag_counts() {
+        let _ = env_logger::try_init();
+        debug!("plan_tag_counts: starting");
+        let pin = |tags: &[&str]| {
+            PinBuilder::new("https://example.com/", "pin")
+                .tags(tags.iter().copied())
+                .into_pin()
+                .expect("valid url")
+        };
+        let tags = [("rust", 3), ("go", 1)]
+            .into_iter()
+            .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+            .collect::<Vec<_>>();
+        let plan = ChangePlan::new(
+            vec![
+                PinChange {
+                    before: Some(pin(&["rust", "go"])),
+                    after: Some(pin(&["rust", "golang"])),
+                },
+                PinChange {
+                    before: Some(pin(&["rust"])),
+                    after: None,
+                },
+            ],
+            &tags,
+        );
+        let counts = plan
+            .tags
+            .iter()
+            .map(|t| (t.tag.as_str(), t.before, t.after))
+            .collect::<Vec<_>>();
+        assert_eq!(vec![("go", 1, 0), ("golang", 0, 1), ("rust", 3, 2)], counts);
+        assert!(!plan.is_empty());
+        assert!(ChangePlan::new(vec![], &tags).is_empty());
+    }
+}
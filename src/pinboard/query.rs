@@ -0,0 +1,568 @@
+//! A small query language to search bookmarks.
+//!
+//! ```text
+//! tag:rust -tag:old title:macro site:github.com
+//! "exact phrase" (tag:rust OR tag:go) toread:yes
+//! ```
+//!
+//! * Terms are separated by spaces and a bookmark must match all of them.
+//! * `OR` (or `|`) between terms matches bookmarks that match either side.
+//! * A leading `-` negates a term or a group in parentheses.
+//! * `"quoted phrases"` are matched as a whole, they can follow a field prefix too.
+//! * Field prefixes: `title:`, `tag:` (whole tag), `url:`, `desc:` (`description:`),
+//!   `site:` (`domain:`) and the flags `toread:` & `shared:` (`yes`/`no`).
+//!
+//! Terms without a prefix are searched in title, tags, url & description, so are terms whose
+//! `word:` isn't one of the prefixes above (`std::vec`, `10:30`).
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+use unicode_normalization::UnicodeNormalization;
+
+use super::cached_data::CachedPin;
+use super::filter::domain_matches;
+
+/// Fields a [`Query::Term`] can be searched in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+    /// Title, tags, url or description.
+    Any,
+    Title,
+    /// Whole tag (case insensitive).
+    Tag,
+    Url,
+    Description,
+    /// Host of the url or any of its parent domains.
+    Site,
+}
+
+/// Yes/no flags of a bookmark.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flag {
+    ToRead,
+    Shared,
+}
+
+/// Parsed search query.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    /// `value` is unicode normalized (nfkd) & lowercased.
+    Term {
+        field: Field,
+        value: String,
+    },
+    Flag {
+        flag: Flag,
+        value: bool,
+    },
+    Not(Box<Query>),
+    And(Vec<Query>),
+    Or(Vec<Query>),
+}
+
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueryErrorKind {
+    EmptyQuery,
+    MissingValue,
+    InvalidFlagValue,
+    UnterminatedQuote,
+    UnexpectedToken,
+    UnclosedParen,
+}
+
+impl fmt::Display for QueryErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let msg = match self {
+            QueryErrorKind::EmptyQuery => "empty query",
+            QueryErrorKind::MissingValue => "missing value",
+            QueryErrorKind::InvalidFlagValue => "flag value should be yes or no",
+            QueryErrorKind::UnterminatedQuote => "unterminated quote",
+            QueryErrorKind::UnexpectedToken => "unexpected token",
+            QueryErrorKind::UnclosedParen => "unclosed parenthesis",
+        };
+        f.write_str(msg)
+    }
+}
+
+/// Error returned when a query can't be parsed.
+///
+/// `position` is the byte offset of the offending `token` within the query.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{kind} at position {position}: `{token}`")]
+pub struct QueryError {
+    pub kind: QueryErrorKind,
+    pub position: usize,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TokenKind {
+    LParen,
+    RParen,
+    Not,
+    Or,
+    Term(Query),
+}
+
+#[derive(Debug)]
+struct Token {
+    kind: TokenKind,
+    position: usize,
+    text: String,
+}
+
+fn normalize(s: &str) -> String {
+    s.nfkd().collect::<String>().to_lowercase()
+}
+
+fn error(kind: QueryErrorKind, position: usize, token: &str) -> QueryError {
+    QueryError {
+        kind,
+        position,
+        token: token.to_string(),
+    }
+}
+
+/// Splits `input` into tokens, parsing field prefixes & quoted phrases on the way.
+fn tokenize(input: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = vec![];
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let simple = match c {
+            '(' => Some(TokenKind::LParen),
+            ')' => Some(TokenKind::RParen),
+            '|' => Some(TokenKind::Or),
+            '-' => Some(TokenKind::Not),
+            _ => None,
+        };
+        if let Some(kind) = simple {
+            chars.next();
+            tokens.push(Token {
+                kind,
+                position: start,
+                text: c.to_string(),
+            });
+            continue;
+        }
+        let end = if c == '"' {
+            quoted_end(input, start)?
+        } else {
+            word_end(input, start)?
+        };
+        while chars.peek().is_some_and(|&(i, _)| i < end) {
+            chars.next();
+        }
+        let text = &input[start..end];
+        let kind = if text == "OR" {
+            TokenKind::Or
+        } else {
+            TokenKind::Term(parse_term(text, start)?)
+        };
+        tokens.push(Token {
+            kind,
+            position: start,
+            text: text.to_string(),
+        });
+    }
+    Ok(tokens)
+}
+
+/// Field & flag prefixes, in lowercase.
+const PREFIXES: &[&str] = &[
+    "title",
+    "tag",
+    "url",
+    "desc",
+    "description",
+    "site",
+    "domain",
+    "toread",
+    "shared",
+];
+
+fn is_prefix(prefix: &str) -> bool {
+    PREFIXES.iter().any(|p| p.eq_ignore_ascii_case(prefix))
+}
+
+/// Returns the end of the quoted phrase starting at `start`.
+fn quoted_end(input: &str, start: usize) -> Result<usize, QueryError> {
+    input[start + 1..]
+        .find('"')
+        .map(|i| start + 1 + i + 1)
+        .ok_or_else(|| error(QueryErrorKind::UnterminatedQuote, start, &input[start..]))
+}
+
+/// Returns the end of the (possibly prefixed) term starting at `start`.
+fn word_end(input: &str, start: usize) -> Result<usize, QueryError> {
+    let rest = &input[start..];
+    match rest.find(':') {
+        Some(colon) if rest[colon + 1..].starts_with('"') && is_prefix(&rest[..colon]) => {
+            quoted_end(input, start + colon + 1)
+        }
+        _ => Ok(rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .map_or(input.len(), |i| start + i)),
+    }
+}
+
+fn parse_term(text: &str, position: usize) -> Result<Query, QueryError> {
+    let unquote = |s: &str| {
+        s.strip_prefix('"')
+            .and_then(|s| s.strip_suffix('"'))
+            .map_or_else(|| s.to_string(), str::to_string)
+    };
+    let Some((prefix, value)) = text.split_once(':') else {
+        return Ok(Query::Term {
+            field: Field::Any,
+            value: normalize(&unquote(text)),
+        });
+    };
+    // Quoted phrases, urls (`https://…`) & other `word:` terms (`std::vec`) aren't prefixed
+    if text.starts_with('"') || !is_prefix(prefix) {
+        return Ok(Query::Term {
+            field: Field::Any,
+            value: normalize(&unquote(text)),
+        });
+    }
+    let value = unquote(value);
+    if value.is_empty() {
+        return Err(error(QueryErrorKind::MissingValue, position, text));
+    }
+    let field = match prefix.to_lowercase().as_str() {
+        "title" => Field::Title,
+        "tag" => Field::Tag,
+        "url" => Field::Url,
+        "desc" | "description" => Field::Description,
+        "site" | "domain" => Field::Site,
+        // `toread` or `shared`
+        flag => {
+            let flag = if flag == "toread" {
+                Flag::ToRead
+            } else {
+                Flag::Shared
+            };
+            let value = match value.to_lowercase().as_str() {
+                "yes" | "true" => true,
+                "no" | "false" => false,
+                _ => return Err(error(QueryErrorKind::InvalidFlagValue, position, text)),
+            };
+            return Ok(Query::Flag { flag, value });
+        }
+    };
+    Ok(Query::Term {
+        field,
+        value: normalize(&value),
+    })
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    idx: usize,
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.idx)
+    }
+
+    #[allow(clippy::unused_self)]
+    fn unexpected(&self, token: &Token) -> QueryError {
+        error(QueryErrorKind::UnexpectedToken, token.position, &token.text)
+    }
+
+    fn parse_or(&mut self) -> Result<Query, QueryError> {
+        let mut branches = vec![self.parse_and()?];
+        while let Some(token) = self.peek() {
+            if token.kind != TokenKind::Or {
+                break;
+            }
+            let position = token.position;
+            let text = token.text.clone();
+            self.idx += 1;
+            match self.peek() {
+                None => return Err(error(QueryErrorKind::MissingValue, position, &text)),
+                Some(next) if matches!(next.kind, TokenKind::Or | TokenKind::RParen) => {
+                    return Err(self.unexpected(next));
+                }
+                Some(_) => branches.push(self.parse_and()?),
+            }
+        }
+        Ok(if branches.len() == 1 {
+            branches.remove(0)
+        } else {
+            Query::Or(branches)
+        })
+    }
+
+    fn parse_and(&mut self) -> Result<Query, QueryError> {
+        let mut terms = vec![];
+        while let Some(token) = self.peek() {
+            if matches!(token.kind, TokenKind::Or | TokenKind::RParen) {
+                break;
+            }
+            terms.push(self.parse_unary()?);
+        }
+        match terms.len() {
+            0 => {
+                let token = self
+                    .peek()
+                    .expect("parse_and is only called before a token");
+                Err(self.unexpected(token))
+            }
+            1 => Ok(terms.remove(0)),
+            _ => Ok(Query::And(terms)),
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<Query, QueryError> {
+        let Some(token) = self.tokens.get(self.idx) else {
+            let position = self.input.len();
+            return Err(error(QueryErrorKind::MissingValue, position, ""));
+        };
+        match &token.kind {
+            TokenKind::Not => {
+                let (position, text) = (token.position, token.text.clone());
+                self.idx += 1;
+                match self.peek() {
+                    Some(next) if matches!(next.kind, TokenKind::Term(_) | TokenKind::LParen) => {
+                        Ok(Query::Not(Box::new(self.parse_unary()?)))
+                    }
+                    Some(next) => Err(self.unexpected(next)),
+                    None => Err(error(QueryErrorKind::MissingValue, position, &text)),
+                }
+            }
+            TokenKind::LParen => {
+                let (position, text) = (token.position, token.text.clone());
+                self.idx += 1;
+                if let Some(next) = self.peek() {
+                    if next.kind == TokenKind::RParen {
+                        return Err(self.unexpected(next));
+                    }
+                }
+                if self.peek().is_none() {
+                    return Err(error(QueryErrorKind::UnclosedParen, position, &text));
+                }
+                let inner = self.parse_or()?;
+                match self.peek() {
+                    Some(next) if next.kind == TokenKind::RParen => {
+                        self.idx += 1;
+                        Ok(inner)
+                    }
+                    _ => Err(error(QueryErrorKind::UnclosedParen, position, &text)),
+                }
+            }
+            TokenKind::Term(term) => {
+                let term = term.clone();
+                self.idx += 1;
+                Ok(term)
+            }
+            TokenKind::Or | TokenKind::RParen => Err(self.unexpected(token)),
+        }
+    }
+}
+
+impl Query {
+    /// Parses `input` into a query.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`QueryError`] pointing at the token that couldn't be parsed.
+    pub fn parse(input: &str) -> Result<Query, QueryError> {
+        debug!("parse: starting.");
+        let tokens = tokenize(input)?;
+        if tokens.is_empty() {
+            return Err(error(QueryErrorKind::EmptyQuery, 0, input));
+        }
+        let mut parser = Parser {
+            input,
+            tokens,
+            idx: 0,
+        };
+        let query = parser.parse_or()?;
+        if let Some(token) = parser.peek() {
+            return Err(parser.unexpected(token));
+        }
+        Ok(query)
+    }
+
+    /// Returns true if `cached_pin` satisfies the query.
+    #[must_use]
+    pub fn matches(&self, cached_pin: &CachedPin) -> bool {
+        match self {
+            Query::Term { field, value } => term_matches(*field, value, cached_pin),
+            Query::Flag { flag, value } => {
+                let field = match flag {
+                    Flag::ToRead => cached_pin.pin.toread,
+                    Flag::Shared => cached_pin.pin.shared,
+                };
+                field == *value
+            }
+            Query::Not(query) => !query.matches(cached_pin),
+            Query::And(queries) => queries.iter().all(|q| q.matches(cached_pin)),
+            Query::Or(queries) => queries.iter().any(|q| q.matches(cached_pin)),
+        }
+    }
+
+    /// Terms every matching bookmark must contain somewhere, used to narrow down the
+    /// bookmarks a store returns.
+    pub(crate) fn required_terms(&self) -> Vec<String> {
+        match self {
+            Query::Term {
+                field: Field::Any,
+                value,
+            } => vec![value.clone()],
+            Query::And(queries) => queries.iter().flat_map(Query::required_terms).collect(),
+            _ => vec![],
+        }
+    }
+}
+
+impl FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Query::parse(s)
+    }
+}
+
+fn term_matches(field: Field, value: &str, cached_pin: &CachedPin) -> bool {
+    let title = || cached_pin.title_lowered.contains(value);
+    let tags = || cached_pin.tag_list.iter().any(|tag| tag.contains(value));
+    let url = || cached_pin.url_lowered.contains(value);
+    let description = || {
+        cached_pin
+            .extended_lowered
+            .as_ref()
+            .is_some_and(|e| e.contains(value))
+    };
+    match field {
+        Field::Any => title() || tags() || url() || description(),
+        Field::Title => title(),
+        Field::Tag => cached_pin.tag_list.iter().any(|tag| tag == value),
+        Field::Url => url(),
+        Field::Description => description(),
+        Field::Site => domain_matches(&cached_pin.pin.url, value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    fn term(field: Field, value: &str) -> Query {
+        Query::Term {
+            field,
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn parse_queries() {
+        let _ = env_logger::try_init();
+        debug!("parse_queries: starting");
+        assert_eq!(
+            Query::And(vec![
+                term(Field::Tag, "rust"),
+                Query::Not(Box::new(term(Field::Tag, "old"))),
+                term(Field::Title, "macro"),
+                term(Field::Site, "github.com"),
+            ]),
+            Query::parse("tag:rust -tag:old title:macro site:github.com").unwrap()
+        );
+        assert_eq!(
+            Query::And(vec![
+                term(Field::Any, "exact phrase"),
+                Query::Or(vec![term(Field::Tag, "rust"), term(Field::Tag, "go")]),
+                Query::Flag {
+                    flag: Flag::ToRead,
+                    value: true
+                },
+                term(Field::Title, "two words"),
+            ]),
+            Query::parse(r#""Exact Phrase" (tag:rust OR tag:go) toread:yes title:"two words""#)
+                .unwrap()
+        );
+        assert_eq!(
+            Query::Or(vec![
+                term(Field::Any, "a"),
+                Query::Not(Box::new(Query::And(vec![
+                    term(Field::Any, "b"),
+                    term(Field::Any, "c")
+                ]))),
+            ]),
+            "a | -(b c)".parse().unwrap()
+        );
+        // Urls & other `word:` terms aren't field prefixes
+        assert_eq!(
+            term(Field::Any, "https://github.com"),
+            Query::parse("https://github.com").unwrap()
+        );
+        for q in ["std::vec", "c++:", "10:30"] {
+            assert_eq!(term(Field::Any, q), Query::parse(q).unwrap(), "{q}");
+        }
+        assert_eq!(
+            term(Field::Any, r#"foo:"bar""#),
+            Query::parse(r#"foo:"bar""#).unwrap()
+        );
+    }
+
+    #[test]
+    fn parse_errors() {
+        let _ = env_logger::try_init();
+        debug!("parse_errors: starting");
+        let err = |q: &str| Query::parse(q).unwrap_err();
+        let e = err("rust tag:");
+        assert_eq!(QueryErrorKind::MissingValue, e.kind);
+        assert_eq!(5, e.position);
+        assert_eq!("tag:", e.token);
+        assert_eq!("missing value at position 5: `tag:`", e.to_string());
+
+        assert_eq!(QueryErrorKind::EmptyQuery, err("  ").kind);
+        assert_eq!(QueryErrorKind::MissingValue, err("tag:").kind);
+        assert_eq!(QueryErrorKind::InvalidFlagValue, err("shared:maybe").kind);
+        let e = err(r#"rust "open"#);
+        assert_eq!((QueryErrorKind::UnterminatedQuote, 5), (e.kind, e.position));
+        let e = err("(rust go");
+        assert_eq!((QueryErrorKind::UnclosedParen, 0), (e.kind, e.position));
+        let e = err("rust go)");
+        assert_eq!((QueryErrorKind::UnexpectedToken, 7), (e.kind, e.position));
+        let e = err("OR rust");
+        assert_eq!((QueryErrorKind::UnexpectedToken, 0), (e.kind, e.position));
+        assert_eq!(QueryErrorKind::MissingValue, err("rust -").kind);
+    }
+
+    #[test]
+    fn query_matches() {
+        let _ = env_logger::try_init();
+        debug!("query_matches: starting");
+        let pin = CachedPin::new(
+            PinBuilder::new("https://www.github.com/rust-lang/rust", "Rust Macro Book")
+                .tags(["rust", "macros"])
+                .toread(true)
+                .shared(false)
+                .description("all about declarative macros")
+                .into_pin()
+                .expect("valid url"),
+        );
+        let matches = |q: &str| Query::parse(q).unwrap().matches(&pin);
+        assert!(matches("tag:rust -tag:old title:macro site:github.com"));
+        assert!(matches("toread:yes shared:no"));
+        assert!(matches(r#""macro book""#));
+        assert!(matches("desc:declarative"));
+        assert!(matches("tag:go OR url:rust-lang"));
+        assert!(!matches("tag:rus"));
+        assert!(!matches("site:hub.com"));
+        assert!(!matches("-tag:macros"));
+        assert!(!matches("shared:yes"));
+        assert!(!matches("(tag:go OR tag:python) rust"));
+    }
+}
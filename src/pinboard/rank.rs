@@ -0,0 +1,216 @@
+//! Scoring used by ranked searches.
+//!
+//! Each search term is scored in every searched field and the best field wins. Field scores
+//! are multiplied by the field's weight and the scores of all terms are added together.
+//! Optionally newer bookmarks get a boost that halves every `recency_half_life_days`.
+use chrono::prelude::*;
+use fuzzy_matcher::FuzzyMatcher;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::pin::Pin;
+use super::tag::Tag;
+use super::{SearchType, MATCHER};
+
+/// Bookmarks found by a ranked search along with their score, best match first.
+pub type RankedPins<'pin> = Vec<(f64, &'pin Pin<'pin>)>;
+/// Tags found by a ranked search along with their score, best match first.
+pub type RankedTags<'a> = Vec<(f64, &'a Tag)>;
+
+/// Weights used to rank search results.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RankWeights {
+    pub title: f64,
+    pub tag: f64,
+    pub url: f64,
+    pub description: f64,
+    /// How much the newest bookmarks are boosted: a bookmark created just now gets its
+    /// score multiplied by `1 + recency`. `0` disables the boost.
+    pub recency: f64,
+    /// Age (in days) at which the recency boost is halved.
+    pub recency_half_life_days: f64,
+}
+
+impl Default for RankWeights {
+    fn default() -> Self {
+        RankWeights {
+            title: 3.0,
+            tag: 2.0,
+            url: 1.0,
+            description: 1.0,
+            recency: 0.0,
+            recency_half_life_days: 365.0,
+        }
+    }
+}
+
+/// Score of `needle` appearing in `haystack` (both lowercased), `None` if it doesn't.
+///
+/// Whole field matches score higher than matches at a word start, which score higher than
+/// matches within a word.
+fn substring_score(haystack: &str, needle: &str) -> Option<i64> {
+    let idx = haystack.find(needle)?;
+    let score = if haystack.len() == needle.len() {
+        30
+    } else if haystack[..idx]
+        .chars()
+        .next_back()
+        .is_none_or(|c| !c.is_alphanumeric())
+    {
+        20
+    } else {
+        10
+    };
+    Some(score)
+}
+
+fn field_score(haystack: &str, needle: &str, fuzzy: bool) -> Option<i64> {
+    if fuzzy {
+        MATCHER.fuzzy_match(haystack, needle)
+    } else {
+        substring_score(haystack, needle)
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn weighted(score: Option<i64>, weight: f64) -> Option<f64> {
+    score.map(|s| s as f64 * weight)
+}
+
+/// Returns the score of `cached_pin` or `None` if any of `queries` is not found in `fields`.
+///
+/// `queries` must be normalized & lowercased.
+pub(crate) fn score_pin(
+    cached_pin: &CachedPin,
+    queries: &[String],
+    fields: &[SearchType],
+    weights: &RankWeights,
+    fuzzy: bool,
+    now: DateTime<Utc>,
+) -> Option<f64> {
+    let mut total = 0.0;
+    for query in queries {
+        let title = || {
+            weighted(
+                field_score(&cached_pin.title_lowered, query, fuzzy),
+                weights.title,
+            )
+        };
+        let tag = || {
+            let best = cached_pin
+                .tag_list
+                .iter()
+                .filter_map(|t| field_score(t, query, fuzzy))
+                .max();
+            weighted(best, weights.tag)
+        };
+        let term_score = fields
+            .iter()
+            .filter_map(|field| match field {
+                SearchType::TitleOnly => title(),
+                SearchType::TagOnly => tag(),
+                SearchType::UrlOnly => weighted(
+                    field_score(&cached_pin.url_lowered, query, fuzzy),
+                    weights.url,
+                ),
+                SearchType::DescriptionOnly => weighted(
+                    cached_pin
+                        .extended_lowered
+                        .as_ref()
+                        .and_then(|e| field_score(e, query, fuzzy)),
+                    weights.description,
+                ),
+                SearchType::TagTitleOnly => match (title(), tag()) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    (a, b) => a.or(b),
+                },
+            })
+            .reduce(f64::max)?;
+        total += term_score;
+    }
+    Some(total * recency_factor(cached_pin.pin.time, weights, now))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn recency_factor(time: DateTime<Utc>, weights: &RankWeights, now: DateTime<Utc>) -> f64 {
+    if weights.recency == 0.0 || weights.recency_half_life_days <= 0.0 {
+        return 1.0;
+    }
+    let age_days = (now - time).num_seconds().max(0) as f64 / 86_400.0;
+    1.0 + weights.recency * 0.5_f64.powf(age_days / weights.recency_half_life_days)
+}
+
+/// Returns the score of `cached_tag` for `query` or `None` if it doesn't match.
+pub(crate) fn score_tag(cached_tag: &CachedTag, query: &str, fuzzy: bool) -> Option<f64> {
+    let score = if fuzzy {
+        MATCHER.fuzzy_match(&cached_tag.tag.0, query)
+    } else {
+        substring_score(&cached_tag.tag_lowered, query)
+    };
+    weighted(score, 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn substring_scores() {
+        let _ = env_logger::try_init();
+        debug!("substring_scores: starting");
+        assert_eq!(Some(30), substring_score("rust", "rust"));
+        assert_eq!(Some(20), substring_score("the rust book", "rust"));
+        assert_eq!(Some(20), substring_score("rust book", "rust"));
+        assert_eq!(Some(10), substring_score("trust", "rust"));
+        assert_eq!(None, substring_score("go", "rust"));
+    }
+
+    #[test]
+    fn pin_scores() {
+        let _ = env_logger::try_init();
+        debug!("pin_scores: starting");
+        let now = Utc::now();
+        let pin = |title: &str, tags: &str, days: i64| {
+            let mut pin = PinBuilder::new("https://example.com/".to_string(), title.to_string())
+                .tags(tags.split_whitespace())
+                .into_pin()
+                .expect("valid url");
+            pin.time = now - chrono::Duration::days(days);
+            CachedPin::new(pin)
+        };
+        let all = [
+            SearchType::TitleOnly,
+            SearchType::TagOnly,
+            SearchType::UrlOnly,
+            SearchType::DescriptionOnly,
+        ];
+        let weights = RankWeights::default();
+        let q = vec!["rust".to_string()];
+        let title_hit = score_pin(&pin("rust", "", 0), &q, &all, &weights, false, now).unwrap();
+        let tag_hit = score_pin(&pin("book", "rust", 0), &q, &all, &weights, false, now).unwrap();
+        assert!(title_hit > tag_hit);
+        assert!(score_pin(&pin("go", "go", 0), &q, &all, &weights, false, now).is_none());
+
+        // Every term has to match
+        let q2 = vec!["rust".to_string(), "book".to_string()];
+        assert!(score_pin(&pin("rust", "", 0), &q2, &all, &weights, false, now).is_none());
+        let both = score_pin(&pin("book", "rust", 0), &q2, &all, &weights, false, now).unwrap();
+        assert!(both > tag_hit);
+
+        // Only the given fields are scored
+        let tags_only = [SearchType::TagOnly];
+        assert!(score_pin(&pin("rust", "", 0), &q, &tags_only, &weights, false, now).is_none());
+
+        // Recency boost
+        let weights = RankWeights {
+            recency: 1.0,
+            recency_half_life_days: 30.0,
+            ..RankWeights::default()
+        };
+        let new = score_pin(&pin("rust", "", 0), &q, &all, &weights, false, now).unwrap();
+        let old = score_pin(&pin("rust", "", 30), &q, &all, &weights, false, now).unwrap();
+        assert!((new - 2.0 * title_hit).abs() < 1e-6);
+        assert!((old - 1.5 * title_hit).abs() < 1e-6);
+    }
+}
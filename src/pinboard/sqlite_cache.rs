@@ -0,0 +1,592 @@
+//! SQLite storage for the local cache (enabled with the `sqlite` feature).
+//!
+//! Pins and tags are stored together with their unicode-normalized, lowercased fields.
+//! Two FTS5 tables using the `trigram` tokenizer index these fields so substring searches
+//! don't have to scan every cached bookmark.
+//!
+//! Pins also store their `position` in the cached list, so single pins can be added or
+//! removed without rewriting the others.
+use std::borrow::Cow;
+use std::path::Path;
+
+use chrono::prelude::*;
+use rusqlite::{params, Connection};
+use url::Url;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::pin::{Pin, UnsetFlags};
+use super::store::StoreMeta;
+use super::tag::{Tag, TagFreq};
+use super::SearchType;
+
+/// Bump this whenever the schema below changes.
+pub(crate) const SCHEMA_VERSION: i32 = 5;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pins (
+    id INTEGER PRIMARY KEY,
+    position INTEGER NOT NULL,
+    url TEXT NOT NULL,
+    title TEXT NOT NULL,
+    tags TEXT NOT NULL,
+    shared INTEGER NOT NULL,
+    toread INTEGER NOT NULL,
+    extended TEXT,
+    time TEXT NOT NULL,
+    url_lowered TEXT NOT NULL,
+    title_lowered TEXT NOT NULL,
+    tags_lowered TEXT NOT NULL,
+    extended_lowered TEXT,
+    meta TEXT,
+    hash TEXT
+);
+CREATE INDEX IF NOT EXISTS pins_position ON pins (position);
+CREATE INDEX IF NOT EXISTS pins_url ON pins (url);
+CREATE TABLE IF NOT EXISTS tags (
+    id INTEGER PRIMARY KEY,
+    tag TEXT NOT NULL,
+    freq INTEGER NOT NULL,
+    tag_lowered TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS meta (
+    id INTEGER PRIMARY KEY CHECK (id = 0),
+    last_sync TEXT,
+    server_update TEXT,
+    dropped_pins INTEGER NOT NULL DEFAULT 0
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS pins_fts USING fts5(
+    title_lowered, tags_lowered, url_lowered, extended_lowered,
+    content = 'pins', content_rowid = 'id', tokenize = 'trigram'
+);
+CREATE VIRTUAL TABLE IF NOT EXISTS tags_fts USING fts5(
+    tag_lowered, content = 'tags', content_rowid = 'id', tokenize = 'trigram'
+);
+";
+
+/// Trigram tokenizer can't match queries shorter than this many characters.
+const MIN_TRIGRAM_QUERY: usize = 3;
+
+// `TagFreq::New` and `TagFreq::Popular` don't have a count, store them as negative numbers.
+const FREQ_NEW: i64 = -1;
+const FREQ_POPULAR: i64 = -2;
+
+#[derive(Debug)]
+pub struct SqliteCache {
+    conn: Connection,
+}
+
+impl SqliteCache {
+    /// Opens (or creates) the database at `path` and makes sure its schema is in place.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, Box<dyn std::error::Error>> {
+        debug!("open: starting");
+        let conn = Connection::open(path)?;
+        let version: i32 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version != SCHEMA_VERSION {
+            debug!(
+                "  schema version {} is outdated, recreating tables",
+                version
+            );
+            conn.execute_batch(
+                "DROP TABLE IF EXISTS pins_fts; DROP TABLE IF EXISTS tags_fts;
+                 DROP TABLE IF EXISTS pins; DROP TABLE IF EXISTS tags;
+                 DROP TABLE IF EXISTS meta;",
+            )?;
+        }
+        conn.execute_batch(SCHEMA)?;
+        conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+        Ok(SqliteCache { conn })
+    }
+
+    /// Replaces everything stored in the database with `pins` and `tags`.
+    ///
+    /// Pins are stored with their position in `pins` & tags are keyed by their position in
+    /// `tags`, so ids returned by searches can be used to index into them.
+    pub fn replace_all(
+        &mut self,
+        pins: &[CachedPin],
+        tags: &[CachedTag],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("replace_all: starting");
+        let tx = self.conn.transaction()?;
+        tx.execute_batch("DELETE FROM pins; DELETE FROM tags;")?;
+        for (position, cached) in pins.iter().enumerate() {
+            insert_pin(&tx, position, cached)?;
+        }
+        insert_tags(&tx, tags)?;
+        tx.execute_batch(
+            "INSERT INTO pins_fts(pins_fts) VALUES ('rebuild');
+             INSERT INTO tags_fts(tags_fts) VALUES ('rebuild');",
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Replaces the stored tags with `tags`, leaving pins as they are.
+    pub fn replace_tags(&mut self, tags: &[CachedTag]) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("replace_tags: starting");
+        let tx = self.conn.transaction()?;
+        tx.execute("DELETE FROM tags", [])?;
+        insert_tags(&tx, tags)?;
+        tx.execute("INSERT INTO tags_fts(tags_fts) VALUES ('rebuild')", [])?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Stores `pin` at `position`, replacing the pins with the same url. Like
+    /// [`upsert_sorted`](super::store::upsert_sorted), `position` is counted once those are
+    /// removed.
+    pub fn upsert_pin(
+        &mut self,
+        position: usize,
+        pin: &CachedPin,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("upsert_pin: starting");
+        let tx = self.conn.transaction()?;
+        let replaced = tx
+            .prepare("SELECT position FROM pins WHERE url = ?1 ORDER BY position DESC")?
+            .query_map([pin.pin.url.as_str()], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for position in replaced {
+            delete_at(&tx, position)?;
+        }
+        tx.execute(
+            "UPDATE pins SET position = position + 1 WHERE position >= ?1",
+            [i64::try_from(position)?],
+        )?;
+        let id = insert_pin(&tx, position, pin)?;
+        tx.execute(
+            "INSERT INTO pins_fts (rowid, title_lowered, tags_lowered, url_lowered,
+                                   extended_lowered)
+             SELECT id, title_lowered, tags_lowered, url_lowered, extended_lowered
+             FROM pins WHERE id = ?1",
+            [id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Removes the pin at `position`, moving the pins after it up.
+    pub fn delete_pin(&mut self, position: usize) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("delete_pin: starting");
+        let tx = self.conn.transaction()?;
+        delete_at(&tx, i64::try_from(position)?)?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Reads all stored pins, ordered the same way they were written.
+    pub fn load_pins(&self) -> Result<Vec<CachedPin<'static>>, Box<dyn std::error::Error>> {
+        debug!("load_pins: starting");
+        let mut stmt = self.conn.prepare(
+            "SELECT url, title, tags, shared, toread, extended, time,
+                    title_lowered, tags_lowered, extended_lowered, url_lowered, meta, hash
+             FROM pins ORDER BY position",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            let url: String = row.get(0)?;
+            let url = Url::parse(&url).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, e.into())
+            })?;
+            let tags: String = row.get(2)?;
+            let time: String = row.get(6)?;
+            let time = DateTime::parse_from_rfc3339(&time)
+                .map(|t| t.with_timezone(&Utc))
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        6,
+                        rusqlite::types::Type::Text,
+                        e.into(),
+                    )
+                })?;
+            let tags_lowered: String = row.get(8)?;
+            Ok(CachedPin {
+                pin: Pin {
+                    url,
+                    title: Cow::Owned(row.get(1)?),
+                    tags: tags.split_whitespace().map(ToString::to_string).collect(),
+                    shared: row.get(3)?,
+                    toread: row.get(4)?,
+                    extended: row.get::<_, Option<String>>(5)?.map(Cow::Owned),
+                    meta: row.get::<_, Option<String>>(11)?.map(Cow::Owned),
+                    hash: row.get::<_, Option<String>>(12)?.map(Cow::Owned),
+                    time,
+                    unset_flags: UnsetFlags::default(),
+                },
+                tag_list: tags_lowered
+                    .split_whitespace()
+                    .map(std::string::ToString::to_string)
+                    .collect(),
+                title_lowered: row.get(7)?,
+                extended_lowered: row.get(9)?,
+                url_lowered: row.get(10)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Reads all stored tags, ordered the same way they were written.
+    pub fn load_tags(&self) -> Result<Vec<CachedTag>, Box<dyn std::error::Error>> {
+        debug!("load_tags: starting");
+        let mut stmt = self
+            .conn
+            .prepare("SELECT tag, freq, tag_lowered FROM tags ORDER BY id")?;
+        let rows = stmt.query_map([], |row| {
+            let freq = match row.get::<_, i64>(1)? {
+                FREQ_NEW => TagFreq::New,
+                FREQ_POPULAR => TagFreq::Popular,
+                n => TagFreq::Used(usize::try_from(n).unwrap_or_default()),
+            };
+            Ok(CachedTag {
+                tag: Tag(row.get(0)?, freq),
+                tag_lowered: row.get(2)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Reads metadata about stored data, default values are returned if none was saved.
+    pub fn load_meta(&self) -> Result<StoreMeta, Box<dyn std::error::Error>> {
+        debug!("load_meta: starting");
+        let mut stmt = self
+            .conn
+            .prepare("SELECT last_sync, server_update, dropped_pins FROM meta WHERE id = 0")?;
+        let mut rows = stmt.query([])?;
+        let Some(row) = rows.next()? else {
+            return Ok(StoreMeta::default());
+        };
+        let parse_time = |t: Option<String>| {
+            t.map(|t| DateTime::parse_from_rfc3339(&t).map(|t| t.with_timezone(&Utc)))
+                .transpose()
+        };
+        Ok(StoreMeta {
+            last_sync: parse_time(row.get(0)?)?,
+            server_update: parse_time(row.get(1)?)?,
+            dropped_pins: usize::try_from(row.get::<_, i64>(2)?)?,
+        })
+    }
+
+    pub fn save_meta(&self, meta: &StoreMeta) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("save_meta: starting");
+        self.conn.execute(
+            "INSERT OR REPLACE INTO meta (id, last_sync, server_update, dropped_pins)
+             VALUES (0, ?1, ?2, ?3)",
+            params![
+                meta.last_sync
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                meta.server_update
+                    .map(|t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+                i64::try_from(meta.dropped_pins)?,
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns positions of pins that contain every one of `queries` in at least one of
+    /// `fields`.
+    ///
+    /// `queries` must already be normalized and lowercased. Queries too short for the trigram
+    /// index are ignored, so the result is a superset of the actual matches and callers still
+    /// need to check each pin. `None` is returned when none of the queries could use the index.
+    pub fn matching_pin_ids(
+        &self,
+        queries: &[String],
+        fields: &[SearchType],
+    ) -> Result<Option<Vec<usize>>, Box<dyn std::error::Error>> {
+        debug!("matching_pin_ids: starting");
+        let mut columns: Vec<&str> = Vec::with_capacity(4);
+        for field in fields {
+            let cols: &[&str] = match *field {
+                SearchType::TitleOnly => &["title_lowered"],
+                SearchType::TagOnly => &["tags_lowered"],
+                SearchType::UrlOnly => &["url_lowered"],
+                SearchType::DescriptionOnly => &["extended_lowered"],
+                SearchType::TagTitleOnly => &["title_lowered", "tags_lowered"],
+            };
+            for col in cols {
+                if !columns.contains(col) {
+                    columns.push(col);
+                }
+            }
+        }
+        let columns = columns.join(" ");
+        let Some(expr) = fts_expression(queries, |q| format!("{{{columns}}} : {q}")) else {
+            return Ok(None);
+        };
+        self.matching_ids(
+            "SELECT pins.position FROM pins_fts JOIN pins ON pins.id = pins_fts.rowid
+             WHERE pins_fts MATCH ?1 ORDER BY pins.position",
+            &expr,
+        )
+        .map(Some)
+    }
+
+    /// Returns ids of tags that contain `query`, or `None` if the query is too short for the
+    /// trigram index.
+    pub fn matching_tag_ids(
+        &self,
+        query: &str,
+    ) -> Result<Option<Vec<usize>>, Box<dyn std::error::Error>> {
+        debug!("matching_tag_ids: starting");
+        let Some(expr) = fts_expression(&[query], std::string::ToString::to_string) else {
+            return Ok(None);
+        };
+        self.matching_ids(
+            "SELECT rowid FROM tags_fts WHERE tags_fts MATCH ?1 ORDER BY rowid",
+            &expr,
+        )
+        .map(Some)
+    }
+
+    fn matching_ids(
+        &self,
+        sql: &str,
+        expr: &str,
+    ) -> Result<Vec<usize>, Box<dyn std::error::Error>> {
+        debug!("  fts query: {}", expr);
+        let mut stmt = self.conn.prepare_cached(sql)?;
+        let rows = stmt.query_map([expr], |row| row.get::<_, i64>(0))?;
+        let mut ids = Vec::new();
+        for id in rows {
+            ids.push(usize::try_from(id?)?);
+        }
+        Ok(ids)
+    }
+}
+
+/// Inserts `cached` at `position` without touching other rows or the search index, returning
+/// its id.
+fn insert_pin(
+    conn: &Connection,
+    position: usize,
+    cached: &CachedPin,
+) -> Result<i64, Box<dyn std::error::Error>> {
+    let pin = &cached.pin;
+    let mut stmt = conn.prepare_cached(
+        "INSERT INTO pins (id, position, url, title, tags, shared, toread, extended, time,
+                           url_lowered, title_lowered, tags_lowered, extended_lowered, meta,
+                           hash)
+         VALUES (NULL, ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+    )?;
+    stmt.execute(params![
+        i64::try_from(position)?,
+        pin.url.as_str(),
+        pin.title.as_ref(),
+        pin.tag_string(),
+        pin.shared,
+        pin.toread,
+        pin.extended.as_deref(),
+        pin.time.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        cached.url_lowered,
+        cached.title_lowered,
+        cached.tag_list.join(" "),
+        cached.extended_lowered,
+        pin.meta.as_deref(),
+        pin.hash.as_deref(),
+    ])?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Inserts `tags` keyed by their position without touching the search index.
+fn insert_tags(conn: &Connection, tags: &[CachedTag]) -> Result<(), Box<dyn std::error::Error>> {
+    let mut stmt =
+        conn.prepare("INSERT INTO tags (id, tag, freq, tag_lowered) VALUES (?1, ?2, ?3, ?4)")?;
+    for (id, cached) in tags.iter().enumerate() {
+        let freq = match cached.tag.1 {
+            TagFreq::Used(n) => i64::try_from(n).unwrap_or(i64::MAX),
+            TagFreq::New => FREQ_NEW,
+            TagFreq::Popular => FREQ_POPULAR,
+        };
+        stmt.execute(params![
+            i64::try_from(id)?,
+            cached.tag.0,
+            freq,
+            cached.tag_lowered
+        ])?;
+    }
+    Ok(())
+}
+
+/// Removes the pin at `position` from the table & the search index, moving the pins after it
+/// up.
+fn delete_at(conn: &Connection, position: i64) -> Result<(), Box<dyn std::error::Error>> {
+    conn.execute(
+        "INSERT INTO pins_fts (pins_fts, rowid, title_lowered, tags_lowered, url_lowered,
+                               extended_lowered)
+         SELECT 'delete', id, title_lowered, tags_lowered, url_lowered, extended_lowered
+         FROM pins WHERE position = ?1",
+        [position],
+    )?;
+    conn.execute("DELETE FROM pins WHERE position = ?1", [position])?;
+    conn.execute(
+        "UPDATE pins SET position = position - 1 WHERE position > ?1",
+        [position],
+    )?;
+    Ok(())
+}
+
+/// Builds an FTS5 expression that requires all `queries`, each quoted as a phrase and passed
+/// through `wrap`. Returns `None` if no query is long enough for the trigram tokenizer.
+fn fts_expression<S, F>(queries: &[S], wrap: F) -> Option<String>
+where
+    S: AsRef<str>,
+    F: Fn(&str) -> String,
+{
+    let terms = queries
+        .iter()
+        .map(AsRef::as_ref)
+        .filter(|q| q.chars().count() >= MIN_TRIGRAM_QUERY)
+        .map(|q| wrap(&format!("\"{}\"", q.replace('"', "\"\""))))
+        .collect::<Vec<String>>();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" AND "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::pinboard::store::upsert_sorted;
+    use crate::tests::rand_temp_path;
+
+    fn sample_cache() -> (Vec<CachedPin<'static>>, Vec<CachedTag>) {
+        let pins: Vec<Pin> = serde_json::from_str(include_str!("../../sample.json"))
+            .expect("Couldn't read sample.json");
+        let pins = pins.into_iter().map(CachedPin::new).collect();
+        let tags = ["rust", "Rust-macros", "git", "zfs"]
+            .iter()
+            .enumerate()
+            .map(|(i, t)| CachedTag::new(Tag::new((*t).to_string(), i + 1)))
+            .collect();
+        (pins, tags)
+    }
+
+    #[test]
+    fn sqlite_roundtrip() {
+        let _ = env_logger::try_init();
+        debug!("sqlite_roundtrip: starting");
+        let (pins, tags) = sample_cache();
+        let path = rand_temp_path().join("cache.sqlite");
+        let mut db = SqliteCache::open(&path).expect("Couldn't open sqlite cache");
+        db.replace_all(&pins, &tags)
+            .expect("Couldn't write to sqlite cache");
+
+        assert_eq!(
+            StoreMeta::default(),
+            db.load_meta().expect("Couldn't load meta")
+        );
+        let meta = StoreMeta {
+            last_sync: Some(Utc.with_ymd_and_hms(2022, 7, 8, 9, 10, 11).unwrap()),
+            server_update: Some(Utc.with_ymd_and_hms(2022, 7, 8, 9, 0, 0).unwrap()),
+            dropped_pins: 2,
+        };
+        db.save_meta(&meta).expect("Couldn't save meta");
+
+        let db = SqliteCache::open(&path).expect("Couldn't reopen sqlite cache");
+        assert_eq!(pins, db.load_pins().expect("Couldn't load pins"));
+        assert_eq!(tags, db.load_tags().expect("Couldn't load tags"));
+        assert_eq!(meta, db.load_meta().expect("Couldn't load meta"));
+    }
+
+    #[test]
+    fn sqlite_matches_linear_search() {
+        let _ = env_logger::try_init();
+        debug!("sqlite_matches_linear_search: starting");
+        let (pins, tags) = sample_cache();
+        let mut db = SqliteCache::open(rand_temp_path().join("cache.sqlite"))
+            .expect("Couldn't open sqlite cache");
+        db.replace_all(&pins, &tags)
+            .expect("Couldn't write to sqlite cache");
+
+        let queries = vec!["series".to_string(), "blog".to_string()];
+        let ids = db
+            .matching_pin_ids(&queries, &[SearchType::TitleOnly, SearchType::TagOnly])
+            .expect("sqlite query failed")
+            .expect("queries are long enough for trigrams");
+        let expected = pins
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| {
+                queries.iter().all(|q| {
+                    p.title_lowered.contains(q.as_str())
+                        || p.tag_list.iter().any(|t| t.contains(q.as_str()))
+                })
+            })
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+        assert!(!expected.is_empty());
+        assert_eq!(expected, ids);
+
+        // Short queries can't use the trigram index
+        let r = db
+            .matching_pin_ids(&["zf".to_string()], &[SearchType::TitleOnly])
+            .expect("sqlite query failed");
+        assert!(r.is_none());
+
+        // Quotes in queries must not break FTS syntax
+        let r = db
+            .matching_pin_ids(&["\"rust".to_string()], &[SearchType::UrlOnly])
+            .expect("sqlite query failed");
+        assert_eq!(Some(vec![]), r);
+
+        let ids = db
+            .matching_tag_ids("rust")
+            .expect("sqlite query failed")
+            .expect("query is long enough for trigrams");
+        assert_eq!(vec![0, 1], ids);
+    }
+
+    #[test]
+    fn sqlite_upsert_delete() {
+        let _ = env_logger::try_init();
+        debug!("sqlite_upsert_delete: starting");
+        let (mut pins, tags) = sample_cache();
+        let path = rand_temp_path().join("cache.sqlite");
+        let mut db = SqliteCache::open(&path).expect("Couldn't open sqlite cache");
+        db.replace_all(&pins, &tags)
+            .expect("Couldn't write to sqlite cache");
+
+        let mut edited = pins[5].pin.clone();
+        edited.title = "Incremental trigrams".into();
+        let new_pin = PinBuilder::new("https://sqlite.org/fts5.html", "FTS5 trigrams")
+            .into_pin()
+            .expect("valid url");
+        for pin in [edited, new_pin] {
+            let pin = CachedPin::new(pin);
+            let (position, _) = upsert_sorted(&mut pins, pin.clone());
+            db.upsert_pin(position, &pin)
+                .expect("Couldn't upsert sqlite pin");
+        }
+        let url = pins.remove(2).pin.url.to_string();
+        db.delete_pin(2).expect("Couldn't delete sqlite pin");
+
+        let ids = db
+            .matching_pin_ids(&["trigrams".to_string()], &[SearchType::TitleOnly])
+            .expect("sqlite query failed")
+            .expect("query is long enough for trigrams");
+        let expected = pins
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.title_lowered.contains("trigrams"))
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+        assert_eq!(2, expected.len());
+        assert_eq!(expected, ids);
+        let ids = db
+            .matching_pin_ids(&[url.to_lowercase()], &[SearchType::UrlOnly])
+            .expect("sqlite query failed")
+            .expect("query is long enough for trigrams");
+        let expected = pins
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| p.url_lowered.contains(&url.to_lowercase()))
+            .map(|(i, _)| i)
+            .collect::<Vec<usize>>();
+        assert_eq!(expected, ids);
+
+        let db = SqliteCache::open(&path).expect("Couldn't reopen sqlite cache");
+        assert_eq!(pins, db.load_pins().expect("Couldn't load pins"));
+    }
+}
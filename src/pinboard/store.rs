@@ -0,0 +1,358 @@
+//! Storage backends that hold the bookmarks & tags [`Pinboard`](super::Pinboard) searches.
+//!
+//! [`CachedData`](super::CachedData) keeps them in files inside a cache directory while
+//! [`MemoryStore`] only keeps them in memory. Applications can implement [`BookmarkStore`] to
+//! keep bookmarks anywhere else.
+use chrono::prelude::*;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::tag::{Tag, TagFreq};
+use super::SearchType;
+
+/// Information about the data kept in a [`BookmarkStore`].
+#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct StoreMeta {
+    /// When pins & tags were last fetched from the server.
+    pub last_sync: Option<DateTime<Utc>>,
+    /// Server's last update time (`posts/update`) as last checked before pins were fetched,
+    /// see [`Pinboard::is_cache_outdated`](super::Pinboard::is_cache_outdated).
+    #[serde(default)]
+    pub server_update: Option<DateTime<Utc>>,
+    /// Number of bookmarks that couldn't be parsed when pins were last fetched.
+    #[serde(default)]
+    pub dropped_pins: usize,
+}
+
+pub trait BookmarkStore<'pin> {
+    /// Loads previously stored pins & tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if stored data is missing or can't be read.
+    fn load(&mut self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns true if the store holds pins & tags that can be searched.
+    fn is_valid(&self) -> bool;
+
+    /// All stored pins, newest first.
+    fn pins(&self) -> Option<&[CachedPin<'pin>]>;
+
+    /// All stored tags, most used first.
+    fn tags(&self) -> Option<&[CachedTag]>;
+
+    /// Replaces all stored pins & tags.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data can't be persisted.
+    fn replace_all(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+        tags: Vec<CachedTag>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Adds `pin` or replaces the stored pin that has the same url. The counts of stored tags
+    /// are updated to match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data can't be persisted.
+    fn upsert_pin(&mut self, pin: CachedPin<'pin>) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Upserts each of `pins` like [`BookmarkStore::upsert_pin`].
+    ///
+    /// Stores that persist all their data at once can override this to only do it once.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data can't be persisted.
+    fn upsert_pins(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        pins.into_iter().try_for_each(|pin| self.upsert_pin(pin))
+    }
+
+    /// Removes the pin with the given `url`, returning it if it was stored. The counts of
+    /// stored tags are updated to match.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the data can't be persisted.
+    fn delete_pin(
+        &mut self,
+        url: &str,
+    ) -> Result<Option<CachedPin<'pin>>, Box<dyn std::error::Error>>;
+
+    fn meta(&self) -> &StoreMeta;
+
+    /// # Errors
+    ///
+    /// Returns an error if the metadata can't be persisted.
+    fn set_meta(&mut self, meta: StoreMeta) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Returns the stored pins that may contain all of `queries` in `fields`.
+    ///
+    /// Stores with a search index can override this to skip pins that can't match. Callers
+    /// still need to check each returned pin. `queries` are normalized and lowercased.
+    fn searchable_pins(&self, queries: &[String], fields: &[SearchType]) -> Vec<&CachedPin<'pin>> {
+        let _ = (queries, fields);
+        self.pins().map(|p| p.iter().collect()).unwrap_or_default()
+    }
+
+    /// Returns the stored pins that have the tag `tag` (normalized & lowercased).
+    fn pins_with_tag(&self, tag: &str) -> Vec<&CachedPin<'pin>> {
+        self.pins()
+            .map(|p| {
+                p.iter()
+                    .filter(|p| p.tag_list.iter().any(|t| t == tag))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the stored tags that may contain `query`.
+    ///
+    /// Just like [`BookmarkStore::searchable_pins`], callers still need to check each returned
+    /// tag.
+    fn searchable_tags(&self, query: &str) -> Vec<&CachedTag> {
+        let _ = query;
+        self.tags().map(|t| t.iter().collect()).unwrap_or_default()
+    }
+}
+
+/// Inserts `pin` into `pins`, replacing any pin with the same url and keeping `pins` in
+/// descending creation time order. Returns where `pin` was inserted & the replaced pins.
+pub(crate) fn upsert_sorted<'pin>(
+    pins: &mut Vec<CachedPin<'pin>>,
+    pin: CachedPin<'pin>,
+) -> (usize, Vec<CachedPin<'pin>>) {
+    let (replaced, kept) = std::mem::take(pins)
+        .into_iter()
+        .partition(|p| p.pin.url == pin.pin.url);
+    *pins = kept;
+    let idx = pins.partition_point(|p| p.pin.time >= pin.pin.time);
+    pins.insert(idx, pin);
+    (idx, replaced)
+}
+
+/// Updates the counts of `tags` once the `removed` pins are replaced by `added`, keeping
+/// `tags` most used first. Tags are compared like [`CachedTag::tag_lowered`], those no pin
+/// uses anymore are dropped and new ones are added.
+pub(crate) fn recount_tags(
+    tags: &mut Vec<CachedTag>,
+    removed: &[CachedPin],
+    added: Option<&CachedPin>,
+) {
+    let changes = removed
+        .iter()
+        .map(|p| (p, false))
+        .chain(added.map(|p| (p, true)));
+    for (cached_pin, is_added) in changes {
+        for name in &cached_pin.pin.tags {
+            let tag = CachedTag::new(Tag::new(name.clone(), 1));
+            match tags.iter_mut().find(|t| t.tag_lowered == tag.tag_lowered) {
+                Some(CachedTag {
+                    tag: Tag(_, TagFreq::Used(n)),
+                    ..
+                }) => {
+                    *n = if is_added {
+                        n.saturating_add(1)
+                    } else {
+                        n.saturating_sub(1)
+                    };
+                }
+                Some(_) => {}
+                None if is_added => tags.push(tag),
+                None => {}
+            }
+        }
+    }
+    tags.retain(|t| t.tag.1 != TagFreq::Used(0));
+    tags.sort_by(|t1, t2| t1.tag.cmp(&t2.tag).reverse());
+}
+
+/// A store that keeps pins & tags only in memory.
+#[derive(Debug, Default)]
+pub struct MemoryStore<'pin> {
+    pins: Option<Vec<CachedPin<'pin>>>,
+    tags: Option<Vec<CachedTag>>,
+    meta: StoreMeta,
+}
+
+impl<'pin> MemoryStore<'pin> {
+    /// Creates an empty store. [`Pinboard`](super::Pinboard) will fill it from the server.
+    #[must_use]
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    /// Creates a store that already holds `pins` & `tags`.
+    #[must_use]
+    pub fn with_data(mut pins: Vec<CachedPin<'pin>>, tags: Vec<CachedTag>) -> Self {
+        pins.sort_by(|p1, p2| p1.pin.time.cmp(&p2.pin.time).reverse());
+        MemoryStore {
+            pins: Some(pins),
+            tags: Some(tags),
+            meta: StoreMeta::default(),
+        }
+    }
+}
+
+impl<'pin> BookmarkStore<'pin> for MemoryStore<'pin> {
+    fn load(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.is_valid() {
+            Ok(())
+        } else {
+            Err("Memory store is empty".into())
+        }
+    }
+
+    fn is_valid(&self) -> bool {
+        self.pins.is_some() && self.tags.is_some()
+    }
+
+    fn pins(&self) -> Option<&[CachedPin<'pin>]> {
+        self.pins.as_deref()
+    }
+
+    fn tags(&self) -> Option<&[CachedTag]> {
+        self.tags.as_deref()
+    }
+
+    fn replace_all(
+        &mut self,
+        pins: Vec<CachedPin<'pin>>,
+        tags: Vec<CachedTag>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.pins = Some(pins);
+        self.tags = Some(tags);
+        Ok(())
+    }
+
+    fn upsert_pin(&mut self, pin: CachedPin<'pin>) -> Result<(), Box<dyn std::error::Error>> {
+        let (idx, replaced) = upsert_sorted(self.pins.get_or_insert_with(Vec::new), pin);
+        if let (Some(tags), Some(pins)) = (self.tags.as_mut(), self.pins.as_ref()) {
+            recount_tags(tags, &replaced, pins.get(idx));
+        }
+        Ok(())
+    }
+
+    fn delete_pin(
+        &mut self,
+        url: &str,
+    ) -> Result<Option<CachedPin<'pin>>, Box<dyn std::error::Error>> {
+        let removed = self.pins.as_mut().and_then(|pins| {
+            pins.iter()
+                .position(|p| p.pin.url.as_str() == url)
+                .map(|idx| pins.remove(idx))
+        });
+        if let (Some(tags), Some(removed)) = (self.tags.as_mut(), removed.as_ref()) {
+            recount_tags(tags, std::slice::from_ref(removed), None);
+        }
+        Ok(removed)
+    }
+
+    fn meta(&self) -> &StoreMeta {
+        &self.meta
+    }
+
+    fn set_meta(&mut self, meta: StoreMeta) -> Result<(), Box<dyn std::error::Error>> {
+        self.meta = meta;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    fn cached_pin(url: &str, title: &str, year: i32) -> CachedPin<'static> {
+        let mut pin = PinBuilder::new(url.to_string(), title.to_string())
+            .tags(["rust"])
+            .into_pin()
+            .expect("valid url");
+        pin.time = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        CachedPin::new(pin)
+    }
+
+    #[test]
+    fn memory_store_upsert_delete() {
+        let _ = env_logger::try_init();
+        debug!("memory_store_upsert_delete: starting");
+        let mut store = MemoryStore::new();
+        assert!(!store.is_valid());
+        assert!(store.load().is_err());
+
+        store
+            .replace_all(
+                vec![
+                    cached_pin("https://b.com/", "b", 2020),
+                    cached_pin("https://a.com/", "a", 2010),
+                ],
+                vec![CachedTag::new(Tag::new("rust".to_string(), 2))],
+            )
+            .expect("memory store can't fail");
+        assert!(store.is_valid());
+
+        // New pins are placed according to their creation time
+        store
+            .upsert_pin(cached_pin("https://c.com/", "c", 2015))
+            .expect("memory store can't fail");
+        let urls = |s: &MemoryStore| {
+            s.pins()
+                .unwrap()
+                .iter()
+                .map(|p| p.pin.url.to_string())
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(
+            vec!["https://b.com/", "https://c.com/", "https://a.com/"],
+            urls(&store)
+        );
+
+        // Existing pins are replaced
+        store
+            .upsert_pin(cached_pin("https://a.com/", "new a", 2010))
+            .expect("memory store can't fail");
+        assert_eq!(3, store.pins().unwrap().len());
+        assert_eq!("new a", store.pins().unwrap()[2].pin.title);
+
+        let removed = store
+            .delete_pin("https://c.com/")
+            .expect("memory store can't fail");
+        assert_eq!("c", removed.expect("pin should have been found").pin.title);
+        assert!(store
+            .delete_pin("https://c.com/")
+            .expect("memory store can't fail")
+            .is_none());
+        assert_eq!(vec!["https://b.com/", "https://a.com/"], urls(&store));
+
+        // Tag counts follow the stored pins
+        let tags = |s: &MemoryStore| {
+            s.tags()
+                .unwrap()
+                .iter()
+                .map(|t| (t.tag.0.clone(), t.tag.1.clone()))
+                .collect::<Vec<(String, TagFreq)>>()
+        };
+        assert_eq!(vec![("rust".to_string(), TagFreq::Used(2))], tags(&store));
+        let mut pin = cached_pin("https://a.com/", "a", 2010).pin;
+        pin.tags = vec!["Rust".to_string(), "lang".to_string()];
+        store
+            .upsert_pin(CachedPin::new(pin))
+            .expect("memory store can't fail");
+        assert_eq!(
+            vec![
+                ("rust".to_string(), TagFreq::Used(2)),
+                ("lang".to_string(), TagFreq::Used(1))
+            ],
+            tags(&store)
+        );
+        store
+            .delete_pin("https://a.com/")
+            .expect("memory store can't fail");
+        assert_eq!(vec![("rust".to_string(), TagFreq::Used(1))], tags(&store));
+    }
+}
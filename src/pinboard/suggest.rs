@@ -0,0 +1,181 @@
+//! Offline tag suggestions ranked from the user's own bookmarks.
+//!
+//! Tags score from three signals:
+//! - bookmarks of the same domain: the share of them using the tag,
+//! - the title & description: tags spelled out in them, and the tags of bookmarks whose titles
+//!   share keywords with the title,
+//! - co-occurrence: tags scored above lend part of their score to tags often used along with
+//!   them.
+use std::collections::{HashMap, HashSet};
+
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+
+use super::cached_data::CachedPin;
+use super::cooccurrence::TagCooccurrence;
+
+const DOMAIN_WEIGHT: f64 = 2.0;
+const KEYWORD_WEIGHT: f64 = 1.5;
+const SIMILAR_TITLE_WEIGHT: f64 = 1.0;
+const COOCCURRENCE_WEIGHT: f64 = 0.5;
+
+/// Words too common to tell bookmarks apart.
+const STOP_WORDS: &[&str] = &[
+    "a", "an", "and", "are", "as", "at", "be", "by", "for", "from", "how", "in", "is", "it", "of",
+    "on", "or", "that", "the", "this", "to", "what", "with", "you", "your",
+];
+
+/// Unicode normalized (nfkd) & lowercased words of `text`, without stop words.
+fn keywords(text: &str) -> HashSet<String> {
+    text.nfkd()
+        .collect::<String>()
+        .to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty() && !STOP_WORDS.contains(w))
+        .map(ToString::to_string)
+        .collect()
+}
+
+fn domain(url: &Url) -> Option<&str> {
+    url.host_str().map(|h| h.strip_prefix("www.").unwrap_or(h))
+}
+
+/// Tags of `pins` scored for a new bookmark, best first. Pins of `url` itself are ignored.
+pub(crate) fn suggest_tags<'pin>(
+    pins: &[CachedPin<'pin>],
+    url: &Url,
+    title: &str,
+    description: &str,
+) -> Vec<(f64, String)> {
+    debug!("suggest_tags: starting.");
+    let pins = pins
+        .iter()
+        .filter(|p| p.pin.url != *url)
+        .collect::<Vec<_>>();
+    let cooccurrence = TagCooccurrence::new(pins.iter().copied());
+    let mut scores: HashMap<&str, f64> = HashMap::new();
+
+    let same_domain = pins
+        .iter()
+        .filter(|p| domain(&p.pin.url).is_some() && domain(&p.pin.url) == domain(url))
+        .collect::<Vec<_>>();
+    #[allow(clippy::cast_precision_loss)]
+    let share = DOMAIN_WEIGHT / same_domain.len().max(1) as f64;
+    for p in &same_domain {
+        for tag in &p.tag_list {
+            *scores.entry(tag).or_default() += share;
+        }
+    }
+
+    let title_words = keywords(title);
+    let words = &title_words | &keywords(description);
+    let mut seen = HashSet::new();
+    for tag in pins.iter().flat_map(|p| &p.tag_list) {
+        let tag_words = keywords(tag);
+        if seen.insert(tag) && !tag_words.is_empty() && tag_words.is_subset(&words) {
+            *scores.entry(tag).or_default() += KEYWORD_WEIGHT;
+        }
+    }
+
+    // Tags of bookmarks with similar titles, weighted by how similar (Jaccard index)
+    let similar = pins
+        .iter()
+        .filter_map(|p| {
+            let pin_words = keywords(&p.title_lowered);
+            let shared = pin_words.intersection(&title_words).count();
+            #[allow(clippy::cast_precision_loss)]
+            (shared > 0).then(|| {
+                (
+                    p,
+                    shared as f64 / pin_words.union(&title_words).count() as f64,
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    let total: f64 = similar.iter().map(|(_, s)| s).sum();
+    for (p, similarity) in &similar {
+        for tag in &p.tag_list {
+            *scores.entry(tag).or_default() += SIMILAR_TITLE_WEIGHT * similarity / total;
+        }
+    }
+
+    let seeds = scores
+        .iter()
+        .map(|(tag, score)| (*tag, *score))
+        .collect::<Vec<_>>();
+    for (seed, seed_score) in seeds {
+        #[allow(clippy::cast_precision_loss)]
+        let seed_count = cooccurrence.count(seed) as f64;
+        for (tag, n) in cooccurrence.with(seed) {
+            #[allow(clippy::cast_precision_loss)]
+            let lent = COOCCURRENCE_WEIGHT * seed_score * n as f64 / seed_count;
+            *scores.entry(tag).or_default() += lent;
+        }
+    }
+
+    let mut suggestions = scores
+        .into_iter()
+        .filter(|(_, score)| *score > 0.0)
+        .map(|(tag, score)| (score, cooccurrence.name(tag).to_string()))
+        .collect::<Vec<_>>();
+    suggestions.sort_by(|(s1, t1), (s2, t2)| s2.total_cmp(s1).then_with(|| t1.cmp(t2)));
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    #[test]
+    fn suggest_local_tags() {
+        let _ = env_logger::try_init();
+        debug!("suggest_local_tags: starting");
+        let pins = [
+            (
+                "https://blog.rust-lang.org/1",
+                "Announcing Rust 1.70",
+                vec!["Rust", "release"],
+            ),
+            (
+                "https://blog.rust-lang.org/2",
+                "Rust 2024 edition",
+                vec!["rust", "edition"],
+            ),
+            (
+                "https://go.dev/blog/1",
+                "Go generics",
+                vec!["go", "generics"],
+            ),
+            (
+                "https://example.com/",
+                "Async Rust patterns",
+                vec!["rust", "async"],
+            ),
+        ]
+        .into_iter()
+        .map(|(url, title, tags)| {
+            CachedPin::new(
+                PinBuilder::new(url, title)
+                    .tags(tags)
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        })
+        .collect::<Vec<_>>();
+        let url = Url::parse("https://www.blog.rust-lang.org/3").expect("valid url");
+        let suggestions = suggest_tags(&pins, &url, "Announcing async closures", "");
+        let tags = suggestions
+            .iter()
+            .map(|(_, t)| t.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(Some(&"Rust"), tags.first());
+        assert!(tags.contains(&"async") && tags.contains(&"release"));
+        assert!(!tags.contains(&"go"));
+        assert!(suggestions.windows(2).all(|w| w[0].0 >= w[1].0));
+
+        // Bookmarks of the url itself don't count
+        let url = Url::parse("https://go.dev/blog/1").expect("valid url");
+        assert!(suggest_tags(&pins, &url, "", "").is_empty());
+    }
+}
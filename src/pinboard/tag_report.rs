@@ -0,0 +1,170 @@
+//! Tag health report: tags worth cleaning up.
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+
+use super::cached_data::{CachedPin, CachedTag};
+use super::cooccurrence::TagCooccurrence;
+use super::tag::TagFreq;
+
+/// A tag whose count in the tags cache differs from the number of cached bookmarks using it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagCountMismatch {
+    pub tag: String,
+    /// Count in the tags cache, `0` if the tag isn't in it.
+    pub cached: usize,
+    /// Number of cached bookmarks using the tag.
+    pub actual: usize,
+}
+
+/// A tag not used by any bookmark created since a given date.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StaleTag {
+    pub tag: String,
+    /// Creation time of the newest bookmark using the tag.
+    pub last_used: DateTime<Utc>,
+    pub count: usize,
+}
+
+/// Tags worth cleaning up, see [`Pinboard::tag_report`].
+///
+/// Tags are compared after unicode normalization, ignoring case.
+///
+/// [`Pinboard::tag_report`]: super::Pinboard::tag_report
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TagReport {
+    /// Tags in the tags cache that no cached bookmark uses, sorted by name.
+    pub unused: Vec<String>,
+    /// Tags used by exactly one cached bookmark, sorted by name.
+    pub singletons: Vec<String>,
+    /// Tags whose cached count is out of date (including unused ones), sorted by name.
+    pub mismatched: Vec<TagCountMismatch>,
+    /// Tags not used since the given date, least recently used first.
+    pub stale: Vec<StaleTag>,
+}
+
+impl TagReport {
+    /// `true` if no tag needs cleaning up.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.unused.is_empty()
+            && self.singletons.is_empty()
+            && self.mismatched.is_empty()
+            && self.stale.is_empty()
+    }
+}
+
+/// Builds the report of `tags` & the tags of `pins`, listing stale tags only if `since` is
+/// given.
+pub(crate) fn tag_report(
+    pins: &[CachedPin],
+    tags: &[CachedTag],
+    since: Option<DateTime<Utc>>,
+) -> TagReport {
+    debug!("tag_report: starting.");
+    let usage = TagCooccurrence::new(pins);
+    // Normalized tag -> (name, cached count)
+    let mut cached: BTreeMap<&str, (&str, usize)> = BTreeMap::new();
+    for cached_tag in tags {
+        let count = match cached_tag.tag.1 {
+            TagFreq::Used(n) => n,
+            _ => 0,
+        };
+        cached
+            .entry(&cached_tag.tag_lowered)
+            .or_insert((&cached_tag.tag.0, 0))
+            .1 += count;
+    }
+    for tag in usage.tags() {
+        cached.entry(tag).or_insert((usage.name(tag), 0));
+    }
+
+    let mut report = TagReport::default();
+    for (tag, (name, cached_count)) in cached {
+        let actual = usage.count(tag);
+        match actual {
+            0 => report.unused.push(name.to_string()),
+            1 => report.singletons.push(name.to_string()),
+            _ => {}
+        }
+        if actual != cached_count {
+            report.mismatched.push(TagCountMismatch {
+                tag: name.to_string(),
+                cached: cached_count,
+                actual,
+            });
+        }
+        if let Some(last_used) = usage
+            .last_used(tag)
+            .filter(|t| since.is_some_and(|s| *t < s))
+        {
+            report.stale.push(StaleTag {
+                tag: name.to_string(),
+                last_used,
+                count: actual,
+            });
+        }
+    }
+    report.stale.sort_by(|t1, t2| {
+        t1.last_used
+            .cmp(&t2.last_used)
+            .then_with(|| t1.tag.cmp(&t2.tag))
+    });
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+    use crate::pinboard::tag::Tag;
+
+    #[test]
+    fn tag_health_report() {
+        let _ = env_logger::try_init();
+        debug!("tag_health_report: starting");
+        let pins = [
+            ("2020-01-01T00:00:00Z", vec!["Rust", "old"]),
+            ("2024-01-01T00:00:00Z", vec!["rust", "once"]),
+            ("2019-06-01T00:00:00Z", vec!["old"]),
+        ]
+        .into_iter()
+        .map(|(time, tags)| {
+            let mut pin = PinBuilder::new("https://example.com/", "pin")
+                .tags(tags)
+                .into_pin()
+                .expect("valid url");
+            pin.time = time.parse().expect("valid time");
+            CachedPin::new(pin)
+        })
+        .collect::<Vec<_>>();
+        let tags = [("Rust", 2), ("old", 3), ("gone", 4)]
+            .into_iter()
+            .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+            .collect::<Vec<_>>();
+        let since = "2021-01-01T00:00:00Z".parse().expect("valid time");
+
+        let report = tag_report(&pins, &tags, Some(since));
+        assert_eq!(vec!["gone"], report.unused);
+        assert_eq!(vec!["once"], report.singletons);
+        assert_eq!(
+            vec![("gone", 4, 0), ("old", 3, 2), ("once", 0, 1)],
+            report
+                .mismatched
+                .iter()
+                .map(|m| (m.tag.as_str(), m.cached, m.actual))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![StaleTag {
+                tag: "old".to_string(),
+                last_used: "2020-01-01T00:00:00Z".parse().expect("valid time"),
+                count: 2,
+            }],
+            report.stale
+        );
+
+        let report = tag_report(&pins, &tags, None);
+        assert!(report.stale.is_empty() && !report.is_empty());
+    }
+}
@@ -0,0 +1,235 @@
+//! Near-duplicate tags (`Rust`, `rust`, differently normalized unicode, typos) and merging them.
+use super::cached_data::{CachedPin, CachedTag};
+use super::pin::Pin;
+use super::tag::{Tag, TagFreq};
+
+/// Keys shorter than this are only grouped when they're the same, any edit turns short
+/// tags like `go` & `js` into unrelated tags.
+const MIN_FUZZY_CHARS: usize = 4;
+
+/// Tags that are variants of each other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagVariants {
+    /// Unicode normalized (nfkd) & lowercased form of the most used tag.
+    pub normalized: String,
+    /// The variants, most used first.
+    pub tags: Vec<Tag>,
+}
+
+/// Number of single character insertions, deletions or substitutions turning `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substitution.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
+
+fn fuzzy_match(a: &str, b: &str, max_distance: usize) -> bool {
+    max_distance > 0
+        && a.chars().count() >= MIN_FUZZY_CHARS
+        && b.chars().count() >= MIN_FUZZY_CHARS
+        && edit_distance(a, b) <= max_distance
+}
+
+/// Groups `tags` that have the same normalized form or, if `max_distance` isn't 0, whose
+/// normalized forms are at most `max_distance` edits apart. Only groups of at least two tags
+/// are returned, the group of the most used tag first.
+pub(crate) fn group_variants(tags: &[CachedTag], max_distance: usize) -> Vec<TagVariants> {
+    debug!("group_variants: starting.");
+    // (normalized forms, tags) of each group
+    let mut groups: Vec<(Vec<&str>, Vec<Tag>)> = vec![];
+    for cached_tag in tags {
+        let key = cached_tag.tag_lowered.as_str();
+        let group = groups.iter_mut().find(|(keys, _)| {
+            keys.iter()
+                .any(|k| *k == key || fuzzy_match(k, key, max_distance))
+        });
+        match group {
+            Some((keys, group_tags)) => {
+                if !keys.contains(&key) {
+                    keys.push(key);
+                }
+                group_tags.push(cached_tag.tag.clone());
+            }
+            None => groups.push((vec![key], vec![cached_tag.tag.clone()])),
+        }
+    }
+
+    let mut variants: Vec<TagVariants> = groups
+        .into_iter()
+        .filter(|(_, tags)| tags.len() > 1)
+        .map(|(_, mut tags)| {
+            tags.sort_by(|t1, t2| t1.cmp(t2).reverse());
+            TagVariants {
+                normalized: CachedTag::new(tags[0].clone()).tag_lowered,
+                tags,
+            }
+        })
+        .collect();
+    variants.sort_by(|v1, v2| v1.tags[0].cmp(&v2.tags[0]).reverse());
+    variants
+}
+
+/// Returns `pin` with its tags `from` renamed to `into`, `None` if it has none of them.
+pub(crate) fn rename_tags<'pin>(pin: &Pin<'pin>, into: &str, from: &[&str]) -> Option<Pin<'pin>> {
+    if !pin.tags.iter().any(|t| from.contains(&t.as_str())) {
+        return None;
+    }
+    let mut renamed = pin.clone();
+    let mut tags: Vec<String> = vec![];
+    for tag in renamed.tags {
+        let tag = if from.contains(&tag.as_str()) {
+            into.to_string()
+        } else {
+            tag
+        };
+        if !tags.contains(&tag) {
+            tags.push(tag);
+        }
+    }
+    renamed.tags = tags;
+    Some(renamed)
+}
+
+/// Returns `pins` & `tags` after renaming the tags `from` to `into` the way the server does.
+pub(crate) fn merge_cached<'pin>(
+    pins: &[CachedPin<'pin>],
+    tags: &[CachedTag],
+    into: &str,
+    from: &[&str],
+) -> (Vec<CachedPin<'pin>>, Vec<CachedTag>) {
+    debug!("merge_cached: starting.");
+    let pins = pins
+        .iter()
+        .map(|cached_pin| {
+            rename_tags(&cached_pin.pin, into, from)
+                .map_or_else(|| cached_pin.clone(), CachedPin::new)
+        })
+        .collect();
+
+    let used = |tag: &Tag| match tag.1 {
+        TagFreq::Used(n) => n,
+        _ => 0,
+    };
+    let count = tags
+        .iter()
+        .filter(|t| t.tag.0 == into || from.contains(&t.tag.0.as_str()))
+        .map(|t| used(&t.tag))
+        .sum();
+    let mut tags: Vec<CachedTag> = tags
+        .iter()
+        .filter(|t| t.tag.0 != into && !from.contains(&t.tag.0.as_str()))
+        .cloned()
+        .collect();
+    tags.push(CachedTag::new(Tag::new(into.to_string(), count)));
+    tags.sort_by(|t1, t2| t1.tag.cmp(&t2.tag).reverse());
+    (pins, tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pinboard::pin::PinBuilder;
+
+    fn cached_tags(tags: &[(&str, usize)]) -> Vec<CachedTag> {
+        tags.iter()
+            .map(|(t, n)| CachedTag::new(Tag::new((*t).to_string(), *n)))
+            .collect()
+    }
+
+    #[test]
+    fn tag_edit_distance() {
+        assert_eq!(0, edit_distance("rust", "rust"));
+        assert_eq!(1, edit_distance("rust", "rusty"));
+        assert_eq!(2, edit_distance("pyhton", "python"));
+        assert_eq!(3, edit_distance("", "abc"));
+        assert_eq!(1, edit_distance("café", "cafe"));
+    }
+
+    #[test]
+    fn group_tag_variants() {
+        let _ = env_logger::try_init();
+        debug!("group_tag_variants: starting");
+        // "ﬁle" is the ligature fi, nfkd normalizes it to "file"
+        let tags = cached_tags(&[
+            ("rust", 20),
+            ("python", 10),
+            ("Rust", 5),
+            ("file", 4),
+            ("pyhton", 2),
+            ("ﬁle", 1),
+            ("go", 1),
+            ("js", 1),
+        ]);
+        let variants = group_variants(&tags, 0);
+        assert_eq!(2, variants.len());
+        assert_eq!("rust", variants[0].normalized);
+        assert_eq!(
+            vec!["rust", "Rust"],
+            variants[0]
+                .tags
+                .iter()
+                .map(|t| t.0.as_str())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec!["file", "ﬁle"],
+            variants[1]
+                .tags
+                .iter()
+                .map(|t| t.0.as_str())
+                .collect::<Vec<_>>()
+        );
+
+        let variants = group_variants(&tags, 2);
+        assert_eq!(3, variants.len());
+        assert_eq!("python", variants[1].normalized);
+        assert_eq!(2, variants[1].tags.len());
+        // Short tags aren't fuzzy matched
+        assert!(variants.iter().all(|v| v.normalized != "go"));
+
+        assert!(group_variants(&cached_tags(&[("a", 1), ("b", 1)]), 0).is_empty());
+    }
+
+    #[test]
+    fn merge_cached_tags() {
+        let _ = env_logger::try_init();
+        debug!("merge_cached_tags: starting");
+        let pins: Vec<CachedPin> = [vec!["Rust", "lang"], vec!["rust", "RUST"], vec!["go"]]
+            .into_iter()
+            .enumerate()
+            .map(|(i, tags)| {
+                CachedPin::new(
+                    PinBuilder::new(format!("https://example.com/{i}"), "pin".to_string())
+                        .tags(tags)
+                        .into_pin()
+                        .expect("valid url"),
+                )
+            })
+            .collect();
+        let tags = cached_tags(&[
+            ("rust", 1),
+            ("Rust", 1),
+            ("lang", 1),
+            ("go", 1),
+            ("RUST", 1),
+        ]);
+        let (pins, tags) = merge_cached(&pins, &tags, "rust", &["Rust", "RUST"]);
+
+        assert_eq!(vec!["rust", "lang"], pins[0].pin.tags);
+        assert_eq!(vec!["rust"], pins[1].pin.tags);
+        assert_eq!(vec!["rust"], pins[1].tag_list);
+        assert_eq!(vec!["go"], pins[2].pin.tags);
+        assert_eq!(3, tags.len());
+        assert_eq!("rust", tags[0].tag.0);
+        assert_eq!(TagFreq::Used(3), tags[0].tag.1);
+    }
+}
@@ -12,17 +12,31 @@ use mockito::{mock, Matcher};
 use url;
 use url::ParseError;
 
-use crate::tests::rand_temp_path;
+use crate::tests::{isolate_config_dir, rand_temp_path};
 
 const TEST_URL: &str = "https://githuуй.com/Здравствуйт?q=13#fragment";
 
+fn sample_pins() -> Vec<CachedPin<'static>> {
+    let pins: Vec<Pin> =
+        serde_json::from_str(include_str!("../../sample.json")).expect("Couldn't read sample.json");
+    pins.into_iter().map(CachedPin::new).collect()
+}
+
+/// Pinboard holding the pins of `sample.json` & `tags` in memory.
+fn sample_pinboard<'api, 'pin>(tags: Vec<CachedTag>) -> Pinboard<'api, 'pin, MemoryStore<'pin>> {
+    Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(sample_pins(), tags),
+    )
+    .unwrap_or_else(|e| panic!("{e:?}"))
+    .pinboard
+}
+
 #[test]
 fn test_cached_file_names() {
     let _ = env_logger::try_init();
     debug!("test_cached_data: starting.");
-    let mut h = dirs::home_dir().unwrap();
-    h.push(".cache");
-    h.push("rusty-pin");
+    let mut h = paths::default_cache_dir().unwrap();
     let p: Option<PathBuf> = None;
     let c = CachedData::new(p).expect("Can't initiate 'CachedData'.");
     assert_eq!(c.cache_dir, h);
@@ -135,8 +149,16 @@ fn find_url_test() {
 
     assert_eq!(1, pins.len());
 
-    let r =
-        pinboard.find_url("https://blog.khubla.com/freebsd/time-machine-backups-using-FreeBSD-zfs");
+    // Urls are canonicalized: scheme, `www.`, trailing slash & tracking parameters don't matter
+    let r = pinboard.find_url(
+        "https://www.blog.khubla.com/freebsd/time-machine-backups-using-FreeBSD-zfs/?utm_source=x",
+    );
+    assert!(r.is_ok());
+    let op = r.unwrap();
+    assert_eq!(1, op.expect("canonical url should match").len());
+
+    let r = pinboard
+        .find_url("http://blog.khubla.com/freebsd/time-machine-backups-using-FreeBSD-zfs#a");
     assert!(r.is_ok());
     let op = r.unwrap();
     assert!(op.is_none());
@@ -331,11 +353,10 @@ fn list_bookmarks() {
 fn add_pin_test() {
     let _ = env_logger::try_init();
     debug!("delete_a_pin: starting.");
-    let mut myhome = dirs::home_dir().expect("Can't find home dir");
-    myhome.push(".cache");
-    myhome.push("mockito-rusty-pin");
-    let cache_path = Some(myhome);
-    let pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path)
+    let (_m1, _m2) = create_mockito_servers();
+    // Added pins are cached, so don't touch the cache other tests read
+    let cache_path = Some(rand_temp_path());
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), cache_path)
         .expect("Can't setup Pinboard")
         .pinboard;
 
@@ -343,30 +364,1058 @@ fn add_pin_test() {
         // add a good url
         let _m1 = start_mockito_server(r"^/posts/add.*$", 200, r#"{"result_code":"done"}"#);
         let p = PinBuilder::new(TEST_URL, "test bookmark/pin")
-            .tags("tagestan what")
+            .tags(["tagestan", "what"])
             .description("russian website!")
-            .shared("yes")
-            .into_pin();
+            .shared(true)
+            .into_pin()
+            .expect("valid url");
         assert!(pinboard.add_pin(p).is_ok());
     }
     {
-        // add a bad url
-        let _m1 = start_mockito_server(r"^/posts/add.+bad_url*$", 200, r#"{"result_code":"done"}"#);
-        let p = PinBuilder::new(":/ bad_url", "test bookmark/pin")
-            .tags("tagestan what")
+        // a pin can't have a bad url
+        let r = PinBuilder::new(":/ bad_url", "test bookmark/pin")
+            .tags(["tagestan", "what"])
             .description("russian website!")
-            .shared("yes")
-            .into_pin();
-        let r = pinboard
-            .add_pin(p)
+            .shared(true)
+            .into_pin()
             .expect_err("Should return parse error for malformed url");
-        assert_eq!(
-            &ParseError::RelativeUrlWithoutBase,
-            r.downcast_ref::<ParseError>().unwrap()
-        );
+        assert_eq!(ParseError::RelativeUrlWithoutBase, r);
     }
 }
 
+#[test]
+fn add_pin_defaults_test() {
+    let _ = env_logger::try_init();
+    debug!("add_pin_defaults_test: starting.");
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![], vec![]),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    pinboard.enable_private_new_pin(true);
+    pinboard.enable_toread_new_pin(true);
+
+    let mock_add = |url: &str, shared: &str, toread: &str| {
+        mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("url".to_string(), url.to_string()),
+                Matcher::UrlEncoded("shared".to_string(), shared.to_string()),
+                Matcher::UrlEncoded("toread".to_string(), toread.to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result_code":"done"}"#)
+            .expect(1)
+            .create()
+    };
+
+    // Flags not set on the builder get the account's defaults
+    let m = mock_add("https://example.com/defaults", "no", "yes");
+    let p = PinBuilder::new("https://example.com/defaults", "defaults")
+        .into_pin()
+        .expect("valid url");
+    pinboard.add_pin(p).expect("couldn't add pin");
+    m.assert();
+
+    // Explicit flags win
+    let m = mock_add("https://example.com/explicit", "yes", "no");
+    let p = PinBuilder::new("https://example.com/explicit", "explicit")
+        .shared(true)
+        .toread(false)
+        .into_pin()
+        .expect("valid url");
+    pinboard.add_pin(p).expect("couldn't add pin");
+    m.assert();
+}
+
+#[test]
+fn add_pin_modes_test() {
+    let _ = env_logger::try_init();
+    debug!("add_pin_modes_test: starting.");
+    let mut pinboard = sample_pinboard(vec![]);
+    let cached_url = "http://notryanb.github.io/rust-blog-series-1.html";
+    let already_exists = |r: Result<(), Box<dyn std::error::Error>>| {
+        let e = r.expect_err("url is already bookmarked");
+        matches!(
+            e.downcast_ref::<AddError>(),
+            Some(AddError::AlreadyExists(_))
+        )
+    };
+
+    // Bookmarked urls are found in the cache without asking the server
+    let p = PinBuilder::new(cached_url, "again")
+        .into_pin()
+        .expect("valid url");
+    assert!(already_exists(
+        pinboard.add_pin_with_mode(p, AddMode::FailIfExists)
+    ));
+
+    // Merging keeps existing tags and creation time
+    let m = mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("url".to_string(), cached_url.to_string()),
+            Matcher::UrlEncoded(
+                "description".to_string(),
+                "Making a simple blog with Rust: Part 1".to_string(),
+            ),
+            Matcher::UrlEncoded("tags".to_string(), "rust tutorial series blog".to_string()),
+            Matcher::UrlEncoded("extended".to_string(), "part one".to_string()),
+            Matcher::UrlEncoded("dt".to_string(), "2017-09-22T03:59:03Z".to_string()),
+            Matcher::UrlEncoded("replace".to_string(), "yes".to_string()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"result_code":"done"}"#)
+        .expect(1)
+        .create();
+    let p = PinBuilder::new(cached_url, "again")
+        .tags(["Rust", "blog"])
+        .description("part one")
+        .into_pin()
+        .expect("valid url");
+    pinboard
+        .add_pin_with_mode(p, AddMode::Merge)
+        .expect("couldn't merge pin");
+    m.assert();
+
+    // Merged & added bookmarks are cached
+    let cached = pinboard
+        .find_url(cached_url)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("url is cached");
+    assert_eq!(Some("part one"), cached[0].extended.as_deref());
+    let m = mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+        .match_query(Matcher::UrlEncoded(
+            "url".to_string(),
+            "http://just.added/".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"result_code":"done"}"#)
+        .expect(1)
+        .create();
+    let new_pin = || {
+        PinBuilder::new("http://just.added/", "new")
+            .into_pin()
+            .expect("valid url")
+    };
+    pinboard
+        .add_pin_with_mode(new_pin(), AddMode::FailIfExists)
+        .expect("couldn't add pin");
+    m.assert();
+    assert!(already_exists(
+        pinboard.add_pin_with_mode(new_pin(), AddMode::FailIfExists)
+    ));
+
+    // Urls missing from the cache are looked up on the server if enabled
+    let _m = mock("GET", Matcher::Regex(r"^/posts/get.*$".to_string()))
+        .match_query(Matcher::UrlEncoded(
+            "url".to_string(),
+            "http://only.on.server/".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"date":"2017-10-09T07:59:36Z","user":"user","posts":[{"href":"http:\/\/only.on.server\/",
+"description":"server","extended":"","meta":"1","hash":"2","time":"2017-10-09T07:59:36Z",
+"shared":"no","toread":"no","tags":""}]}"#,
+        )
+        .create();
+    pinboard.enable_server_check_on_add(true);
+    let p = PinBuilder::new("http://only.on.server/", "new")
+        .into_pin()
+        .expect("valid url");
+    assert!(already_exists(
+        pinboard.add_pin_with_mode(p, AddMode::FailIfExists)
+    ));
+}
+
+#[test]
+fn replace_pin_flags_test() {
+    let _ = env_logger::try_init();
+    debug!("replace_pin_flags_test: starting.");
+    let public = PinBuilder::new("https://public.pin/", "public")
+        .shared(true)
+        .toread(true)
+        .into_pin()
+        .expect("valid url");
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![CachedPin::new(public)], vec![]),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    pinboard.enable_private_new_pin(true);
+    pinboard.enable_toread_new_pin(false);
+
+    let add = |title: &str, shared: &str, toread: &str| {
+        mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("url".to_string(), "https://public.pin/".to_string()),
+                Matcher::UrlEncoded("description".to_string(), title.to_string()),
+                Matcher::UrlEncoded("shared".to_string(), shared.to_string()),
+                Matcher::UrlEncoded("toread".to_string(), toread.to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result_code":"done"}"#)
+            .expect(1)
+            .create()
+    };
+
+    // Flags left unset keep the existing values, not the defaults of new pins
+    let m = add("replaced", "yes", "yes");
+    let p = PinBuilder::new("https://public.pin/", "replaced")
+        .into_pin()
+        .expect("valid url");
+    pinboard.add_pin(p).expect("couldn't replace pin");
+    m.assert();
+
+    let m = add("private", "no", "yes");
+    let p = PinBuilder::new("https://public.pin/", "private")
+        .shared(false)
+        .into_pin()
+        .expect("valid url");
+    pinboard.add_pin(p).expect("couldn't replace pin");
+    m.assert();
+}
+
+#[test]
+fn update_pin_test() {
+    let _ = env_logger::try_init();
+    debug!("update_pin_test: starting.");
+    let mut pinboard = sample_pinboard(vec![]);
+    let cached_url = "http://notryanb.github.io/rust-blog-series-1.html";
+
+    // The full record is sent back with its creation time
+    let m = mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+        .match_query(Matcher::AllOf(vec![
+            Matcher::UrlEncoded("url".to_string(), cached_url.to_string()),
+            Matcher::UrlEncoded(
+                "description".to_string(),
+                "Making a simple blog with Rust: Part 1".to_string(),
+            ),
+            Matcher::UrlEncoded("tags".to_string(), "rust tutorial blog".to_string()),
+            Matcher::UrlEncoded("extended".to_string(), "part one".to_string()),
+            Matcher::UrlEncoded("toread".to_string(), "yes".to_string()),
+            Matcher::UrlEncoded("dt".to_string(), "2017-09-22T03:59:03Z".to_string()),
+            Matcher::UrlEncoded("replace".to_string(), "yes".to_string()),
+        ]))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"result_code":"done"}"#)
+        .expect(1)
+        .create();
+    let patch = PinPatch::new()
+        .description("part one")
+        .toread(true)
+        .remove_tag("Series")
+        .add_tag("blog");
+    pinboard
+        .update_pin(cached_url, &patch)
+        .expect("couldn't update pin");
+    m.assert();
+
+    let _m = mock("GET", Matcher::Regex(r"^/posts/get.*$".to_string()))
+        .match_query(Matcher::UrlEncoded(
+            "url".to_string(),
+            "http://not.bookmarked.anywhere/".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"date":"2017-10-09T07:59:36Z","user":"user","posts":[]}"#)
+        .create();
+    let e = pinboard
+        .update_pin("http://not.bookmarked.anywhere/", &patch)
+        .expect_err("url isn't bookmarked");
+    assert!(matches!(
+        e.downcast_ref::<PatchError>(),
+        Some(PatchError::NotFound(_))
+    ));
+
+    let e = pinboard
+        .update_pin(cached_url, &PinPatch::new().title(""))
+        .expect_err("title can't be cleared");
+    assert_eq!(
+        Some(&PatchError::EmptyTitle),
+        e.downcast_ref::<PatchError>()
+    );
+
+    // Searches see the edit without refreshing the cache
+    let pins = pinboard
+        .find_url(cached_url)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("url is cached");
+    assert_eq!(1, pins.len());
+    assert_eq!(Some("part one"), pins[0].extended.as_deref());
+    assert_eq!(vec!["rust", "tutorial", "blog"], pins[0].tags);
+}
+
+#[test]
+fn bulk_edit_test() {
+    let _ = env_logger::try_init();
+    debug!("bulk_edit_test: starting.");
+    let pins = [
+        ("https://bulk.one/", vec!["rust", "todo"]),
+        ("https://bulk.two/", vec!["Rust"]),
+        ("https://bulk.three/", vec!["go"]),
+    ]
+    .into_iter()
+    .map(|(url, tags)| {
+        PinBuilder::new(url, "bulk")
+            .tags(tags)
+            .toread(false)
+            .into_pin()
+            .expect("valid url")
+    })
+    .collect::<Vec<_>>();
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins.iter().cloned().map(CachedPin::new).collect(), vec![]),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+
+    let mock_add = |url: &str, tags: &str, result: &str| {
+        mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("url".to_string(), url.to_string()),
+                Matcher::UrlEncoded("tags".to_string(), tags.to_string()),
+                Matcher::UrlEncoded("toread".to_string(), "yes".to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(format!(r#"{{"result_code":"{result}"}}"#))
+            .expect(1)
+            .create()
+    };
+    let m1 = mock_add("https://bulk.one/", "todo rust-lang", "done");
+    let m2 = mock_add("https://bulk.two/", "rust-lang", "something went wrong");
+    let m3 = mock_add("https://bulk.three/", "go", "done");
+
+    // A repeated bookmark & one without the replaced tag
+    let url = |i: usize| pins[i].url.clone();
+    let selection = [url(0), url(1), url(0), url(2)];
+
+    let ops = [BulkOp::ReplaceTag {
+        from: "rust".to_string(),
+        to: "rust-lang".to_string(),
+    }];
+    let outcomes = pinboard.bulk_edit(&selection, &[ops[0].clone(), BulkOp::SetToread(true)]);
+    m1.assert();
+    m2.assert();
+    m3.assert();
+    assert_eq!(3, outcomes.len());
+    let status = |url: &str| {
+        &outcomes
+            .iter()
+            .find(|o| o.url.as_str() == url)
+            .expect("every url has an outcome")
+            .status
+    };
+    assert_eq!(
+        BulkStatus::Updated,
+        *status("https://bulk.one/").as_ref().expect("edited")
+    );
+    assert_eq!(
+        "something went wrong",
+        status("https://bulk.two/")
+            .as_ref()
+            .expect_err("server failure")
+            .to_string()
+    );
+    assert_eq!(
+        BulkStatus::Updated,
+        *status("https://bulk.three/").as_ref().expect("edited")
+    );
+
+    // bulk.three doesn't have the replaced tag
+    let outcomes = pinboard.bulk_edit(&selection[3..], &ops);
+    assert_eq!(
+        BulkStatus::Unchanged,
+        *outcomes[0].status.as_ref().expect("untouched")
+    );
+
+    // Updated bookmarks are updated in the cache, the failed one is left as is
+    let cached = pinboard
+        .find_tag("rust-lang")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("edited pin is cached");
+    assert_eq!(1, cached.len());
+    assert_eq!("https://bulk.one/", cached[0].url.as_str());
+    assert!(cached[0].toread);
+    let cached = pinboard
+        .find_url("https://bulk.two/")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("cached");
+    assert_eq!(vec!["Rust"], cached[0].tags);
+
+    // Search results can be selected while they borrow the pinboard
+    let m = mock_add("https://bulk.three/", "go lang", "done");
+    let selection: Vec<Url> = pinboard
+        .find_tag("go")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("cached")
+        .iter()
+        .map(|p| p.url.clone())
+        .collect();
+    let outcomes = pinboard.bulk_edit(&selection, &[BulkOp::AddTag("lang".to_string())]);
+    m.assert();
+    assert_eq!(
+        BulkStatus::Updated,
+        *outcomes[0].status.as_ref().expect("edited")
+    );
+
+    // Urls must be cached
+    let outcomes = pinboard.bulk_edit(
+        &[Url::parse("https://not.cached/").expect("valid url")],
+        &[BulkOp::SetToread(true)],
+    );
+    assert!(outcomes[0]
+        .status
+        .as_ref()
+        .is_err_and(|e| e.is::<PatchError>()));
+}
+
+#[test]
+fn merge_tags_test() {
+    let _ = env_logger::try_init();
+    debug!("merge_tags_test: starting.");
+    let pins: Vec<CachedPin> = [vec!["rust", "lang"], vec!["Rust"], vec!["russt", "Rust"]]
+        .into_iter()
+        .enumerate()
+        .map(|(i, tags)| {
+            CachedPin::new(
+                PinBuilder::new(format!("https://merge.tags/{i}"), "merge".to_string())
+                    .tags(tags)
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        })
+        .collect();
+    let tags = [("rust", 1), ("Rust", 2), ("lang", 1), ("russt", 1)]
+        .into_iter()
+        .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+        .collect();
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins, tags),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+
+    let variants = pinboard
+        .tag_variants(0)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("Rust & rust are variants");
+    assert_eq!(1, variants.len());
+    assert_eq!("Rust", variants[0].tags[0].0);
+    let variants = pinboard
+        .tag_variants(1)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("russt is a typo");
+    assert_eq!(3, variants[0].tags.len());
+
+    let mock_rename = |old: &str| {
+        mock("GET", Matcher::Regex(r"^/tags/rename.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("old".to_string(), old.to_string()),
+                Matcher::UrlEncoded("new".to_string(), "rust".to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":"done"}"#)
+            .expect(1)
+            .create()
+    };
+    let m1 = mock_rename("Rust");
+    let m2 = mock_rename("russt");
+    pinboard
+        .merge_tags("rust", &["rust", "Rust", "russt"])
+        .expect("couldn't merge tags");
+    m1.assert();
+    m2.assert();
+
+    let tags = pinboard.list_tag_pairs().expect("tags are cached");
+    assert_eq!(2, tags.len());
+    assert_eq!(
+        ("rust", TagFreq::Used(4)),
+        (tags[0].0.as_str(), tags[0].1.clone())
+    );
+    assert_eq!(
+        3,
+        pinboard
+            .find_tag("rust")
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("merged tag")
+            .len()
+    );
+    assert!(pinboard
+        .list_bookmarks()
+        .expect("pins are cached")
+        .iter()
+        .all(|p| p.tags.iter().all(|t| t == "rust" || t == "lang")));
+    assert!(pinboard
+        .tag_variants(1)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+}
+
+#[test]
+fn dry_run_test() {
+    let _ = env_logger::try_init();
+    debug!("dry_run_test: starting.");
+    let pins: Vec<CachedPin> = [vec!["rust", "lang"], vec!["Rust"], vec!["go", "lang"]]
+        .into_iter()
+        .enumerate()
+        .map(|(i, tags)| {
+            CachedPin::new(
+                PinBuilder::new(format!("https://dry.run/{i}"), "dry".to_string())
+                    .tags(tags)
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        })
+        .collect();
+    let tags = [("rust", 1), ("Rust", 1), ("lang", 2), ("go", 1)]
+        .into_iter()
+        .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+        .collect();
+    let pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins, tags),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    let dry_run = pinboard.dry_run();
+    let counts = |plan: &ChangePlan| {
+        plan.tags
+            .iter()
+            .map(|t| (t.tag.clone(), t.before, t.after))
+            .collect::<Vec<_>>()
+    };
+
+    let plan = dry_run.delete_tag("lang");
+    assert_eq!(2, plan.pins.len());
+    assert!(plan.pins.iter().all(|c| c
+        .after
+        .as_ref()
+        .is_some_and(|p| !p.tags.contains(&"lang".to_string()))));
+    assert_eq!(vec![("lang".to_string(), 2, 0)], counts(&plan));
+
+    let plan = dry_run.merge_tags("rust", &["Rust", "rust"]);
+    assert_eq!(1, plan.pins.len());
+    assert_eq!(
+        vec![("Rust".to_string(), 1, 0), ("rust".to_string(), 1, 2)],
+        counts(&plan)
+    );
+    assert_eq!(plan, dry_run.rename_tag("Rust", "rust"));
+
+    let plan = dry_run.delete("https://dry.run/2");
+    assert_eq!(None, plan.pins[0].after);
+    assert_eq!(
+        vec![("go".to_string(), 1, 0), ("lang".to_string(), 2, 1)],
+        counts(&plan)
+    );
+    assert!(dry_run.delete("https://not.cached/").is_empty());
+
+    let not_cached = Url::parse("https://not.cached/").expect("valid url");
+    let selection: Vec<Url> = pinboard
+        .list_bookmarks()
+        .expect("cached pins")
+        .iter()
+        .map(|p| p.url.clone())
+        .chain([not_cached.clone()])
+        .collect();
+    let plan = dry_run.bulk_edit(&selection, &[BulkOp::AddTag("lang".to_string())]);
+    assert_eq!(1, plan.pins.len());
+    assert_eq!(vec![("lang".to_string(), 2, 3)], counts(&plan));
+    // Bookmarks bulk_edit would fail on are reported
+    assert_eq!(
+        vec![(
+            not_cached.clone(),
+            PatchError::NotFound(not_cached.to_string())
+        )],
+        plan.errors
+    );
+
+    let plan = dry_run
+        .update_pin("https://dry.run/0", &PinPatch::new().toread(true))
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(plan.pins[0].after.as_ref().is_some_and(|p| p.toread));
+    assert!(plan.tags.is_empty());
+
+    let p = PinBuilder::new("https://dry.run/0", "again")
+        .tags(["new"])
+        .into_pin()
+        .expect("valid url");
+    let plan = dry_run
+        .add_pin(p.clone(), AddMode::Merge)
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert_eq!(
+        vec!["rust", "lang", "new"],
+        plan.pins[0].after.as_ref().expect("merged").tags
+    );
+    assert!(dry_run.add_pin(p, AddMode::FailIfExists).is_err());
+
+    // Nothing was changed
+    assert_eq!(3, pinboard.list_bookmarks().expect("cached pins").len());
+}
+
+#[test]
+fn undo_journal_test() {
+    let _ = env_logger::try_init();
+    debug!("undo_journal_test: starting.");
+    let pins: Vec<CachedPin> = [vec!["rust", "lang"], vec!["rust"]]
+        .into_iter()
+        .enumerate()
+        .map(|(i, tags)| {
+            let mut pin = PinBuilder::new(format!("https://undo.me/{i}"), "undo".to_string())
+                .tags(tags)
+                .description("notes")
+                .shared(false)
+                .toread(false)
+                .into_pin()
+                .expect("valid url");
+            pin.time = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+            CachedPin::new(pin)
+        })
+        .collect();
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins, vec![]),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    assert!(pinboard.undo_last().is_err());
+    pinboard.set_journal_path(Some(rand_temp_path().join(JOURNAL_FN)));
+    assert!(pinboard.undo_last().expect("empty journal").is_none());
+
+    let done = |path: &str| {
+        mock("GET", Matcher::Regex(format!("^{path}.*$")))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result_code":"done"}"#)
+            .create()
+    };
+    {
+        let _m1 = done("/posts/delete");
+        let _m2 = done("/tags/delete");
+        pinboard.delete_tag("lang").expect("couldn't delete tag");
+        pinboard
+            .delete("https://undo.me/1")
+            .expect("couldn't delete");
+    }
+    let entries = pinboard.journal_entries().expect("couldn't read journal");
+    assert_eq!(2, entries.len());
+    assert_eq!("delete_tag lang", entries[0].operation);
+    assert_eq!(1, entries[0].before.len());
+    assert_eq!("delete https://undo.me/1", entries[1].operation);
+
+    // Bookmarks are added back as they were, keeping their creation time
+    let restore = |url: &str, tags: &str| {
+        mock("GET", Matcher::Regex(r"^/posts/add.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("url".to_string(), url.to_string()),
+                Matcher::UrlEncoded("tags".to_string(), tags.to_string()),
+                Matcher::UrlEncoded("extended".to_string(), "notes".to_string()),
+                Matcher::UrlEncoded("dt".to_string(), "2020-01-02T03:04:05Z".to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result_code":"done"}"#)
+            .expect(1)
+            .create()
+    };
+    let m1 = restore("https://undo.me/0", "rust lang");
+    let m2 = restore("https://undo.me/1", "rust");
+    let undone = pinboard
+        .undo_last()
+        .expect("couldn't undo")
+        .expect("an entry to undo");
+    assert_eq!(2, undone.id);
+    assert!(undone.undone);
+    m2.assert();
+
+    let undone = pinboard
+        .undo_last()
+        .expect("couldn't undo")
+        .expect("an entry to undo");
+    assert_eq!(1, undone.id);
+    m1.assert();
+    assert!(pinboard.undo_last().expect("nothing to undo").is_none());
+    let e = pinboard.undo(1).expect_err("already undone");
+    assert_eq!(
+        Some(&JournalError::AlreadyUndone(1)),
+        e.downcast_ref::<JournalError>()
+    );
+    let e = pinboard.undo(9).expect_err("no such entry");
+    assert_eq!(
+        Some(&JournalError::NotFound(9)),
+        e.downcast_ref::<JournalError>()
+    );
+
+    // Undoing the addition of a new url deletes it
+    {
+        let _m = done("/posts/add");
+        let p = PinBuilder::new("https://undo.me/new", "new")
+            .into_pin()
+            .expect("valid url");
+        pinboard.add_pin(p).expect("couldn't add pin");
+    }
+    let m = mock("GET", Matcher::Regex(r"^/posts/delete.*$".to_string()))
+        .match_query(Matcher::UrlEncoded(
+            "url".to_string(),
+            "https://undo.me/new".to_string(),
+        ))
+        .with_status(200)
+        .with_header("content-type", "application/json")
+        .with_body(r#"{"result_code":"done"}"#)
+        .expect(1)
+        .create();
+    let undone = pinboard.undo(3).expect("couldn't undo");
+    assert_eq!(vec!["https://undo.me/new"], undone.created);
+    m.assert();
+}
+
+#[test]
+fn journal_failed_call_test() {
+    let _ = env_logger::try_init();
+    debug!("journal_failed_call_test: starting.");
+    let pin = PinBuilder::new("https://fail.me/cached", "cached")
+        .tags(["failing"])
+        .into_pin()
+        .expect("valid url");
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![CachedPin::new(pin.clone())], vec![]),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    pinboard.set_journal_path(Some(rand_temp_path().join(JOURNAL_FN)));
+
+    let fail = |path: &str, key: &str, value: &str| {
+        mock("GET", Matcher::Regex(format!("^{path}.*$")))
+            .match_query(Matcher::UrlEncoded(key.to_string(), value.to_string()))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result_code":"something went wrong"}"#)
+            .expect(1)
+            .create()
+    };
+    let mocks = [
+        fail("/posts/add", "url", "https://fail.me/new"),
+        fail("/posts/delete", "url", "https://fail.me/cached"),
+        fail("/tags/delete", "tag", "failing"),
+        fail("/posts/add", "url", "https://fail.me/cached"),
+    ];
+    let new_pin = PinBuilder::new("https://fail.me/new", "new")
+        .into_pin()
+        .expect("valid url");
+    assert!(pinboard.add_pin(new_pin).is_err());
+    assert!(pinboard.delete("https://fail.me/cached").is_err());
+    assert!(pinboard.delete_tag("failing").is_err());
+    let outcomes = pinboard.bulk_edit(
+        std::slice::from_ref(&pin.url),
+        &[BulkOp::AddTag("more".to_string())],
+    );
+    assert!(outcomes[0].status.is_err());
+    for m in mocks {
+        m.assert();
+    }
+
+    // Nothing happened on the server, so there's nothing to undo
+    assert!(pinboard
+        .journal_entries()
+        .expect("couldn't read journal")
+        .is_empty());
+    assert!(pinboard.undo_last().expect("empty journal").is_none());
+}
+
+#[test]
+fn tag_hierarchy_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_hierarchy_test: starting.");
+    let pins: Vec<CachedPin> = [
+        vec!["lang/rust", "tools"],
+        vec!["Lang/Go"],
+        vec!["language"],
+        vec!["lang"],
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(i, tags)| {
+        CachedPin::new(
+            PinBuilder::new(format!("https://tag.tree/{i}"), "tree".to_string())
+                .tags(tags)
+                .into_pin()
+                .expect("valid url"),
+        )
+    })
+    .collect();
+    let tags = [
+        ("lang/rust", 1),
+        ("Lang/Go", 1),
+        ("language", 1),
+        ("lang", 1),
+        ("tools", 1),
+    ]
+    .into_iter()
+    .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+    .collect();
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins, tags),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+
+    // Hierarchy is opt-in
+    let e = pinboard.tag_tree().expect_err("no separator");
+    assert_eq!(
+        Some(&HierarchyError::Disabled),
+        e.downcast_ref::<HierarchyError>()
+    );
+    pinboard.set_tag_separator(Some('/'));
+
+    let tree = pinboard
+        .tag_tree()
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("tags are cached");
+    assert_eq!("lang", tree[0].path);
+    assert_eq!(3, tree[0].total);
+    assert_eq!(2, tree[0].children.len());
+
+    let rename = |old: &str, new: &str| {
+        mock("GET", Matcher::Regex(r"^/tags/rename.*$".to_string()))
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("old".to_string(), old.to_string()),
+                Matcher::UrlEncoded("new".to_string(), new.to_string()),
+            ]))
+            .with_status(200)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"result":"done"}"#)
+            .expect(1)
+            .create()
+    };
+    let mocks = [
+        rename("lang/rust", "code/rust"),
+        rename("Lang/Go", "code/Go"),
+        rename("lang", "code"),
+    ];
+    let renames = pinboard
+        .rename_tag_subtree("lang", "code")
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert_eq!(3, renames.len());
+    assert!(renames.contains(&("Lang/Go".to_string(), "code/Go".to_string())));
+    for m in mocks {
+        m.assert();
+    }
+
+    // Cached tags & pins are renamed too
+    let tree = pinboard
+        .tag_tree()
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("tags are cached");
+    assert!(tree.iter().all(|n| n.path.to_lowercase() != "lang"));
+    let code = tree.iter().find(|n| n.path == "code").expect("renamed");
+    assert_eq!(3, code.total);
+    assert_eq!(2, code.children.len());
+
+    let pins = pinboard
+        .find_tag_subtree("CODE/")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("pins under code/");
+    let mut urls = pins.iter().map(|p| p.url.as_str()).collect::<Vec<_>>();
+    urls.sort_unstable();
+    assert_eq!(
+        vec![
+            "https://tag.tree/0",
+            "https://tag.tree/1",
+            "https://tag.tree/3"
+        ],
+        urls
+    );
+    assert!(pinboard
+        .find_tag_subtree("lang/")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+}
+
+#[test]
+fn tag_aliases_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_aliases_test: starting.");
+    let pins: Vec<CachedPin> = [vec!["javascript"], vec!["js", "node"], vec!["golang"]]
+        .into_iter()
+        .enumerate()
+        .map(|(i, tags)| {
+            CachedPin::new(
+                PinBuilder::new(format!("https://alias.es/{i}"), "aliases".to_string())
+                    .tags(tags)
+                    .into_pin()
+                    .expect("valid url"),
+            )
+        })
+        .collect();
+    let tags = [("javascript", 1), ("js", 1), ("node", 1), ("golang", 1)]
+        .into_iter()
+        .map(|(t, n)| CachedTag::new(Tag::new(t.to_string(), n)))
+        .collect();
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(pins, tags),
+    )
+    .expect("Can't setup Pinboard")
+    .pinboard;
+    pinboard.enable_fuzzy_search(false);
+    let mut aliases = TagAliases::new();
+    aliases.insert("js", "javascript");
+    aliases.insert("go", "golang");
+    pinboard.set_aliases(aliases);
+
+    let urls = |pins: Vec<&Pin>| {
+        let mut urls = pins.iter().map(|p| p.url.to_string()).collect::<Vec<_>>();
+        urls.sort_unstable();
+        urls
+    };
+    let both = vec!["https://alias.es/0", "https://alias.es/1"];
+    for tag in ["JS", "javascript"] {
+        let pins = pinboard
+            .find_tag(tag)
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("tagged pins");
+        assert_eq!(both, urls(pins));
+    }
+    let pins = pinboard
+        .search(&["javascript"], &[SearchType::TagOnly])
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("tagged pins");
+    assert_eq!(both, urls(pins));
+    let pins = pinboard
+        .search(&["go"], &[SearchType::TitleOnly])
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(pins.is_none());
+
+    let tags = pinboard
+        .search_list_of_tags("js")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("tags");
+    assert_eq!(
+        vec!["javascript", "js"],
+        tags.iter().map(|t| t.0.as_str()).collect::<Vec<_>>()
+    );
+
+    let pin = PinBuilder::new("https://alias.es/new", "new")
+        .tags(["JS", "go", "web", "javascript"])
+        .into_pin()
+        .expect("valid url");
+    assert_eq!(
+        vec!["javascript", "golang", "web"],
+        pinboard.apply_tag_aliases(pin).tags
+    );
+}
+
+#[test]
+fn suggest_tags_local_test() {
+    let _ = env_logger::try_init();
+    debug!("suggest_tags_local_test: starting.");
+    let pinboard = sample_pinboard(vec![]);
+
+    let tags = pinboard
+        .suggest_tags_local("http://notryanb.github.io/rust-blog-series-2.html", "", "")
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    let mut top = tags[..3].to_vec();
+    top.sort_unstable();
+    assert_eq!(vec!["rust", "series", "tutorial"], top);
+
+    assert!(pinboard
+        .suggest_tags_local("https://unknown.org/", "", "")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_empty());
+    assert!(pinboard.suggest_tags_local("not a url", "", "").is_err());
+}
+
+#[test]
+fn related_tags_test() {
+    let _ = env_logger::try_init();
+    debug!("related_tags_test: starting.");
+    let pinboard = sample_pinboard(vec![]);
+
+    let related = pinboard
+        .related_tags("RUST", 5)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("rust has related tags");
+    assert_eq!(
+        vec![("series".to_string(), 153), ("tutorial".to_string(), 153)],
+        related
+    );
+    assert_eq!(
+        1,
+        pinboard
+            .related_tags("rust", 1)
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("rust has related tags")
+            .len()
+    );
+    assert!(pinboard
+        .related_tags("non-existence-tag", 5)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+
+    let graph = pinboard.tag_graph(1).unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(graph.nodes.contains(&TagGraphNode {
+        tag: "rust".to_string(),
+        freq: TagFreq::Used(153),
+    }));
+    assert!(graph.edges.contains(&TagGraphEdge {
+        source: "rust".to_string(),
+        target: "series".to_string(),
+        weight: 153,
+    }));
+    assert!(graph
+        .to_dot()
+        .contains(r#"  "rust" -- "series" [weight=153];"#));
+    assert!(pinboard
+        .tag_graph(154)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .edges
+        .is_empty());
+}
+
+#[test]
+fn tag_report_test() {
+    let _ = env_logger::try_init();
+    debug!("tag_report_test: starting.");
+    let tags = vec![
+        CachedTag::new(Tag::new("rust".to_string(), 10)),
+        CachedTag::new(Tag::new("unused-tag".to_string(), 1)),
+    ];
+    let pinboard = sample_pinboard(tags);
+
+    let since = "2018-01-01T00:00:00Z".parse().expect("valid time");
+    let report = pinboard
+        .tag_report(Some(since))
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert_eq!(vec!["unused-tag"], report.unused);
+    assert!(report.mismatched.contains(&TagCountMismatch {
+        tag: "rust".to_string(),
+        cached: 10,
+        actual: 153,
+    }));
+    assert!(report.stale.contains(&StaleTag {
+        tag: "rust".to_string(),
+        last_used: "2017-09-22T03:59:03Z".parse().expect("valid time"),
+        count: 153,
+    }));
+    assert!(pinboard
+        .tag_report(None)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .stale
+        .is_empty());
+}
+
 #[test]
 fn delete_test() {
     let _ = env_logger::try_init();
@@ -424,6 +1473,7 @@ fn delete_test() {
 fn popular_tags() {
     let _ = env_logger::try_init();
     debug!("popular_tags: starting.");
+    isolate_config_dir();
     let _m1 = mock("GET", Matcher::Regex(r"^/posts/suggest.*$".to_string()))
             .with_status(200)
             .with_header("content-type", "application/json")
@@ -1118,36 +2168,29 @@ fn serde_update_cache() {
         // tags
         assert_eq!(fresh_pin.tags, cached_pin.pin.tags);
         assert_eq!(
-            fresh_pin.tags.nfkd().collect::<String>().to_lowercase(),
+            fresh_pin
+                .tag_string()
+                .nfkd()
+                .collect::<String>()
+                .to_lowercase(),
             cached_pin.tag_list.join(" ")
         );
         // shared
-        assert_eq!(fresh_pin.shared.to_lowercase(), cached_pin.pin.shared);
+        assert_eq!(fresh_pin.shared, cached_pin.pin.shared);
         // toread
-        assert_eq!(fresh_pin.toread.to_lowercase(), cached_pin.pin.toread);
+        assert_eq!(fresh_pin.toread, cached_pin.pin.toread);
         // time
         assert_eq!(fresh_pin.time, cached_pin.pin.time);
 
         // extended
-        if fresh_pin.extended.is_some() {
+        if let Some(ref fresh_extended) = fresh_pin.extended {
             assert!(cached_pin.pin.extended.is_some());
             assert_eq!(
-                fresh_pin
-                    .extended
-                    .as_ref()
-                    .unwrap()
-                    .nfkd()
-                    .collect::<String>(),
+                fresh_extended.nfkd().collect::<String>(),
                 cached_pin.pin.extended.as_ref().unwrap().as_ref()
             );
             assert_eq!(
-                fresh_pin
-                    .extended
-                    .as_ref()
-                    .unwrap()
-                    .nfkd()
-                    .collect::<String>()
-                    .to_lowercase(),
+                fresh_extended.nfkd().collect::<String>().to_lowercase(),
                 cached_pin.extended_lowered.as_ref().unwrap().as_ref()
             );
         } else {
@@ -1183,8 +2226,12 @@ fn test_update_cache() {
         Err(e) => panic!("{e:?}"),
     };
     let mut pinboard = pinboard.pinboard;
-    let Some(pins) = pinboard.cached_data.pins.take() else { panic!("No pins found in cache!") };
-    let Some(tags) = pinboard.cached_data.tags.take() else { panic!("No tags found in cache!") };
+    let Some(pins) = pinboard.cached_data.pins.take() else {
+        panic!("No pins found in cache!")
+    };
+    let Some(tags) = pinboard.cached_data.tags.take() else {
+        panic!("No tags found in cache!")
+    };
     assert!(pins.len() > IDX);
     assert!(tags.len() > IDX);
 
@@ -1224,6 +2271,442 @@ fn test_update_cache() {
     assert_eq!(tags[IDX], pinboard.cached_data.tags.as_ref().unwrap()[IDX]);
 }
 
+#[test]
+fn cache_info_and_clear() {
+    let _ = env_logger::try_init();
+    debug!("cache_info_and_clear: starting.");
+
+    let (_m1, _m2) = create_mockito_servers();
+    let _m3 = start_mockito_server(
+        r"^/posts/update.*$",
+        200,
+        r#"{"update_time":"2018-02-07T01:54:09Z"}"#,
+    );
+    let cache_dir = rand_temp_path();
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), Some(&cache_dir))
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .pinboard;
+
+    let info = pinboard.cache_info();
+    assert_eq!(cache_dir, info.cache_dir);
+    assert_eq!(58, info.pins);
+    assert_eq!(4, info.dropped_pins);
+    assert!(info.tags > 0);
+    assert!(info.last_sync.is_some());
+    // The server's update time isn't known until it's checked
+    assert!(info.server_update.is_none());
+    assert_eq!(CachedData::FORMAT_VERSION, info.format_version);
+    assert!(!info.files.is_empty());
+    assert!(info.files.iter().all(|f| f.path.starts_with(&cache_dir)));
+    assert!(info.total_size() > 0);
+
+    // Unrelated files in the cache folder are left alone
+    let other = cache_dir.join("other.txt");
+    fs::write(&other, "keep me").expect("Couldn't write test file");
+    pinboard.clear_cache().unwrap_or_else(|e| panic!("{e:?}"));
+    let info = pinboard.cache_info();
+    assert!(info.files.is_empty());
+    assert_eq!(0, info.pins);
+    assert!(info.last_sync.is_none());
+    assert!(other.exists());
+    assert!(pinboard.search_items("rust").is_err());
+
+    let last_update = Utc.with_ymd_and_hms(2018, 1, 1, 0, 0, 0).unwrap();
+    assert!(pinboard
+        .is_cache_outdated(last_update)
+        .unwrap_or_else(|e| panic!("{e:?}")));
+    pinboard.update_cache().unwrap_or_else(|e| panic!("{e:?}"));
+    let info = pinboard.cache_info();
+    assert_eq!(58, info.pins);
+    let server_update = Some(Utc.with_ymd_and_hms(2018, 2, 7, 1, 54, 9).unwrap());
+    assert_eq!(server_update, info.server_update);
+
+    // Updating without checking the server again keeps the saved update time
+    let mut pinboard = Pinboard::new(include_str!("api_token.txt"), Some(&cache_dir))
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .pinboard;
+    pinboard.update_cache().unwrap_or_else(|e| panic!("{e:?}"));
+    assert_eq!(server_update, pinboard.cache_info().server_update);
+}
+
+#[test]
+fn memory_store_search() {
+    let _ = env_logger::try_init();
+    debug!("memory_store_search: starting.");
+
+    let tags = vec![
+        CachedTag::new(Tag::new("rust".to_string(), 10)),
+        CachedTag::new(Tag::new("git".to_string(), 1)),
+    ];
+    let count = sample_pins().len();
+
+    // A store holding valid data doesn't need the server nor a cache directory.
+    let pb = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(sample_pins(), vec![]),
+    )
+    .unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(matches!(pb.cache_state, CacheState::Cold));
+    let mut pinboard = sample_pinboard(tags);
+    assert!(pinboard.store().is_valid());
+    assert_eq!(count, pinboard.list_bookmarks().unwrap().len());
+    assert_eq!(2, pinboard.list_tag_pairs().unwrap().len());
+
+    pinboard.enable_fuzzy_search(false);
+    let found = pinboard
+        .search_items("series")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample should have pins about 'series'");
+    assert!(found.iter().all(|p| p.contains("series")));
+    assert!(pinboard
+        .search_items("non-existence-pin")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+
+    let tags = pinboard
+        .search_list_of_tags("gi")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("'git' tag should be found");
+    assert_eq!(1, tags.len());
+}
+
+#[test]
+fn cached_data_search_across_tags() {
+    let _ = env_logger::try_init();
+    debug!("cached_data_search_across_tags: starting.");
+    let pins = vec![CachedPin::new(
+        PinBuilder::new("https://across.tags/", "Little book")
+            .tags(vec!["rust", "macros"])
+            .into_pin()
+            .expect("valid url"),
+    )];
+    let mut cached_data = CachedData::init(Some(&rand_temp_path())).expect("Couldn't init cache");
+    cached_data
+        .replace_all(pins, vec![])
+        .expect("Couldn't write cache");
+    let mut pinboard = Pinboard::with_store(include_str!("api_token.txt"), cached_data)
+        .expect("Can't setup Pinboard")
+        .pinboard;
+    pinboard.enable_fuzzy_search(false);
+
+    // Tags are searched as one space separated string, like MemoryStore does
+    assert_eq!(
+        Some(1),
+        pinboard
+            .search_items("rust macros")
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .map(|found| found.len())
+    );
+    assert!(pinboard
+        .search_items("macros rust")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+}
+
+#[test]
+fn search_query_test() {
+    let _ = env_logger::try_init();
+    debug!("search_query_test: starting.");
+
+    let pinboard = sample_pinboard(vec![]);
+
+    // Plain terms behave like `search` over all fields
+    let expected = pinboard
+        .search(&["series", "blog"], &[])
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    let found = pinboard
+        .search_query("series blog")
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(found.is_some());
+    assert_eq!(expected, found);
+
+    let found = pinboard
+        .search_query("toread:yes -toread:yes")
+        .unwrap_or_else(|e| panic!("{e:?}"));
+    assert!(found.is_none());
+
+    let found = pinboard
+        .search_query("site:theverge.com toread:yes")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample has unread pins from theverge.com");
+    assert!(found
+        .iter()
+        .all(|p| p.url.as_str().contains("theverge.com") && p.toread));
+
+    let err = pinboard
+        .search_query("tag:rust title:")
+        .expect_err("missing value should fail");
+    let err = err
+        .downcast_ref::<QueryError>()
+        .expect("should be a QueryError");
+    assert_eq!(9, err.position);
+    assert_eq!("title:", err.token);
+}
+
+#[test]
+fn find_duplicates_test() {
+    let _ = env_logger::try_init();
+    debug!("find_duplicates_test: starting.");
+
+    let mut pinboard = sample_pinboard(vec![]);
+    // Sample has 4 distinct urls, add a variant of one of them & a url that's bookmarked once
+    let variant = PinBuilder::new(
+        "https://www.nerderati.com/2011/03/17/simplify-your-life-with-an-ssh-config-file?utm_medium=rss",
+        "variant",
+    )
+    .into_pin()
+    .expect("valid url");
+    let single = PinBuilder::new("https://example.com/", "single")
+        .into_pin()
+        .expect("valid url");
+    for pin in [variant, single] {
+        pinboard
+            .cached_data
+            .upsert_pin(CachedPin::new(pin))
+            .unwrap_or_else(|e| panic!("{e:?}"));
+    }
+
+    let groups = pinboard
+        .find_duplicates()
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample has duplicates");
+    assert_eq!(4, groups.len());
+    let (canonical_url, pins) = groups
+        .iter()
+        .find(|(u, _)| u.contains("nerderati"))
+        .expect("nerderati group");
+    assert_eq!(
+        "https://nerderati.com/2011/03/17/simplify-your-life-with-an-ssh-config-file",
+        canonical_url
+    );
+    assert_eq!(154, pins.len());
+    assert!(pins.iter().any(|p| p.title == "variant"));
+    assert!(groups.iter().all(|(u, _)| !u.contains("example.com")));
+
+    let pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![], vec![]),
+    )
+    .unwrap_or_else(|e| panic!("{e:?}"))
+    .pinboard;
+    assert!(pinboard
+        .find_duplicates()
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+}
+
+#[test]
+fn search_ranked_test() {
+    let _ = env_logger::try_init();
+    debug!("search_ranked_test: starting.");
+
+    let tags = ["trust", "Rust-macros", "rust", "go"]
+        .iter()
+        .enumerate()
+        .map(|(i, t)| CachedTag::new(Tag::new((*t).to_string(), 10 - i)))
+        .collect();
+    let mut pinboard = sample_pinboard(tags);
+
+    for fuzzy in [false, true] {
+        pinboard.enable_fuzzy_search(fuzzy);
+        let queries = ["series", "blog"];
+        let mut expected = pinboard
+            .search(&queries, &[])
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("sample should have matches");
+        let ranked = pinboard
+            .search_ranked(&queries, &[])
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("sample should have matches");
+        // Same pins as an unranked search, best score first
+        assert!(ranked.windows(2).all(|w| w[0].0 >= w[1].0));
+        let mut found = ranked.iter().map(|(_, p)| *p).collect::<Vec<&Pin>>();
+        expected.sort_by(|p1, p2| p1.url.cmp(&p2.url));
+        found.sort_by(|p1, p2| p1.url.cmp(&p2.url));
+        assert_eq!(expected, found);
+    }
+
+    // Weights decide which field matters most
+    pinboard.enable_fuzzy_search(false);
+    pinboard.set_rank_weights(RankWeights {
+        title: 0.0,
+        tag: 0.0,
+        url: 1.0,
+        description: 0.0,
+        ..RankWeights::default()
+    });
+    let ranked = pinboard
+        .search_ranked(&["github"], &[])
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample has github pins");
+    assert!(ranked[0].1.url.as_str().contains("github"));
+    assert!(ranked[0].0 > 0.0);
+
+    let tags = pinboard
+        .search_list_of_tags_ranked("rust")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("rust tags should be found");
+    let tags = tags
+        .iter()
+        .map(|(_, t)| t.0.as_str())
+        .collect::<Vec<&str>>();
+    assert_eq!(vec!["rust", "Rust-macros", "trust"], tags);
+}
+
+#[test]
+fn search_highlighted_test() {
+    let _ = env_logger::try_init();
+    debug!("search_highlighted_test: starting.");
+
+    let pin = PinBuilder::new("https://example.com/ﬁle", "Ｒust ﬁle handling")
+        .tags(["rust", "io"])
+        .into_pin()
+        .expect("valid url");
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![CachedPin::new(pin)], vec![]),
+    )
+    .unwrap_or_else(|e| panic!("{e:?}"))
+    .pinboard;
+
+    for fuzzy in [false, true] {
+        pinboard.enable_fuzzy_search(fuzzy);
+        let found = pinboard
+            .search_highlighted(&["RUST", "file"], &[SearchType::TitleOnly])
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("pin should be found");
+        assert_eq!(1, found.len());
+        assert_eq!(
+            vec![FieldMatch {
+                field: MatchField::Title,
+                ranges: vec![0..4, 5..8],
+            }],
+            found[0].matches
+        );
+    }
+
+    pinboard.enable_fuzzy_search(false);
+    let found = pinboard
+        .search_highlighted(&["rust"], &[])
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("pin should be found");
+    let fields = found[0]
+        .matches
+        .iter()
+        .map(|m| m.field)
+        .collect::<Vec<MatchField>>();
+    assert_eq!(vec![MatchField::Title, MatchField::Tags], fields);
+    assert!(pinboard
+        .search_highlighted(&["missing"], &[])
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+}
+
+#[test]
+fn search_decoded_url_test() {
+    let _ = env_logger::try_init();
+    debug!("search_decoded_url_test: starting.");
+
+    let pin = PinBuilder::new("https://example.com/C%2B%2B-guide", "guide")
+        .into_pin()
+        .expect("valid url");
+    let mut pinboard = Pinboard::with_store(
+        include_str!("api_token.txt"),
+        MemoryStore::with_data(vec![CachedPin::new(pin)], vec![]),
+    )
+    .unwrap_or_else(|e| panic!("{e:?}"))
+    .pinboard;
+
+    // Fuzzy & plain searches both match the decoded url
+    for fuzzy in [false, true] {
+        pinboard.enable_fuzzy_search(fuzzy);
+        let found = pinboard
+            .search(&["c++-guide"], &[SearchType::UrlOnly])
+            .unwrap_or_else(|e| panic!("{e:?}"));
+        assert_eq!(Some(1), found.map(|f| f.len()), "fuzzy: {fuzzy}");
+    }
+}
+
+#[test]
+fn search_filter_test() {
+    let _ = env_logger::try_init();
+    debug!("search_filter_test: starting.");
+
+    let mut pinboard = sample_pinboard(vec![]);
+    let all_fields = [
+        SearchType::TitleOnly,
+        SearchType::TagOnly,
+        SearchType::UrlOnly,
+        SearchType::DescriptionOnly,
+    ];
+
+    // Only the Strange Loop bookmark is unread
+    pinboard.set_search_filter(SearchFilter::new().toread(true));
+    let found = pinboard
+        .search_items("s")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample should have unread pins");
+    assert!(found.iter().all(|p| p.toread));
+    assert!(pinboard
+        .search(&["rust"], &all_fields)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+
+    // Domain & time window
+    let y = |year, month| Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0).unwrap();
+    pinboard.set_search_filter(SearchFilter::new().domain("github.io"));
+    for fuzzy in [false, true] {
+        pinboard.enable_fuzzy_search(fuzzy);
+        let found = pinboard
+            .search(&["series"], &all_fields)
+            .unwrap_or_else(|e| panic!("{e:?}"))
+            .expect("sample should have matches");
+        assert!(found.iter().all(|p| p.url.as_str().contains("github.io")));
+    }
+    pinboard.enable_fuzzy_search(false);
+    pinboard.set_search_filter(SearchFilter::new().since(y(2017, 9)).until(y(2017, 9)));
+    assert!(pinboard
+        .search_ranked(&["e"], &all_fields)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+    pinboard.set_search_filter(SearchFilter::new().since(y(2017, 9)).until(y(2017, 10)));
+    let ranked = pinboard
+        .search_ranked(&["e"], &all_fields)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("sample should have matches");
+    assert!(ranked.iter().all(|(_, p)| p.time < y(2017, 10)));
+    assert!(pinboard
+        .search_query("site:theverge.com")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+
+    // Filters passed to `*_filtered` replace the one that is set
+    let unread = SearchFilter::new().toread(true);
+    let found = pinboard
+        .search_filtered(&["strange"], &all_fields, &unread)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .expect("the unread Strange Loop bookmark");
+    assert!(found.iter().all(|p| p.toread));
+    assert!(pinboard
+        .search_items_filtered("strange", &unread)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_some());
+    assert!(pinboard
+        .search_query_filtered("site:theverge.com", &SearchFilter::new())
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_some());
+    assert!(pinboard
+        .search_ranked_filtered(&["rust"], &all_fields, &unread)
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_none());
+
+    // Filters don't apply to exact lookups
+    assert!(pinboard
+        .find_url("https://www.theverge.com/2017/10/2/16404152/strange-loop-2017-programming-talks-youtube")
+        .unwrap_or_else(|e| panic!("{e:?}"))
+        .is_some());
+}
+
 #[cfg(feature = "bench")]
 #[bench]
 fn bench_search_items_openpgp(b: &mut Bencher) {